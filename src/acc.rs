@@ -0,0 +1,301 @@
+//! Analog Comparator Controller (ACC)
+//!
+//! The comparator compares a positive input, one of the first AFEC inputs, with a negative input,
+//! which is an AFEC input, a DAC output, VREFP or the temperature sensor. Its output can be
+//! routed to the PWM fault inputs (`pwm::FaultInput::Acc`) to switch off the outputs without
+//! software intervention, e.g. on over-current.
+//!
+//! Reading the status clears the comparison event, so every read, including the one of
+//! `output`, latches it until it is consumed by `is_pending`.
+
+use core::cell::Cell;
+use core::convert::Infallible;
+
+use embedded_hal::digital::v2::InputPin;
+
+use crate::target_device::ACC;
+use crate::target_device::PMC;
+
+use crate::adc::TemperatureSensor;
+use crate::gpio::{Input, Floating};
+use crate::gpio::pioa::PA21;
+use crate::gpio::piob::{PB1, PB2, PB3};
+use crate::gpio::pioc::PC13;
+use crate::gpio::piod::PD30;
+use crate::gpio::pioe::{PE4, PE5};
+
+// Mode register
+const MR_SELMINUS_SHIFT: u32 = 0;
+const MR_SELPLUS_SHIFT: u32 = 4;
+const MR_ACEN: u32 = 1 << 8;
+const MR_EDGETYP_SHIFT: u32 = 9;
+const MR_INV: u32 = 1 << 12;
+const MR_SELFS: u32 = 1 << 13;
+const MR_FE: u32 = 1 << 14;
+
+// Analog control register
+const ACR_ISEL: u32 = 1 << 0;
+const ACR_HYST_SHIFT: u32 = 1;
+
+// Interrupt status register
+const ISR_CE: u32 = 1 << 0;
+const ISR_SCO: u32 = 1 << 1;
+const ISR_MASK: u32 = 1 << 31;
+
+/// Output edge flagged as comparison event
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Edge {
+	Rising,
+	Falling,
+	Any,
+}
+
+impl Edge {
+	fn bits(&self) -> u32 {
+		match self {
+			Edge::Rising => 0,
+			Edge::Falling => 1,
+			Edge::Any => 2,
+		}
+	}
+}
+
+/// Hysteresis of the comparator
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Hysteresis {
+	None,
+	Low,
+	Medium,
+	High,
+}
+
+/// Signal driving the PWM fault input
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FaultSource {
+	/// The comparator output, the fault is active as long as the output is high
+	Output,
+	/// The comparison event, the fault is active from the selected edge until the status is read
+	///
+	/// Any status read ends the fault, including `output` and the `InputPin` implementation.
+	Event,
+}
+
+pub mod config {
+	use super::{Edge, Hysteresis};
+
+	pub struct AccConfig {
+		pub edge: Edge,
+		pub hysteresis: Hysteresis,
+		/// Trades a higher current consumption for a shorter propagation delay
+		pub high_speed: bool,
+		pub invert: bool,
+	}
+
+	impl AccConfig {
+		pub fn edge(mut self, edge: Edge) -> Self {
+			self.edge = edge;
+			self
+		}
+
+		pub fn hysteresis(mut self, hysteresis: Hysteresis) -> Self {
+			self.hysteresis = hysteresis;
+			self
+		}
+
+		pub fn high_speed(mut self, high_speed: bool) -> Self {
+			self.high_speed = high_speed;
+			self
+		}
+
+		pub fn invert(mut self, invert: bool) -> Self {
+			self.invert = invert;
+			self
+		}
+	}
+
+	impl Default for AccConfig {
+		fn default() -> AccConfig {
+			AccConfig {
+				edge: Edge::Any,
+				hysteresis: Hysteresis::None,
+				high_speed: true,
+				invert: false,
+			}
+		}
+	}
+}
+
+/// Positive input of the comparator
+pub trait PositiveInput {
+	const SELPLUS: u32;
+}
+
+/// Negative input of the comparator
+pub trait NegativeInput {
+	const SELMINUS: u32;
+}
+
+/// Voltage reference VREFP
+pub struct Vrefp;
+/// Output of DAC channel 0
+pub struct Dac0;
+/// Output of DAC channel 1
+pub struct Dac1;
+
+macro_rules! acc_inputs {
+	($Input:ident, $SEL:ident: [$($IN:ty: $sel:expr),*]) => {
+		$(
+			impl $Input for $IN {
+				const $SEL: u32 = $sel;
+			}
+		)*
+	}
+}
+
+acc_inputs!(PositiveInput, SELPLUS: [
+	PD30<Input<Floating>>: 0,
+	PA21<Input<Floating>>: 1,
+	PB3<Input<Floating>>: 2,
+	PE5<Input<Floating>>: 3,
+	PE4<Input<Floating>>: 4,
+	PB2<Input<Floating>>: 5,
+	PB1<Input<Floating>>: 6,
+	PC13<Input<Floating>>: 7
+]);
+
+acc_inputs!(NegativeInput, SELMINUS: [
+	TemperatureSensor: 0,
+	Vrefp: 1,
+	Dac0: 2,
+	Dac1: 3,
+	PD30<Input<Floating>>: 4,
+	PA21<Input<Floating>>: 5,
+	PB3<Input<Floating>>: 6,
+	PE5<Input<Floating>>: 7
+]);
+
+/// ACC abstraction
+pub struct Acc<PLUS, MINUS> {
+	acc: ACC,
+	plus: PLUS,
+	minus: MINUS,
+	/// Comparison event read from ISR but not consumed yet
+	event: Cell<bool>,
+}
+
+impl<PLUS: PositiveInput, MINUS: NegativeInput> Acc<PLUS, MINUS> {
+	/// Configures and enables the comparator
+	pub fn new(acc: ACC, plus: PLUS, minus: MINUS, config: config::AccConfig, pmc: &mut PMC) -> Self {
+		//enable peripheral clock in pmc
+		pmc.pmc_pcer1.write(|w| w.pid33().set_bit() );
+
+		//reset peripheral
+		acc.acc_cr.write(|w| w.swrst().set_bit());
+
+		let hyst = match config.hysteresis {
+			Hysteresis::None => 0,
+			Hysteresis::Low => 1,
+			Hysteresis::Medium => 2,
+			Hysteresis::High => 3,
+		};
+		let mut acr = hyst << ACR_HYST_SHIFT;
+		if config.high_speed {
+			acr |= ACR_ISEL;
+		}
+		acc.acc_acr.write(|w| unsafe { w.bits(acr) });
+
+		let mut mr = PLUS::SELPLUS << MR_SELPLUS_SHIFT
+			| MINUS::SELMINUS << MR_SELMINUS_SHIFT
+			| config.edge.bits() << MR_EDGETYP_SHIFT
+			| MR_ACEN;
+		if config.invert {
+			mr |= MR_INV;
+		}
+		acc.acc_mr.write(|w| unsafe { w.bits(mr) });
+		acc.acc_idr.write(|w| unsafe { w.bits(ISR_CE) });
+
+		Acc { acc, plus, minus, event: Cell::new(false) }
+	}
+
+	/// Returns if the positive input is above the negative one, inverted if configured
+	///
+	/// Returns `None` while the output is not valid yet after enabling or reconfiguration.
+	pub fn output(&self) -> Option<bool> {
+		let isr = self.read_status();
+		if isr & ISR_MASK != 0 {
+			None
+		} else {
+			Some(isr & ISR_SCO != 0)
+		}
+	}
+
+	/// Changes the edge flagged as comparison event
+	pub fn set_edge(&mut self, edge: Edge) {
+		self.acc.acc_mr.modify(|r, w| unsafe {
+			w.bits(r.bits() & !(0x3 << MR_EDGETYP_SHIFT) | edge.bits() << MR_EDGETYP_SHIFT)
+		});
+	}
+
+	/// Starts the comparison event interrupt
+	pub fn listen(&mut self) {
+		self.acc.acc_ier.write(|w| unsafe { w.bits(ISR_CE) });
+	}
+
+	/// Stops the comparison event interrupt
+	pub fn unlisten(&mut self) {
+		self.acc.acc_idr.write(|w| unsafe { w.bits(ISR_CE) });
+	}
+
+	/// Returns if a comparison event occurred since the last call and clears it
+	///
+	/// With `FaultSource::Event` this also clears the fault of the PWM.
+	pub fn is_pending(&mut self) -> bool {
+		let _ = self.read_status();
+		self.event.replace(false)
+	}
+
+	/// Reads ISR and latches the comparison event its read clears
+	fn read_status(&self) -> u32 {
+		let isr = self.acc.acc_isr.read().bits();
+		if isr & ISR_CE != 0 {
+			self.event.set(true);
+		}
+		isr
+	}
+
+	/// Drives the ACC input of the PWM fault units with `source`
+	pub fn enable_fault(&mut self, source: FaultSource) {
+		self.acc.acc_mr.modify(|r, w| unsafe {
+			let mut bits = r.bits() & !MR_SELFS | MR_FE;
+			if source == FaultSource::Output {
+				bits |= MR_SELFS;
+			}
+			w.bits(bits)
+		});
+	}
+
+	/// Stops driving the PWM fault units
+	pub fn disable_fault(&mut self) {
+		self.acc.acc_mr.modify(|r, w| unsafe { w.bits(r.bits() & !MR_FE) });
+	}
+
+	/// Disables the comparator and releases the ACC and inputs
+	pub fn release(self) -> (ACC, PLUS, MINUS) {
+		self.acc.acc_idr.write(|w| unsafe { w.bits(ISR_CE) });
+		self.acc.acc_mr.write(|w| unsafe { w.bits(0) });
+		(self.acc, self.plus, self.minus)
+	}
+}
+
+impl<PLUS: PositiveInput, MINUS: NegativeInput> InputPin for Acc<PLUS, MINUS> {
+	type Error = Infallible;
+
+	/// The output is reported low while it is not valid
+	fn is_high(&self) -> Result<bool, Infallible> {
+		Ok(self.output().unwrap_or(false))
+	}
+
+	fn is_low(&self) -> Result<bool, Infallible> {
+		self.is_high().map(|high| !high)
+	}
+}
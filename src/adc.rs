@@ -0,0 +1,693 @@
+//! Analog Front-End Controller (AFEC)
+//!
+//! Every AFEC converts 12 channels with 12 bits, averaging extends the resolution up to 16 bits.
+//! The pins are connected to the AFEC as soon as their channel is enabled, they should be left
+//! as floating inputs.
+//!
+//! Besides software started conversions, conversions of all enabled channels can be triggered by
+//! hardware events or run continuously, the results are then transferred by DMA.
+
+use embedded_hal::adc::{Channel, OneShot};
+
+use crate::target_device::{AFEC0, AFEC1};
+use crate::target_device::PMC;
+
+use crate::gpio::{Input, Floating};
+use crate::gpio::pioa::{PA17, PA18, PA19, PA20, PA21};
+use crate::gpio::piob::{PB0, PB1, PB2, PB3};
+use crate::gpio::pioc::{PC0, PC12, PC13, PC15, PC26, PC27, PC29, PC30, PC31};
+use crate::gpio::piod::{PD30};
+use crate::gpio::pioe::{PE0, PE3, PE4, PE5};
+use crate::clock_gen::Clocks;
+use crate::time::Hertz;
+use crate::dma::{Channel as DmaChannel, ChannelId, DescriptorView0, DmaPeripheral, DoubleBufferTransfer, WriteBuffer};
+
+/// Number of channels of an AFEC
+pub const CHANNELS: u8 = 12;
+
+/// Maximum AFE clock frequency
+const MAX_AFE_CLOCK: u32 = 40_000_000;
+
+// Mode register
+const MR_TRGEN: u32 = 1 << 0;
+const MR_TRGSEL_SHIFT: u32 = 1;
+const MR_TRGSEL_MASK: u32 = 0x7 << MR_TRGSEL_SHIFT;
+const MR_FREERUN: u32 = 1 << 7;
+const MR_STARTUP_SUT64: u32 = 4 << 16;
+const MR_ONE: u32 = 1 << 23;
+const MR_TRACKTIM_SHIFT: u32 = 24;
+const MR_USEQ: u32 = 1 << 31;
+
+// Extended mode register
+const EMR_CMPMODE_MASK: u32 = 0x3;
+const EMR_CMPSEL_SHIFT: u32 = 3;
+const EMR_CMPSEL_MASK: u32 = 0x1f << EMR_CMPSEL_SHIFT;
+const EMR_CMPALL: u32 = 1 << 9;
+const EMR_RES_SHIFT: u32 = 16;
+const EMR_TAG: u32 = 1 << 24;
+const EMR_STM: u32 = 1 << 25;
+
+// Analog control register, bias current and programmable gain amplifiers
+const ACR_PGA0EN: u32 = 1 << 2;
+const ACR_PGA1EN: u32 = 1 << 3;
+const ACR_IBCTL: u32 = 1 << 8;
+
+// Status flags of the interrupt status register
+const ISR_DRDY: u32 = 1 << 24;
+const ISR_GOVRE: u32 = 1 << 25;
+const ISR_COMPE: u32 = 1 << 26;
+
+// Last converted data register with channel tag
+const LCDR_CHNB_SHIFT: u32 = 24;
+
+/// Offset which centers single ended conversions in the input range
+const DEFAULT_OFFSET: u16 = 0x200;
+
+/// ADC error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+	/// A conversion result was overwritten before it was read
+	Overrun,
+	/// No sequence was configured or the buffer does not match its length
+	InvalidSequence,
+	/// The channel number is not below `CHANNELS`
+	InvalidChannel,
+	#[doc(hidden)]
+	_Extensible,
+}
+
+/// Conversion resolution, more than 12 bits are reached by averaging
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Resolution {
+	Bits12,
+	/// Average of 4 samples
+	Bits13,
+	/// Average of 16 samples
+	Bits14,
+	/// Average of 64 samples
+	Bits15,
+	/// Average of 256 samples
+	Bits16,
+}
+
+impl Resolution {
+	fn bits(&self) -> u32 {
+		match self {
+			Resolution::Bits12 => 0,
+			Resolution::Bits13 => 2,
+			Resolution::Bits14 => 3,
+			Resolution::Bits15 => 4,
+			Resolution::Bits16 => 5,
+		}
+	}
+}
+
+/// Conversion trigger
+///
+/// The timer triggers are TIOA0 - TIOA2 of TC0 for AFEC0 and of TC1 for AFEC1, see
+/// `timer::Timer::trigger_output`. The PWM event lines are the ones of PWM0 for AFEC0 and of
+/// PWM1 for AFEC1.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Trigger {
+	/// Conversions are started by `start` or the blocking reads
+	Software,
+	/// Converts continuously
+	FreeRun,
+	/// AFE_ADTRG pin
+	Pin,
+	Tioa0,
+	Tioa1,
+	Tioa2,
+	PwmEventLine0,
+	PwmEventLine1,
+	/// Analog comparator (ACC)
+	Comparator,
+}
+
+/// Comparison window of the conversion results
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Window {
+	Below(u16),
+	Above(u16),
+	Inside(u16, u16),
+	Outside(u16, u16),
+}
+
+/// Interrupt event
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+	/// A conversion result is ready
+	DataReady,
+	/// A result was overwritten before it was read
+	Overrun,
+	/// A result matched the comparison window
+	Comparison,
+}
+
+impl Event {
+	fn mask(&self) -> u32 {
+		match self {
+			Event::DataReady => ISR_DRDY,
+			Event::Overrun => ISR_GOVRE,
+			Event::Comparison => ISR_COMPE,
+		}
+	}
+}
+
+/// Conversion result tagged with its channel
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Sample {
+	pub channel: u8,
+	pub value: u16,
+}
+
+impl Sample {
+	/// Decodes a raw value of the last converted data register, e.g. transferred by DMA
+	pub fn from_raw(raw: u32) -> Self {
+		Sample {
+			channel: ((raw >> LCDR_CHNB_SHIFT) & 0xf) as u8,
+			value: raw as u16,
+		}
+	}
+}
+
+/// Programmable gain of a channel
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Gain {
+	X1,
+	X2,
+	X4,
+}
+
+impl Gain {
+	fn bits(&self) -> u32 {
+		match self {
+			Gain::X1 => 0,
+			Gain::X2 => 1,
+			Gain::X4 => 2,
+		}
+	}
+}
+
+pub mod config {
+	use super::{Gain, Resolution, DEFAULT_OFFSET};
+	use crate::time::Hertz;
+
+	pub struct AdcConfig {
+		/// Target AFE clock, the prescaler selects the closest clock not above it
+		pub clock: Hertz,
+		pub resolution: Resolution,
+		/// Tracking time in AFE clock cycles minus 15, at most 15
+		pub tracking_time: u8,
+	}
+
+	impl AdcConfig {
+		pub fn clock(mut self, clock: Hertz) -> Self {
+			self.clock = clock;
+			self
+		}
+
+		pub fn resolution(mut self, resolution: Resolution) -> Self {
+			self.resolution = resolution;
+			self
+		}
+
+		pub fn tracking_time(mut self, tracking_time: u8) -> Self {
+			self.tracking_time = tracking_time;
+			self
+		}
+	}
+
+	impl Default for AdcConfig {
+		fn default() -> AdcConfig {
+			AdcConfig {
+				clock: Hertz(20_000_000),
+				resolution: Resolution::Bits12,
+				tracking_time: 15,
+			}
+		}
+	}
+
+	/// Per channel configuration
+	pub struct ChannelConfig {
+		pub gain: Gain,
+		/// Analog offset (AOFF), 10 bits
+		pub offset: u16,
+		/// Converts the difference of the channel and the next odd channel, the result is signed
+		pub differential: bool,
+	}
+
+	impl ChannelConfig {
+		pub fn gain(mut self, gain: Gain) -> Self {
+			self.gain = gain;
+			self
+		}
+
+		pub fn offset(mut self, offset: u16) -> Self {
+			self.offset = offset;
+			self
+		}
+
+		pub fn differential(mut self) -> Self {
+			self.differential = true;
+			self
+		}
+	}
+
+	impl Default for ChannelConfig {
+		fn default() -> ChannelConfig {
+			ChannelConfig {
+				gain: Gain::X1,
+				offset: DEFAULT_OFFSET,
+				differential: false,
+			}
+		}
+	}
+
+	#[derive(Debug)]
+	pub struct InvalidConfig;
+}
+
+/// Calculates the AFE clock prescaler for the fastest clock not above `target`
+pub fn calc_prescaler(mck: Hertz, target: Hertz) -> Option<u8> {
+	let target = core::cmp::min(target.0, MAX_AFE_CLOCK);
+	if target == 0 {
+		return None;
+	}
+	// f_AFE = mck / (PRESCAL + 1)
+	let div = (mck.0 + target - 1) / target;
+	if div == 0 || div > 256 {
+		return None;
+	}
+	Some((div - 1) as u8)
+}
+
+fn check_channel(ch: u8) -> Result<(), Error> {
+	if ch < CHANNELS {
+		Ok(())
+	} else {
+		Err(Error::InvalidChannel)
+	}
+}
+
+/// AFEC abstraction
+pub struct Adc<AFEC> {
+	afec: AFEC,
+	clock: Hertz,
+	sequence_len: usize,
+}
+
+/// AFEC transferring its results by DMA into two alternating buffers
+pub struct AdcDma<AFEC, CH: ChannelId, BUF: WriteBuffer> {
+	adc: Adc<AFEC>,
+	transfer: DoubleBufferTransfer<CH, BUF>,
+}
+
+impl<AFEC, CH: ChannelId, BUF: WriteBuffer<Word = u32>> AdcDma<AFEC, CH, BUF> {
+	/// Calls `f` with the next filled buffer, returns `None` while it is still being filled
+	pub fn read<R, F>(&mut self, f: F) -> Option<R>
+	where
+		F: FnOnce(&[u32]) -> R,
+	{
+		self.transfer.read(f)
+	}
+
+	/// Stops the transfer, the trigger stays configured
+	pub fn stop(self) -> (Adc<AFEC>, [BUF; 2], &'static mut [DescriptorView0; 2], DmaChannel<CH>) {
+		let (buffers, descriptors, channel) = self.transfer.stop();
+		(self.adc, buffers, descriptors, channel)
+	}
+}
+
+/// Internal temperature sensor, connected to channel 11 of AFEC0
+pub struct TemperatureSensor;
+
+macro_rules! adc_pins {
+	($($AFECX:ident: [$($PIN:ty: $ch:expr),*])+) => {
+		$(
+			$(
+				impl Channel<Adc<$AFECX>> for $PIN {
+					type ID = u8;
+
+					fn channel() -> u8 {
+						$ch
+					}
+				}
+			)*
+		)+
+	}
+}
+
+adc_pins! {
+	AFEC0: [
+		PD30<Input<Floating>>: 0,
+		PA21<Input<Floating>>: 1,
+		PB3<Input<Floating>>: 2,
+		PE5<Input<Floating>>: 3,
+		PE4<Input<Floating>>: 4,
+		PB2<Input<Floating>>: 5,
+		PA17<Input<Floating>>: 6,
+		PA18<Input<Floating>>: 7,
+		PA19<Input<Floating>>: 8,
+		PA20<Input<Floating>>: 9,
+		PB0<Input<Floating>>: 10,
+		TemperatureSensor: 11
+	]
+	AFEC1: [
+		PB1<Input<Floating>>: 0,
+		PC13<Input<Floating>>: 1,
+		PC15<Input<Floating>>: 2,
+		PC12<Input<Floating>>: 3,
+		PC29<Input<Floating>>: 4,
+		PC30<Input<Floating>>: 5,
+		PC31<Input<Floating>>: 6,
+		PC26<Input<Floating>>: 7,
+		PC27<Input<Floating>>: 8,
+		PC0<Input<Floating>>: 9,
+		PE3<Input<Floating>>: 10,
+		PE0<Input<Floating>>: 11
+	]
+}
+
+macro_rules! adc_hal {
+	($( $AFECX:ident: (
+			$afecX:ident,
+			$en_reg:ident,
+			$perid:ident,
+			$dma:ident
+		),
+	)+) => {
+		$(
+			impl Adc<$AFECX> {
+				/// Configures an AFEC for software triggered conversions
+				pub fn $afecX(
+					afec: $AFECX,
+					config: config::AdcConfig,
+					clocks: &Clocks,
+					pmc: &mut PMC,
+				) -> Result<Self, config::InvalidConfig> {
+					let prescal = calc_prescaler(clocks.mck(), config.clock).ok_or(config::InvalidConfig)?;
+					if config.tracking_time > 15 {
+						return Err(config::InvalidConfig);
+					}
+
+					//enable peripheral clock in pmc
+					pmc.$en_reg.write(|w| w.$perid().set_bit() );
+
+					//reset peripheral
+					afec.afec_cr.write(|w| w.swrst().set_bit());
+
+					afec.afec_mr.write(|w| unsafe {
+						w.bits((prescal as u32) << 8
+							| MR_STARTUP_SUT64
+							| MR_ONE
+							| (config.tracking_time as u32) << MR_TRACKTIM_SHIFT)
+					});
+					// averaged results need only a single trigger
+					afec.afec_emr.write(|w| unsafe {
+						w.bits(config.resolution.bits() << EMR_RES_SHIFT | EMR_TAG | EMR_STM)
+					});
+					afec.afec_acr.write(|w| unsafe { w.bits(ACR_IBCTL | ACR_PGA0EN | ACR_PGA1EN) });
+
+					afec.afec_chdr.write(|w| unsafe { w.bits(0xfff) });
+					afec.afec_idr.write(|w| unsafe { w.bits(0xffff_ffff) });
+
+					let mut adc = Adc {
+						afec,
+						clock: Hertz(clocks.mck().0 / (prescal as u32 + 1)),
+						sequence_len: 0,
+					};
+					for ch in 0..CHANNELS {
+						adc.configure_channel(ch, config::ChannelConfig::default())?;
+					}
+
+					Ok(adc)
+				}
+
+				/// Releases the AFEC
+				pub fn release(self) -> $AFECX {
+					self.afec.afec_chdr.write(|w| unsafe { w.bits(0xfff) });
+					self.afec
+				}
+
+				/// AFE clock frequency
+				pub fn clock(&self) -> Hertz {
+					self.clock
+				}
+
+				/// Configures gain, offset and differential mode of channel `ch`
+				pub fn configure_channel(&mut self, ch: u8, config: config::ChannelConfig) -> Result<(), config::InvalidConfig> {
+					if ch >= CHANNELS || config.offset > 0x3ff {
+						return Err(config::InvalidConfig);
+					}
+					let shift = 2 * ch as u32;
+					let mask = 1u32 << ch;
+
+					self.afec.afec_cgr.modify(|r, w| unsafe {
+						w.bits(r.bits() & !(0x3 << shift) | config.gain.bits() << shift)
+					});
+					self.afec.afec_diffr.modify(|r, w| unsafe {
+						if config.differential {
+							w.bits(r.bits() | mask)
+						} else {
+							w.bits(r.bits() & !mask)
+						}
+					});
+					self.afec.afec_cselr.write(|w| unsafe { w.bits(ch as u32) });
+					self.afec.afec_cocr.write(|w| unsafe { w.bits(config.offset as u32) });
+
+					Ok(())
+				}
+
+				/// Configures the channel of `pin`, see `configure_channel`
+				pub fn configure<PIN>(&mut self, _pin: &PIN, config: config::ChannelConfig) -> Result<(), config::InvalidConfig>
+				where
+					PIN: Channel<Self, ID = u8>,
+				{
+					self.configure_channel(PIN::channel(), config)
+				}
+
+				/// Sets the channels converted by `read_sequence`, in order, up to 12 entries
+				///
+				/// A channel may appear multiple times in the sequence.
+				pub fn configure_sequence(&mut self, channels: &[u8]) -> Result<(), config::InvalidConfig> {
+					if channels.is_empty() || channels.len() > CHANNELS as usize || channels.iter().any(|&ch| ch >= CHANNELS) {
+						return Err(config::InvalidConfig);
+					}
+
+					let mut seq1 = 0u32;
+					let mut seq2 = 0u32;
+					for (slot, &ch) in channels.iter().enumerate() {
+						if slot < 8 {
+							seq1 |= (ch as u32) << (4 * slot);
+						} else {
+							seq2 |= (ch as u32) << (4 * (slot - 8));
+						}
+					}
+					self.afec.afec_seq1r.write(|w| unsafe { w.bits(seq1) });
+					self.afec.afec_seq2r.write(|w| unsafe { w.bits(seq2) });
+					self.sequence_len = channels.len();
+
+					Ok(())
+				}
+
+				/// Converts the configured sequence once, `buffer` receives one result per entry
+				pub fn read_sequence(&mut self, buffer: &mut [u16]) -> Result<(), Error> {
+					if self.sequence_len == 0 || buffer.len() != self.sequence_len {
+						return Err(Error::InvalidSequence);
+					}
+
+					// with USEQ the channel enables select the sequence slots
+					self.afec.afec_mr.modify(|r, w| unsafe { w.bits(r.bits() | MR_USEQ) });
+					self.afec.afec_cher.write(|w| unsafe { w.bits((1 << self.sequence_len) - 1) });
+					let _ = self.afec.afec_isr.read().bits();
+					let _ = self.afec.afec_lcdr.read().bits();
+
+					self.afec.afec_cr.write(|w| w.start().set_bit());
+
+					let mut result = Ok(());
+					for value in buffer.iter_mut() {
+						let isr = self.wait_data_ready();
+						if isr & ISR_GOVRE != 0 {
+							result = Err(Error::Overrun);
+						}
+						*value = self.afec.afec_lcdr.read().bits() as u16;
+					}
+
+					self.afec.afec_chdr.write(|w| unsafe { w.bits(0xfff) });
+					self.afec.afec_mr.modify(|r, w| unsafe { w.bits(r.bits() & !MR_USEQ) });
+
+					result
+				}
+
+				/// Converts channel `ch` once
+				pub fn read_channel(&mut self, ch: u8) -> Result<u16, Error> {
+					check_channel(ch)?;
+					self.afec.afec_cher.write(|w| unsafe { w.bits(1 << ch) });
+					let _ = self.afec.afec_isr.read().bits();
+					let _ = self.afec.afec_lcdr.read().bits();
+
+					self.afec.afec_cr.write(|w| w.start().set_bit());
+					let isr = self.wait_data_ready();
+					let value = self.afec.afec_lcdr.read().bits() as u16;
+
+					self.afec.afec_chdr.write(|w| unsafe { w.bits(1 << ch) });
+
+					if isr & ISR_GOVRE != 0 {
+						Err(Error::Overrun)
+					} else {
+						Ok(value)
+					}
+				}
+
+				/// Selects what starts conversions of the enabled channels
+				pub fn set_trigger(&mut self, trigger: Trigger) {
+					let (trgen, trgsel, freerun) = match trigger {
+						Trigger::Software => (false, 0, false),
+						Trigger::FreeRun => (false, 0, true),
+						Trigger::Pin => (true, 0, false),
+						Trigger::Tioa0 => (true, 1, false),
+						Trigger::Tioa1 => (true, 2, false),
+						Trigger::Tioa2 => (true, 3, false),
+						Trigger::PwmEventLine0 => (true, 4, false),
+						Trigger::PwmEventLine1 => (true, 5, false),
+						Trigger::Comparator => (true, 6, false),
+					};
+
+					self.afec.afec_mr.modify(|r, w| unsafe {
+						let mut bits = r.bits() & !(MR_TRGEN | MR_TRGSEL_MASK | MR_FREERUN);
+						bits |= trgsel << MR_TRGSEL_SHIFT;
+						if trgen {
+							bits |= MR_TRGEN;
+						}
+						if freerun {
+							bits |= MR_FREERUN;
+						}
+						w.bits(bits)
+					});
+				}
+
+				/// Includes channel `ch` in triggered conversions
+				pub fn enable_channel(&mut self, ch: u8) -> Result<(), Error> {
+					check_channel(ch)?;
+					self.afec.afec_cher.write(|w| unsafe { w.bits(1 << ch) });
+					Ok(())
+				}
+
+				/// Excludes channel `ch` from triggered conversions
+				pub fn disable_channel(&mut self, ch: u8) -> Result<(), Error> {
+					check_channel(ch)?;
+					self.afec.afec_chdr.write(|w| unsafe { w.bits(1 << ch) });
+					Ok(())
+				}
+
+				/// Starts a conversion of all enabled channels
+				pub fn start(&mut self) {
+					self.afec.afec_cr.write(|w| w.start().set_bit());
+				}
+
+				/// Returns the next result tagged with its channel
+				pub fn read_tagged(&mut self) -> nb::Result<Sample, Error> {
+					let isr = self.afec.afec_isr.read().bits();
+					if isr & ISR_DRDY == 0 {
+						return Err(nb::Error::WouldBlock);
+					}
+					let sample = Sample::from_raw(self.afec.afec_lcdr.read().bits());
+					if isr & ISR_GOVRE != 0 {
+						Err(nb::Error::Other(Error::Overrun))
+					} else {
+						Ok(sample)
+					}
+				}
+
+				/// Flags results of channel `ch`, or of all channels if `None`, matching `window`
+				pub fn enable_comparison(&mut self, window: Window, ch: Option<u8>) -> Result<(), Error> {
+					if let Some(ch) = ch {
+						check_channel(ch)?;
+					}
+					let (mode, low, high) = match window {
+						Window::Below(low) => (0, low, 0),
+						Window::Above(high) => (1, 0, high),
+						Window::Inside(low, high) => (2, low, high),
+						Window::Outside(low, high) => (3, low, high),
+					};
+
+					self.afec.afec_cwr.write(|w| unsafe { w.bits((high as u32) << 16 | low as u32) });
+					self.afec.afec_emr.modify(|r, w| unsafe {
+						let mut bits = r.bits() & !(EMR_CMPMODE_MASK | EMR_CMPSEL_MASK | EMR_CMPALL);
+						bits |= mode;
+						match ch {
+							Some(ch) => bits |= (ch as u32) << EMR_CMPSEL_SHIFT,
+							None => bits |= EMR_CMPALL,
+						}
+						w.bits(bits)
+					});
+					Ok(())
+				}
+
+				/// Starts an interrupt event
+				pub fn listen(&mut self, event: Event) {
+					self.afec.afec_ier.write(|w| unsafe { w.bits(event.mask()) });
+				}
+
+				/// Stops an interrupt event
+				pub fn unlisten(&mut self, event: Event) {
+					self.afec.afec_idr.write(|w| unsafe { w.bits(event.mask()) });
+				}
+
+				/// Returns if `event` is pending, clears the comparison and overrun flags
+				pub fn is_pending(&mut self, event: Event) -> bool {
+					self.afec.afec_isr.read().bits() & event.mask() != 0
+				}
+
+				/// Transfers the results of the enabled channels into `buffers` alternately
+				///
+				/// The results keep their channel tag, see `Sample::from_raw`. Conversions are
+				/// started by the configured trigger.
+				pub fn into_dma<B, CH>(
+					self,
+					buffers: [B; 2],
+					descriptors: &'static mut [DescriptorView0; 2],
+					channel: DmaChannel<CH>,
+				) -> AdcDma<$AFECX, CH, B>
+				where
+					B: WriteBuffer<Word = u32>,
+					CH: ChannelId,
+				{
+					let _ = self.afec.afec_isr.read().bits();
+					let _ = self.afec.afec_lcdr.read().bits();
+
+					let lcdr = &self.afec.afec_lcdr as *const _ as u32;
+					let transfer = channel.double_buffer_from_peripheral(DmaPeripheral::$dma, lcdr, buffers, descriptors);
+
+					AdcDma { adc: self, transfer }
+				}
+
+				fn wait_data_ready(&self) -> u32 {
+					loop {
+						let isr = self.afec.afec_isr.read().bits();
+						if isr & ISR_DRDY != 0 {
+							return isr;
+						}
+					}
+				}
+			}
+
+			impl<PIN> OneShot<Adc<$AFECX>, u16, PIN> for Adc<$AFECX>
+			where
+				PIN: Channel<Adc<$AFECX>, ID = u8>,
+			{
+				type Error = Error;
+
+				fn read(&mut self, _pin: &mut PIN) -> nb::Result<u16, Error> {
+					self.read_channel(PIN::channel()).map_err(nb::Error::Other)
+				}
+			}
+		)+
+	}
+}
+
+adc_hal! {
+	AFEC0 : (afec0, pmc_pcer0, pid29, Afec0),
+	AFEC1 : (afec1, pmc_pcer1, pid40, Afec1),
+}
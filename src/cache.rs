@@ -0,0 +1,65 @@
+//! Data cache maintenance for memory shared with bus masters or the QSPI memory region
+
+use cortex_m::asm;
+use cortex_m::peripheral::CBP;
+
+/// Size of a D-cache line of the Cortex-M7
+const LINE_SIZE: usize = 32;
+
+// D-cache geometry of the SAM E70, 16 KiB in 4 ways
+const DCACHE_SIZE: usize = 16 * 1024;
+const DCACHE_WAYS: u32 = 4;
+const DCACHE_SETS: u32 = (DCACHE_SIZE / LINE_SIZE) as u32 / DCACHE_WAYS;
+
+/// Invalidates the D-cache lines covering `size` bytes from `addr`
+///
+/// Dirty lines are discarded, so this must only be used on write-through memory or on memory
+/// that has not been written by the CPU. Ranges larger than the cache clean and invalidate the
+/// whole cache instead.
+pub(crate) fn invalidate_dcache(addr: usize, size: usize) {
+	if size >= DCACHE_SIZE {
+		return clean_invalidate_all();
+	}
+	for_each_line(addr, size, |line| {
+		// NOTE(unsafe) write-only maintenance register, only affects this line
+		unsafe { (*CBP::ptr()).dcimvac.write(line) }
+	});
+}
+
+/// Writes back and invalidates the D-cache lines covering `size` bytes from `addr`
+pub(crate) fn clean_invalidate_dcache(addr: usize, size: usize) {
+	if size >= DCACHE_SIZE {
+		return clean_invalidate_all();
+	}
+	for_each_line(addr, size, |line| {
+		// NOTE(unsafe) write-only maintenance register, only affects this line
+		unsafe { (*CBP::ptr()).dccimvac.write(line) }
+	});
+}
+
+fn for_each_line<F: Fn(u32)>(addr: usize, size: usize, op: F) {
+	if size == 0 {
+		return;
+	}
+
+	asm::dsb();
+	let mut line = addr & !(LINE_SIZE - 1);
+	while line < addr + size {
+		op(line as u32);
+		line += LINE_SIZE;
+	}
+	asm::dsb();
+	asm::isb();
+}
+
+fn clean_invalidate_all() {
+	asm::dsb();
+	for set in 0..DCACHE_SETS {
+		for way in 0..DCACHE_WAYS {
+			// NOTE(unsafe) write-only maintenance register, cleaning keeps the memory contents
+			unsafe { (*CBP::ptr()).dccisw.write(way << 30 | set << 5) };
+		}
+	}
+	asm::dsb();
+	asm::isb();
+}
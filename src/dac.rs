@@ -0,0 +1,422 @@
+//! Digital to Analog Converter Controller (DACC)
+//!
+//! The DACC drives two 12 bit channels, DAC0 on PB13 and DAC1 on PD0. A channel converts either
+//! every written value as soon as possible, at the maximum rate of 1 MS/s, or one value per
+//! trigger event. Triggered channels can interpolate between the values to reduce the output
+//! steps, and waveforms are streamed from a sample buffer by DMA.
+
+use core::marker::PhantomData;
+
+use void::Void;
+
+use crate::target_device::DACC;
+use crate::target_device::PMC;
+
+use crate::gpio::{Input, Floating};
+use crate::gpio::piob::PB13;
+use crate::gpio::piod::PD0;
+use crate::clock_gen::Clocks;
+use crate::time::Hertz;
+use crate::dma::{Channel as DmaChannel, ChannelId as DmaChannelId, CircWriteTransfer, DescriptorView0, DmaPeripheral, ReadBuffer, Transfer};
+
+/// Maximum DAC clock frequency, a conversion takes 12 DAC clock cycles
+const MAX_DAC_CLOCK: u32 = 12_000_000;
+
+/// Largest value accepted by a channel
+pub const MAX_VALUE: u16 = 0xfff;
+
+// Mode register
+const MR_MAXS0: u32 = 1 << 0;
+const MR_PRESCALER_SHIFT: u32 = 24;
+
+// Trigger register
+const TRIGR_TRGEN0: u32 = 1 << 0;
+const TRIGR_TRGSEL0_SHIFT: u32 = 4;
+const TRIGR_OSR0_SHIFT: u32 = 16;
+
+// Analog current of both channels, recommended setting for conversions up to 1 MS/s
+const ACR_IBCTL: u32 = 0x2 | 0x2 << 2;
+
+// Channel status register
+const CHSR_DACRDY0: u32 = 1 << 8;
+
+// Status flags of the interrupt status register
+const ISR_TXRDY0: u32 = 1 << 0;
+const ISR_EOC0: u32 = 1 << 4;
+
+/// Conversion trigger of a channel
+///
+/// The timer triggers are TIOA0 - TIOA2 of TC0, see `timer::Timer::trigger_output`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Trigger {
+	/// DATRG pin
+	Pin,
+	Tioa0,
+	Tioa1,
+	Tioa2,
+	PwmEventLine0,
+	PwmEventLine1,
+}
+
+impl Trigger {
+	fn bits(&self) -> u32 {
+		match self {
+			Trigger::Pin => 0,
+			Trigger::Tioa0 => 1,
+			Trigger::Tioa1 => 2,
+			Trigger::Tioa2 => 3,
+			Trigger::PwmEventLine0 => 4,
+			Trigger::PwmEventLine1 => 5,
+		}
+	}
+}
+
+/// Interpolation of triggered conversions
+///
+/// With an oversampling ratio of N every trigger converts one of N values interpolated between
+/// the previous and the current value, a new value is taken every N triggers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interpolation {
+	X1,
+	X2,
+	X4,
+	X8,
+	X16,
+	X32,
+}
+
+impl Interpolation {
+	fn bits(&self) -> u32 {
+		match self {
+			Interpolation::X1 => 0,
+			Interpolation::X2 => 1,
+			Interpolation::X4 => 2,
+			Interpolation::X8 => 3,
+			Interpolation::X16 => 4,
+			Interpolation::X32 => 5,
+		}
+	}
+}
+
+/// Conversion mode of a channel
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+	/// Converts a value as soon as it is written
+	FreeRunning,
+	/// Converts values back to back every 12 DAC clock cycles without waiting for the output to settle
+	MaxSpeed,
+	/// Converts a value on every trigger event
+	Triggered(Trigger),
+}
+
+/// Interrupt event of a channel
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+	/// The channel accepts a new value
+	TxReady,
+	/// A conversion has ended
+	EndOfConversion,
+}
+
+impl Event {
+	fn mask<CH: ChannelId>(&self) -> u32 {
+		match self {
+			Event::TxReady => ISR_TXRDY0 << CH::NR,
+			Event::EndOfConversion => ISR_EOC0 << CH::NR,
+		}
+	}
+}
+
+pub mod config {
+	use super::{Interpolation, Mode, Trigger};
+	use crate::time::Hertz;
+
+	pub struct DacConfig {
+		/// Target DAC clock, the prescaler selects the closest clock not above it
+		pub clock: Hertz,
+	}
+
+	impl DacConfig {
+		pub fn clock(mut self, clock: Hertz) -> Self {
+			self.clock = clock;
+			self
+		}
+	}
+
+	impl Default for DacConfig {
+		fn default() -> DacConfig {
+			DacConfig {
+				clock: Hertz(12_000_000),
+			}
+		}
+	}
+
+	/// Per channel configuration
+	pub struct ChannelConfig {
+		pub mode: Mode,
+		/// Only used in triggered mode
+		pub interpolation: Interpolation,
+	}
+
+	impl ChannelConfig {
+		pub fn max_speed(mut self) -> Self {
+			self.mode = Mode::MaxSpeed;
+			self
+		}
+
+		pub fn triggered(mut self, trigger: Trigger) -> Self {
+			self.mode = Mode::Triggered(trigger);
+			self
+		}
+
+		pub fn interpolation(mut self, interpolation: Interpolation) -> Self {
+			self.interpolation = interpolation;
+			self
+		}
+	}
+
+	impl Default for ChannelConfig {
+		fn default() -> ChannelConfig {
+			ChannelConfig {
+				mode: Mode::FreeRunning,
+				interpolation: Interpolation::X1,
+			}
+		}
+	}
+
+	#[derive(Debug)]
+	pub struct InvalidConfig;
+}
+
+/// Calculates the DAC clock prescaler for the fastest clock not above `target`
+pub fn calc_prescaler(mck: Hertz, target: Hertz) -> Option<u8> {
+	let target = core::cmp::min(target.0, MAX_DAC_CLOCK);
+	if target == 0 {
+		return None;
+	}
+	// f_DAC = mck / (PRESCALER + 2)
+	let div = core::cmp::max((mck.0 + target - 1) / target, 2);
+	if div > 17 {
+		return None;
+	}
+	Some((div - 2) as u8)
+}
+
+/// DAC channel marker
+pub trait ChannelId {
+	const NR: u32;
+	const DMA: DmaPeripheral;
+}
+
+pub struct C0;
+pub struct C1;
+
+impl ChannelId for C0 {
+	const NR: u32 = 0;
+	const DMA: DmaPeripheral = DmaPeripheral::Dacc0;
+}
+
+impl ChannelId for C1 {
+	const NR: u32 = 1;
+	const DMA: DmaPeripheral = DmaPeripheral::Dacc1;
+}
+
+/// Output pin of channel `CH`
+pub trait Pin<CH> {}
+
+impl Pin<C0> for PB13<Input<Floating>> {}
+impl Pin<C1> for PD0<Input<Floating>> {}
+
+/// DACC abstraction
+pub struct Dac {
+	dacc: DACC,
+	clock: Hertz,
+}
+
+/// An enabled DAC channel with its output pin
+pub struct DacChannel<CH, PIN> {
+	pin: PIN,
+	_ch: PhantomData<CH>,
+}
+
+impl Dac {
+	/// Configures the DACC, all channels are disabled
+	pub fn dacc(dacc: DACC, config: config::DacConfig, clocks: &Clocks, pmc: &mut PMC) -> Result<Self, config::InvalidConfig> {
+		let prescaler = calc_prescaler(clocks.mck(), config.clock).ok_or(config::InvalidConfig)?;
+
+		//enable peripheral clock in pmc
+		pmc.pmc_pcer0.write(|w| w.pid30().set_bit() );
+
+		//reset peripheral
+		dacc.dacc_cr.write(|w| w.swrst().set_bit());
+
+		// half word mode, every 32 bit write carries a single value
+		dacc.dacc_mr.write(|w| unsafe { w.bits((prescaler as u32) << MR_PRESCALER_SHIFT) });
+		dacc.dacc_trigr.write(|w| unsafe { w.bits(0) });
+		dacc.dacc_acr.write(|w| unsafe { w.bits(ACR_IBCTL) });
+		dacc.dacc_chdr.write(|w| unsafe { w.bits(0x3) });
+		dacc.dacc_idr.write(|w| unsafe { w.bits(0xffff_ffff) });
+
+		Ok(Dac {
+			dacc,
+			clock: Hertz(clocks.mck().0 / (prescaler as u32 + 2)),
+		})
+	}
+
+	/// DAC clock frequency
+	pub fn clock(&self) -> Hertz {
+		self.clock
+	}
+
+	/// Enables the channel of `pin`, blocks until its output has started up
+	pub fn enable<CH, PIN>(&mut self, pin: PIN, config: config::ChannelConfig) -> Result<DacChannel<CH, PIN>, config::InvalidConfig>
+	where
+		CH: ChannelId,
+		PIN: Pin<CH>,
+	{
+		let (trigger, maxs) = match config.mode {
+			Mode::FreeRunning => (None, false),
+			Mode::MaxSpeed => (None, true),
+			Mode::Triggered(trigger) => (Some(trigger), false),
+		};
+		if trigger.is_none() && config.interpolation != Interpolation::X1 {
+			return Err(config::InvalidConfig);
+		}
+
+		let nr = CH::NR;
+		self.dacc.dacc_mr.modify(|r, w| unsafe {
+			if maxs {
+				w.bits(r.bits() | MR_MAXS0 << nr)
+			} else {
+				w.bits(r.bits() & !(MR_MAXS0 << nr))
+			}
+		});
+		self.dacc.dacc_trigr.modify(|r, w| unsafe {
+			let mut bits = r.bits()
+				& !(TRIGR_TRGEN0 << nr
+				| 0x7 << (TRIGR_TRGSEL0_SHIFT + 4 * nr)
+				| 0x7 << (TRIGR_OSR0_SHIFT + 4 * nr));
+			if let Some(trigger) = trigger {
+				bits |= TRIGR_TRGEN0 << nr
+					| trigger.bits() << (TRIGR_TRGSEL0_SHIFT + 4 * nr)
+					| config.interpolation.bits() << (TRIGR_OSR0_SHIFT + 4 * nr);
+			}
+			w.bits(bits)
+		});
+
+		self.dacc.dacc_cher.write(|w| unsafe { w.bits(1 << nr) });
+		while self.dacc.dacc_chsr.read().bits() & CHSR_DACRDY0 << nr == 0 {
+			//Wait for the channel to start up
+		}
+
+		Ok(DacChannel { pin, _ch: PhantomData })
+	}
+
+	/// Disables a channel and releases its pin
+	pub fn disable<CH: ChannelId, PIN>(&mut self, channel: DacChannel<CH, PIN>) -> PIN {
+		self.dacc.dacc_idr.write(|w| unsafe { w.bits(Event::TxReady.mask::<CH>() | Event::EndOfConversion.mask::<CH>()) });
+		self.dacc.dacc_chdr.write(|w| unsafe { w.bits(1 << CH::NR) });
+		channel.pin
+	}
+
+	/// Releases the DACC, all channels have to be disabled before
+	pub fn release(self) -> DACC {
+		self.dacc
+	}
+}
+
+impl<CH: ChannelId, PIN> DacChannel<CH, PIN> {
+	fn regs() -> &'static crate::target_device::dacc::RegisterBlock {
+		// NOTE(unsafe) channels only access their own data register and write-one registers
+		unsafe { &*DACC::ptr() }
+	}
+
+	/// Returns if the channel accepts a new value
+	pub fn is_ready(&self) -> bool {
+		Self::regs().dacc_isr.read().bits() & ISR_TXRDY0 << CH::NR != 0
+	}
+
+	/// Queues `value` for conversion, values above `MAX_VALUE` are truncated
+	pub fn write(&mut self, value: u16) -> nb::Result<(), Void> {
+		if !self.is_ready() {
+			return Err(nb::Error::WouldBlock);
+		}
+		Self::regs().dacc_cdr[CH::NR as usize].write(|w| unsafe { w.bits((value & MAX_VALUE) as u32) });
+		Ok(())
+	}
+
+	/// Starts an interrupt event
+	pub fn listen(&mut self, event: Event) {
+		Self::regs().dacc_ier.write(|w| unsafe { w.bits(event.mask::<CH>()) });
+	}
+
+	/// Stops an interrupt event
+	pub fn unlisten(&mut self, event: Event) {
+		Self::regs().dacc_idr.write(|w| unsafe { w.bits(event.mask::<CH>()) });
+	}
+
+	/// Sends `buffer` once by DMA, the values are converted according to the channel mode
+	pub fn write_dma<B, DMACH>(self, buffer: B, channel: DmaChannel<DMACH>) -> DacTransfer<CH, PIN, DMACH, B>
+	where
+		B: ReadBuffer<Word = u16>,
+		DMACH: DmaChannelId,
+	{
+		let cdr = &Self::regs().dacc_cdr[CH::NR as usize] as *const _ as u32;
+		let transfer = channel.write_to_peripheral(CH::DMA, cdr, buffer);
+
+		DacTransfer { channel: self, transfer }
+	}
+
+	/// Repeats `buffer` by DMA until the stream is stopped, e.g. to generate a waveform
+	///
+	/// The waveform frequency is the conversion rate divided by the buffer length, so the
+	/// channel should be triggered by a timer.
+	pub fn stream<B, DMACH>(
+		self,
+		buffer: B,
+		descriptor: &'static mut DescriptorView0,
+		channel: DmaChannel<DMACH>,
+	) -> DacStream<CH, PIN, DMACH, B>
+	where
+		B: ReadBuffer<Word = u16>,
+		DMACH: DmaChannelId,
+	{
+		let cdr = &Self::regs().dacc_cdr[CH::NR as usize] as *const _ as u32;
+		let transfer = channel.circular_to_peripheral(CH::DMA, cdr, buffer, descriptor);
+
+		DacStream { channel: self, transfer }
+	}
+}
+
+/// A DAC channel sending a buffer once by DMA
+pub struct DacTransfer<CH: ChannelId, PIN, DMACH: DmaChannelId, BUF> {
+	channel: DacChannel<CH, PIN>,
+	transfer: Transfer<DMACH, BUF>,
+}
+
+impl<CH: ChannelId, PIN, DMACH: DmaChannelId, BUF> DacTransfer<CH, PIN, DMACH, BUF> {
+	/// Returns true once all values have been handed to the channel
+	pub fn is_done(&self) -> bool {
+		self.transfer.is_done()
+	}
+
+	/// Blocks until all values have been handed to the channel
+	pub fn wait(self) -> (DacChannel<CH, PIN>, BUF, DmaChannel<DMACH>) {
+		let (buffer, channel) = self.transfer.wait();
+		(self.channel, buffer, channel)
+	}
+}
+
+/// A DAC channel repeating a buffer by DMA
+pub struct DacStream<CH: ChannelId, PIN, DMACH: DmaChannelId, BUF: ReadBuffer> {
+	channel: DacChannel<CH, PIN>,
+	transfer: CircWriteTransfer<DMACH, BUF>,
+}
+
+impl<CH: ChannelId, PIN, DMACH: DmaChannelId, BUF: ReadBuffer> DacStream<CH, PIN, DMACH, BUF> {
+	/// Stops the stream, the output keeps the last converted value
+	pub fn stop(self) -> (DacChannel<CH, PIN>, BUF, &'static mut DescriptorView0, DmaChannel<DMACH>) {
+		let (buffer, descriptor, channel) = self.transfer.abort();
+		(self.channel, buffer, descriptor, channel)
+	}
+}
@@ -0,0 +1,839 @@
+//! Direct Memory Access using the XDMAC controller
+//!
+//! The controller is split into 24 independent channels. Every transfer takes ownership of
+//! the channel and the buffer until it has completed, so the buffer can not be touched by the
+//! CPU while the DMA engine is still accessing it.
+//!
+//! The XDMAC does not snoop the data cache of the Cortex-M7. Buffers either have to be placed in
+//! a region marked as non cacheable with the `mpu` module or the cache has to be maintained by
+//! the caller.
+
+use core::marker::PhantomData;
+use core::sync::atomic::{self, Ordering};
+
+use crate::target_device::{XDMAC, PMC};
+
+/// Hardware peripheral ids used to synchronise a channel with a peripheral
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DmaPeripheral {
+	Hsmci = 0,
+	Spi0Tx = 1,
+	Spi0Rx = 2,
+	Spi1Tx = 3,
+	Spi1Rx = 4,
+	QspiTx = 5,
+	QspiRx = 6,
+	Usart0Tx = 7,
+	Usart0Rx = 8,
+	Usart1Tx = 9,
+	Usart1Rx = 10,
+	Usart2Tx = 11,
+	Usart2Rx = 12,
+	Pwm0 = 13,
+	Twihs0Tx = 14,
+	Twihs0Rx = 15,
+	Twihs1Tx = 16,
+	Twihs1Rx = 17,
+	Twihs2Tx = 18,
+	Twihs2Rx = 19,
+	Uart0Tx = 20,
+	Uart0Rx = 21,
+	Uart1Tx = 22,
+	Uart1Rx = 23,
+	Uart2Tx = 24,
+	Uart2Rx = 25,
+	Uart3Tx = 26,
+	Uart3Rx = 27,
+	Uart4Tx = 28,
+	Uart4Rx = 29,
+	Dacc0 = 30,
+	Dacc1 = 31,
+	SscTx = 32,
+	SscRx = 33,
+	Pioa = 34,
+	Afec0 = 35,
+	Afec1 = 36,
+	AesTx = 37,
+	AesRx = 38,
+	Pwm1 = 39,
+	Tc0 = 40,
+	Tc1 = 41,
+	Tc2 = 42,
+	Tc3 = 43,
+}
+
+impl DmaPeripheral {
+	/// get hardware interface number
+	pub fn id(&self) -> u32 {
+		*self as u32
+	}
+}
+
+/// Width of a single data transfer
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DataWidth {
+	Byte = 0,
+	HalfWord = 1,
+	Word = 2,
+}
+
+/// Number of data transfers per peripheral request
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChunkSize {
+	Chunk1 = 0,
+	Chunk2 = 1,
+	Chunk4 = 2,
+	Chunk8 = 3,
+	Chunk16 = 4,
+}
+
+/// Number of data transfers per memory burst
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BurstSize {
+	Single = 0,
+	Four = 1,
+	Eight = 2,
+	Sixteen = 3,
+}
+
+/// Source and destination of a channel
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TransferType {
+	/// Software triggered copy between two memory regions
+	MemoryToMemory,
+	/// Peripheral is the source, transfers are triggered by the peripheral
+	PeripheralToMemory(DmaPeripheral),
+	/// Peripheral is the destination, transfers are triggered by the peripheral
+	MemoryToPeripheral(DmaPeripheral),
+}
+
+/// Holds the configuration of a channel
+#[derive(Debug, Copy, Clone)]
+pub struct ChannelConfig {
+	transfer : TransferType,
+	width : DataWidth,
+	chunk : ChunkSize,
+	burst : BurstSize,
+}
+
+impl ChannelConfig {
+	pub fn memory_to_memory() -> Self {
+		Self::new(TransferType::MemoryToMemory)
+	}
+
+	pub fn peripheral_to_memory(peripheral: DmaPeripheral) -> Self {
+		Self::new(TransferType::PeripheralToMemory(peripheral))
+	}
+
+	pub fn memory_to_peripheral(peripheral: DmaPeripheral) -> Self {
+		Self::new(TransferType::MemoryToPeripheral(peripheral))
+	}
+
+	fn new(transfer: TransferType) -> Self {
+		ChannelConfig {
+			transfer,
+			width : DataWidth::Byte,
+			chunk : ChunkSize::Chunk1,
+			burst : BurstSize::Single,
+		}
+	}
+
+	pub fn width(mut self, width: DataWidth) -> Self {
+		self.width = width;
+		self
+	}
+
+	pub fn chunk(mut self, chunk: ChunkSize) -> Self {
+		self.chunk = chunk;
+		self
+	}
+
+	pub fn burst(mut self, burst: BurstSize) -> Self {
+		self.burst = burst;
+		self
+	}
+
+	/// get configured transfer type
+	pub fn transfer_type(&self) -> TransferType {
+		self.transfer
+	}
+
+	/// Value of the XDMAC_CCx register, also used as the configuration member of view 2 and 3 descriptors
+	pub fn bits(&self) -> u32 {
+		let mut cc : u32 = 0;
+
+		cc |= (self.burst as u32) << 1;
+		cc |= (self.chunk as u32) << 8;
+		cc |= (self.width as u32) << 11;
+
+		match self.transfer {
+			TransferType::MemoryToMemory => {
+				// memory transfer, both addresses incrementing, both on bus interface 0
+				cc |= 1 << 6;
+				cc |= 1 << 16;
+				cc |= 1 << 18;
+			}
+			TransferType::PeripheralToMemory(p) => {
+				// peripheral on bus interface 1, fixed source address
+				cc |= 1;
+				cc |= 1 << 13;
+				cc |= 1 << 18;
+				cc |= p.id() << 24;
+			}
+			TransferType::MemoryToPeripheral(p) => {
+				// peripheral on bus interface 1, fixed destination address
+				cc |= 1;
+				cc |= 1 << 4;
+				cc |= 1 << 14;
+				cc |= 1 << 16;
+				cc |= p.id() << 24;
+			}
+		}
+
+		cc
+	}
+}
+
+/// Types that can be moved by the DMA engine in one data transfer
+pub trait Word: Copy {
+	fn width() -> DataWidth;
+}
+
+impl Word for u8 {
+	fn width() -> DataWidth {
+		DataWidth::Byte
+	}
+}
+
+impl Word for u16 {
+	fn width() -> DataWidth {
+		DataWidth::HalfWord
+	}
+}
+
+impl Word for u32 {
+	fn width() -> DataWidth {
+		DataWidth::Word
+	}
+}
+
+/// Buffer the DMA engine reads from
+///
+/// Implementors guarantee that the returned memory stays valid for the whole lifetime of the value
+pub unsafe trait ReadBuffer {
+	type Word: Word;
+
+	fn read_buffer(&self) -> (*const Self::Word, usize);
+}
+
+/// Buffer the DMA engine writes to
+///
+/// Implementors guarantee that the returned memory stays valid for the whole lifetime of the value
+pub unsafe trait WriteBuffer {
+	type Word: Word;
+
+	fn write_buffer(&mut self) -> (*mut Self::Word, usize);
+}
+
+unsafe impl<W: Word> ReadBuffer for &'static [W] {
+	type Word = W;
+
+	fn read_buffer(&self) -> (*const W, usize) {
+		(self.as_ptr(), self.len())
+	}
+}
+
+unsafe impl<W: Word> ReadBuffer for &'static mut [W] {
+	type Word = W;
+
+	fn read_buffer(&self) -> (*const W, usize) {
+		(self.as_ptr(), self.len())
+	}
+}
+
+unsafe impl<W: Word> WriteBuffer for &'static mut [W] {
+	type Word = W;
+
+	fn write_buffer(&mut self) -> (*mut W, usize) {
+		(self.as_mut_ptr(), self.len())
+	}
+}
+
+// Fields of the microblock control member of a descriptor
+const UBC_NDE : u32 = 1 << 24;
+const UBC_NSEN : u32 = 1 << 25;
+const UBC_NDEN : u32 = 1 << 26;
+const UBC_NVIEW_SHIFT : u32 = 27;
+const UBC_UBLEN_MASK : u32 = 0x00ff_ffff;
+
+/// Linked list descriptor
+///
+/// Descriptors are fetched by the DMA engine, they therefore need to live in memory that stays
+/// valid for the whole transfer.
+pub unsafe trait Descriptor {
+	/// Descriptor view number
+	const VIEW : u32;
+}
+
+macro_rules! descriptor {
+	($(
+		$(#[$attr:meta])*
+		$View:ident: $view:expr, [$($field:ident),*],
+	)+) => {
+		$(
+			$(#[$attr])*
+			#[repr(C)]
+			#[derive(Debug, Default)]
+			pub struct $View {
+				nda : u32,
+				ubc : u32,
+				$(
+					pub $field : u32,
+				)*
+			}
+
+			impl $View {
+				/// Zeroed descriptor, meant for initialising statics
+				pub const EMPTY : $View = $View {
+					nda : 0,
+					ubc : 0,
+					$(
+						$field : 0,
+					)*
+				};
+
+				/// Creates a descriptor for a microblock of `len` data transfers
+				pub fn new(len: u32 $(, $field: u32)*) -> Self {
+					assert!(len != 0 && len <= UBC_UBLEN_MASK);
+					$View {
+						nda : 0,
+						ubc : len,
+						$(
+							$field,
+						)*
+					}
+				}
+
+				/// Appends `next` to this descriptor
+				///
+				/// Since `next` can not be modified anymore the list has to be built from its end.
+				pub fn link<D: Descriptor>(&mut self, next: &'static D) {
+					self.nda = next as *const D as u32;
+					self.ubc = (self.ubc & UBC_UBLEN_MASK) | UBC_NDE | UBC_NSEN | UBC_NDEN | (D::VIEW << UBC_NVIEW_SHIFT);
+				}
+			}
+
+			unsafe impl Descriptor for $View {
+				const VIEW : u32 = $view;
+			}
+		)+
+	}
+}
+
+descriptor! {
+	/// Descriptor view 0, only updates the memory address
+	DescriptorView0: 0, [ta],
+	/// Descriptor view 1, updates source and destination address
+	DescriptorView1: 1, [sa, da],
+	/// Descriptor view 2, additionally updates the channel configuration
+	DescriptorView2: 2, [sa, da, cfg],
+	/// Descriptor view 3, additionally updates block control and strides
+	DescriptorView3: 3, [sa, da, cfg, bc, ds, sus, dus],
+}
+
+/// Channel identifier (type state)
+pub trait ChannelId {
+	const NR : usize;
+}
+
+/// Owned handle of a single XDMAC channel
+pub struct Channel<CH> {
+	_ch : PhantomData<CH>,
+}
+
+/// An ongoing transfer, owns the channel and the buffer until it is finished
+pub struct Transfer<CH: ChannelId, BUF> {
+	channel : Channel<CH>,
+	buffer : BUF,
+}
+
+impl<CH: ChannelId> Channel<CH> {
+	fn regs() -> &'static crate::target_device::xdmac::XDMAC_CHID {
+		// NOTE(unsafe) every channel only accesses its own register block
+		unsafe { &(*XDMAC::ptr()).xdmac_chid[CH::NR] }
+	}
+
+	fn mask() -> u32 {
+		1 << CH::NR
+	}
+
+	/// Programs the channel for a single microblock transfer, without enabling it
+	fn setup_single(&mut self, config: &ChannelConfig, src: u32, dst: u32, len: usize) {
+		assert!(len != 0 && len as u32 <= UBC_UBLEN_MASK);
+		let ch = Self::regs();
+
+		// clear pending status flags
+		let _ = ch.xdmac_cis.read().bits();
+
+		unsafe {
+			ch.xdmac_csa.write(|w| w.bits(src));
+			ch.xdmac_cda.write(|w| w.bits(dst));
+			ch.xdmac_cubc.write(|w| w.bits(len as u32));
+			ch.xdmac_cc.write(|w| w.bits(config.bits()));
+			ch.xdmac_cndc.write(|w| w.bits(0));
+			ch.xdmac_cbc.write(|w| w.bits(0));
+			ch.xdmac_cds_msp.write(|w| w.bits(0));
+			ch.xdmac_csus.write(|w| w.bits(0));
+			ch.xdmac_cdus.write(|w| w.bits(0));
+		}
+	}
+
+	fn enable(&mut self) {
+		// buffer accesses must not be moved behind the start of the transfer
+		atomic::compiler_fence(Ordering::Release);
+		cortex_m::asm::dmb();
+
+		// NOTE(unsafe) write-one register, does not affect other channels
+		unsafe { (*XDMAC::ptr()).xdmac_ge.write(|w| w.bits(Self::mask())) };
+	}
+
+	/// Starts a peripheral to memory transfer, `src` is the address of the peripheral data register
+	pub fn read_from_peripheral<B>(mut self, peripheral: DmaPeripheral, src: u32, mut buffer: B) -> Transfer<CH, B>
+	where
+		B: WriteBuffer,
+	{
+		let (ptr, len) = buffer.write_buffer();
+		let config = ChannelConfig::peripheral_to_memory(peripheral).width(B::Word::width());
+		self.setup_single(&config, src, ptr as u32, len);
+		self.enable();
+
+		Transfer { channel: self, buffer }
+	}
+
+	/// Starts a memory to peripheral transfer, `dst` is the address of the peripheral data register
+	pub fn write_to_peripheral<B>(mut self, peripheral: DmaPeripheral, dst: u32, buffer: B) -> Transfer<CH, B>
+	where
+		B: ReadBuffer,
+	{
+		let (ptr, len) = buffer.read_buffer();
+		let config = ChannelConfig::memory_to_peripheral(peripheral).width(B::Word::width());
+		self.setup_single(&config, ptr as u32, dst, len);
+		self.enable();
+
+		Transfer { channel: self, buffer }
+	}
+
+	/// Copies `src` to `dst`, the shorter of both buffers determines the length
+	pub fn memory_to_memory<S, D>(mut self, src: S, mut dst: D) -> Transfer<CH, (S, D)>
+	where
+		S: ReadBuffer,
+		D: WriteBuffer<Word = S::Word>,
+	{
+		let (src_ptr, src_len) = src.read_buffer();
+		let (dst_ptr, dst_len) = dst.write_buffer();
+		let config = ChannelConfig::memory_to_memory().width(S::Word::width());
+		self.setup_single(&config, src_ptr as u32, dst_ptr as u32, core::cmp::min(src_len, dst_len));
+		self.enable();
+
+		Transfer { channel: self, buffer: (src, dst) }
+	}
+
+	/// Starts a linked list transfer beginning with `first`
+	///
+	/// `src` and `dst` are the initial addresses, the ones not contained in the descriptor view
+	/// stay in effect for the whole list. For view 0 and 1 descriptors `config` is used for all
+	/// microblocks.
+	pub fn linked_list<D>(mut self, config: ChannelConfig, src: u32, dst: u32, first: &'static mut D) -> Transfer<CH, &'static mut D>
+	where
+		D: Descriptor,
+	{
+		self.start_linked(config, src, dst, &*first as *const D as u32, D::VIEW);
+
+		Transfer { channel: self, buffer: first }
+	}
+
+	/// Programs and enables the channel to fetch the descriptor at `first`
+	fn start_linked(&mut self, config: ChannelConfig, src: u32, dst: u32, first: u32, view: u32) {
+		let ch = Self::regs();
+
+		// view 0 only carries the memory address, all other views carry both
+		let (update_src, update_dst) = match (view, config.transfer_type()) {
+			(0, TransferType::PeripheralToMemory(_)) => (false, true),
+			(0, TransferType::MemoryToPeripheral(_)) => (true, false),
+			(0, TransferType::MemoryToMemory) => (false, true),
+			_ => (true, true),
+		};
+
+		// clear pending status flags
+		let _ = ch.xdmac_cis.read().bits();
+
+		unsafe {
+			ch.xdmac_csa.write(|w| w.bits(src));
+			ch.xdmac_cda.write(|w| w.bits(dst));
+			ch.xdmac_cc.write(|w| w.bits(config.bits()));
+			ch.xdmac_cbc.write(|w| w.bits(0));
+			ch.xdmac_cds_msp.write(|w| w.bits(0));
+			ch.xdmac_csus.write(|w| w.bits(0));
+			ch.xdmac_cdus.write(|w| w.bits(0));
+			ch.xdmac_cnda.write(|w| w.bits(first & !0x3));
+			ch.xdmac_cndc.write(|w| {
+				w.bits(
+					1
+					| (update_src as u32) << 1
+					| (update_dst as u32) << 2
+					| view << 3
+				)
+			});
+		}
+		self.enable();
+	}
+
+	/// Starts a never ending peripheral to memory transfer into a ring buffer
+	///
+	/// `descriptor` is linked to itself, so the DMA engine wraps around to the start of the
+	/// buffer after it has been filled.
+	pub fn circular_from_peripheral<B>(
+		self,
+		peripheral: DmaPeripheral,
+		src: u32,
+		mut buffer: B,
+		descriptor: &'static mut DescriptorView0
+	) -> CircTransfer<CH, B>
+	where
+		B: WriteBuffer,
+	{
+		let (ptr, len) = buffer.write_buffer();
+		assert!(len != 0 && len as u32 <= UBC_UBLEN_MASK);
+
+		descriptor.ta = ptr as u32;
+		descriptor.nda = &*descriptor as *const DescriptorView0 as u32;
+		descriptor.ubc = len as u32 | UBC_NDE | UBC_NDEN;
+
+		let config = ChannelConfig::peripheral_to_memory(peripheral).width(B::Word::width());
+		let transfer = self.linked_list(config, src, ptr as u32, descriptor);
+
+		CircTransfer {
+			channel : transfer.channel,
+			descriptor : transfer.buffer,
+			buffer,
+			start : ptr as u32,
+			len,
+			read_index : 0,
+		}
+	}
+
+	/// Starts a never ending memory to peripheral transfer repeating `buffer`
+	///
+	/// `descriptor` is linked to itself, so the DMA engine restarts at the beginning of the
+	/// buffer after it has been sent. The transfer only ends with `CircWriteTransfer::abort`.
+	pub fn circular_to_peripheral<B>(
+		self,
+		peripheral: DmaPeripheral,
+		dst: u32,
+		buffer: B,
+		descriptor: &'static mut DescriptorView0
+	) -> CircWriteTransfer<CH, B>
+	where
+		B: ReadBuffer,
+	{
+		let (ptr, len) = buffer.read_buffer();
+		assert!(len != 0 && len as u32 <= UBC_UBLEN_MASK);
+
+		descriptor.ta = ptr as u32;
+		descriptor.nda = &*descriptor as *const DescriptorView0 as u32;
+		descriptor.ubc = len as u32 | UBC_NDE | UBC_NDEN;
+
+		let config = ChannelConfig::memory_to_peripheral(peripheral).width(B::Word::width());
+		let transfer = self.linked_list(config, ptr as u32, dst, descriptor);
+
+		CircWriteTransfer { transfer: Transfer { channel: transfer.channel, buffer: (buffer, transfer.buffer) } }
+	}
+
+	/// Enables the end of block and end of list interrupt of this channel
+	pub fn listen(&mut self) {
+		let ch = Self::regs();
+		ch.xdmac_cie.write(|w| {
+			w.bie().set_bit();
+			w.lie().set_bit()
+		});
+		// NOTE(unsafe) write-one register, does not affect other channels
+		unsafe { (*XDMAC::ptr()).xdmac_gie.write(|w| w.bits(Self::mask())) };
+	}
+
+	/// Disables all interrupts of this channel
+	pub fn unlisten(&mut self) {
+		// NOTE(unsafe) write-one register, does not affect other channels
+		unsafe { (*XDMAC::ptr()).xdmac_gid.write(|w| w.bits(Self::mask())) };
+		Self::regs().xdmac_cid.write(|w| unsafe { w.bits(0x7f) });
+	}
+
+	/// Clears the pending interrupt flags of this channel
+	pub fn clear_interrupts(&mut self) {
+		let _ = Self::regs().xdmac_cis.read().bits();
+	}
+}
+
+impl<CH: ChannelId, BUF> Transfer<CH, BUF> {
+	/// Returns true once the channel has been disabled by the hardware
+	pub fn is_done(&self) -> bool {
+		// NOTE(unsafe) atomic read with no side effects
+		unsafe { (*XDMAC::ptr()).xdmac_gs.read().bits() & Channel::<CH>::mask() == 0 }
+	}
+
+	/// Number of data transfers left in the current microblock
+	pub fn remaining(&self) -> u32 {
+		Channel::<CH>::regs().xdmac_cubc.read().bits() & UBC_UBLEN_MASK
+	}
+
+	/// Blocks until the transfer is done and releases the buffer and channel
+	pub fn wait(self) -> (BUF, Channel<CH>) {
+		while !self.is_done() {}
+
+		// buffer accesses must not be moved in front of the end of the transfer
+		cortex_m::asm::dmb();
+		atomic::compiler_fence(Ordering::Acquire);
+
+		(self.buffer, self.channel)
+	}
+
+	/// Stops the transfer and releases the buffer and channel
+	pub fn abort(self) -> (BUF, Channel<CH>) {
+		// NOTE(unsafe) write-one register, does not affect other channels
+		unsafe { (*XDMAC::ptr()).xdmac_gd.write(|w| w.bits(Channel::<CH>::mask())) };
+		self.wait()
+	}
+}
+
+impl<CH: ChannelId> Channel<CH> {
+	/// Starts a transfer filling `buffers` alternately, runs until it is stopped
+	///
+	/// Both buffers must have the same length. While the engine fills one buffer the other one
+	/// can be processed with `DoubleBufferTransfer::read`.
+	pub fn double_buffer_from_peripheral<B>(
+		mut self,
+		peripheral: DmaPeripheral,
+		src: u32,
+		mut buffers: [B; 2],
+		descriptors: &'static mut [DescriptorView0; 2]
+	) -> DoubleBufferTransfer<CH, B>
+	where
+		B: WriteBuffer,
+	{
+		let (ptr0, len) = buffers[0].write_buffer();
+		let (ptr1, len1) = buffers[1].write_buffer();
+		assert!(len != 0 && len == len1 && len as u32 <= UBC_UBLEN_MASK);
+
+		let addr0 = &descriptors[0] as *const DescriptorView0 as u32;
+		let addr1 = &descriptors[1] as *const DescriptorView0 as u32;
+		descriptors[0].ta = ptr0 as u32;
+		descriptors[0].nda = addr1;
+		descriptors[0].ubc = len as u32 | UBC_NDE | UBC_NDEN;
+		descriptors[1].ta = ptr1 as u32;
+		descriptors[1].nda = addr0;
+		descriptors[1].ubc = len as u32 | UBC_NDE | UBC_NDEN;
+
+		let config = ChannelConfig::peripheral_to_memory(peripheral).width(B::Word::width());
+		self.start_linked(config, src, ptr0 as u32, addr0, DescriptorView0::VIEW);
+
+		DoubleBufferTransfer {
+			channel : self,
+			buffers,
+			descriptors,
+			next : 0,
+		}
+	}
+}
+
+/// A transfer filling two buffers alternately, runs until it is stopped
+pub struct DoubleBufferTransfer<CH: ChannelId, BUF: WriteBuffer> {
+	channel : Channel<CH>,
+	buffers : [BUF; 2],
+	descriptors : &'static mut [DescriptorView0; 2],
+	next : usize,
+}
+
+impl<CH: ChannelId, BUF: WriteBuffer> DoubleBufferTransfer<CH, BUF> {
+	/// Returns if the engine writes into buffer `index`
+	fn is_writing(&mut self, index: usize) -> bool {
+		let cda = Channel::<CH>::regs().xdmac_cda.read().bits();
+		let (ptr, len) = self.buffers[index].write_buffer();
+		let start = ptr as u32;
+		let end = start + (len * core::mem::size_of::<BUF::Word>()) as u32;
+		// the address points past the end for a moment before the descriptor is reloaded
+		cda >= start && cda <= end
+	}
+
+	/// Calls `f` with the next filled buffer, returns `None` while it is still being filled
+	///
+	/// The buffer has to be processed before the engine has filled the other buffer, otherwise
+	/// it is overwritten without notice.
+	pub fn read<R, F>(&mut self, f: F) -> Option<R>
+	where
+		F: FnOnce(&[BUF::Word]) -> R,
+	{
+		let index = self.next;
+		if self.is_writing(index) {
+			return None;
+		}
+
+		// data written by the DMA engine has to be visible before it is read
+		cortex_m::asm::dmb();
+		atomic::compiler_fence(Ordering::Acquire);
+
+		let (ptr, len) = self.buffers[index].write_buffer();
+		// NOTE(unsafe) the engine is writing the other buffer
+		let result = f(unsafe { core::slice::from_raw_parts(ptr, len) });
+		self.next = 1 - index;
+
+		Some(result)
+	}
+
+	/// Stops the transfer and releases the buffers, descriptors and channel
+	pub fn stop(self) -> ([BUF; 2], &'static mut [DescriptorView0; 2], Channel<CH>) {
+		let transfer = Transfer { channel: self.channel, buffer: self.descriptors };
+		let (descriptors, channel) = transfer.abort();
+		(self.buffers, descriptors, channel)
+	}
+}
+
+/// A circular transfer into a ring buffer, runs until it is stopped
+pub struct CircTransfer<CH: ChannelId, BUF: WriteBuffer> {
+	channel : Channel<CH>,
+	buffer : BUF,
+	descriptor : &'static mut DescriptorView0,
+	start : u32,
+	len : usize,
+	read_index : usize,
+}
+
+impl<CH: ChannelId, BUF: WriteBuffer> CircTransfer<CH, BUF> {
+	/// Index of the element the DMA engine will write next
+	pub fn write_index(&self) -> usize {
+		let cda = Channel::<CH>::regs().xdmac_cda.read().bits();
+		let offset = cda.wrapping_sub(self.start) as usize / core::mem::size_of::<BUF::Word>();
+		// the address points past the end for a moment before the descriptor is reloaded
+		offset % self.len
+	}
+
+	/// Number of elements that have been received but not read yet
+	pub fn available(&self) -> usize {
+		(self.write_index() + self.len - self.read_index) % self.len
+	}
+
+	/// Copies received elements into `out` and returns the number of elements copied
+	///
+	/// The ring buffer has to be read faster than it is filled, otherwise older data is
+	/// overwritten without notice.
+	pub fn read(&mut self, out: &mut [BUF::Word]) -> usize {
+		let count = core::cmp::min(self.available(), out.len());
+
+		// data written by the DMA engine has to be visible before it is read
+		cortex_m::asm::dmb();
+		atomic::compiler_fence(Ordering::Acquire);
+
+		let (ptr, _) = self.buffer.write_buffer();
+		for o in out.iter_mut().take(count) {
+			// NOTE(unsafe) read_index is always within the buffer
+			*o = unsafe { core::ptr::read_volatile(ptr.add(self.read_index)) };
+			self.read_index = (self.read_index + 1) % self.len;
+		}
+
+		count
+	}
+
+	/// Stops the transfer and releases the buffer, descriptor and channel
+	pub fn stop(self) -> (BUF, &'static mut DescriptorView0, Channel<CH>) {
+		let transfer = Transfer { channel: self.channel, buffer: self.descriptor };
+		let (descriptor, channel) = transfer.abort();
+		(self.buffer, descriptor, channel)
+	}
+}
+
+/// A circular transfer repeating a buffer, runs until it is aborted
+pub struct CircWriteTransfer<CH: ChannelId, BUF: ReadBuffer> {
+	transfer : Transfer<CH, (BUF, &'static mut DescriptorView0)>,
+}
+
+impl<CH: ChannelId, BUF: ReadBuffer> CircWriteTransfer<CH, BUF> {
+	/// Stops the transfer and releases the buffer, descriptor and channel
+	pub fn abort(self) -> (BUF, &'static mut DescriptorView0, Channel<CH>) {
+		let ((buffer, descriptor), channel) = self.transfer.abort();
+		(buffer, descriptor, channel)
+	}
+}
+
+/// Extension trait to split the XDMAC into its channels
+pub trait DmaExt {
+	type Channels;
+
+	/// Enables the XDMAC and splits it into independent channels
+	fn split(self, pmc: &mut PMC) -> Self::Channels;
+}
+
+macro_rules! dma {
+	($($CX:ident: ($chx:ident, $x:expr),)+) => {
+		$(
+			/// Channel identifier (type state)
+			pub struct $CX;
+
+			impl ChannelId for $CX {
+				const NR : usize = $x;
+			}
+		)+
+
+		/// XDMAC channels
+		pub struct Channels {
+			$(
+				pub $chx: Channel<$CX>,
+			)+
+		}
+
+		impl DmaExt for XDMAC {
+			type Channels = Channels;
+
+			fn split(self, pmc: &mut PMC) -> Channels {
+				// Enable XDMAC clock
+				pmc.pmc_pcer1.write( |w| w.pid58().set_bit() );
+
+				// make sure no channel is left running
+				self.xdmac_gd.write(|w| unsafe { w.bits(0x00ff_ffff) });
+				while self.xdmac_gs.read().bits() != 0 {
+					//Wait for all channels to be disabled
+				}
+
+				Channels {
+					$(
+						$chx: Channel { _ch: PhantomData },
+					)+
+				}
+			}
+		}
+	}
+}
+
+dma! {
+	C0: (ch0, 0),
+	C1: (ch1, 1),
+	C2: (ch2, 2),
+	C3: (ch3, 3),
+	C4: (ch4, 4),
+	C5: (ch5, 5),
+	C6: (ch6, 6),
+	C7: (ch7, 7),
+	C8: (ch8, 8),
+	C9: (ch9, 9),
+	C10: (ch10, 10),
+	C11: (ch11, 11),
+	C12: (ch12, 12),
+	C13: (ch13, 13),
+	C14: (ch14, 14),
+	C15: (ch15, 15),
+	C16: (ch16, 16),
+	C17: (ch17, 17),
+	C18: (ch18, 18),
+	C19: (ch19, 19),
+	C20: (ch20, 20),
+	C21: (ch21, 21),
+	C22: (ch22, 22),
+	C23: (ch23, 23),
+}
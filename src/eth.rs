@@ -0,0 +1,981 @@
+//! Ethernet MAC (GMAC)
+//!
+//! The GMAC transfers frames by its own DMA engine through rings of descriptors. Descriptors and
+//! frame buffers live in a `DescriptorRing`, which `Ethernet::new` marks as non cacheable with the
+//! `mpu` module. The MPU is enabled if it is not already and lines of the ring still held by the
+//! D-cache are written back and invalidated once, afterwards neither side needs any cache
+//! maintenance. Every frame fits into a single buffer.
+//!
+//! The IEEE 1588 timer unit counts seconds and nanoseconds with the peripheral clock. The GMAC
+//! latches the time of the last sent and received PTP event frame, `receive_timestamped` and
+//! `tx_timestamp` pair these with the frames. A timestamp is only correct if the frame is
+//! handled before the next PTP event frame of the same kind passes.
+//!
+//! With the `smoltcp-phy` feature `Ethernet` implements `smoltcp::phy::Device`.
+
+use core::sync::atomic::{self, Ordering};
+use core::ptr;
+
+use cortex_m::peripheral::MPU;
+use void::Void;
+
+use crate::target_device::GMAC;
+use crate::target_device::PMC;
+
+use crate::gpio::{PeripheralCntr, PeriphA};
+use crate::gpio::piod::{PD0, PD1, PD2, PD3, PD4, PD5, PD6, PD7, PD8, PD9, PD10, PD11, PD12, PD13, PD14, PD15, PD16, PD17};
+use crate::clock_gen::Clocks;
+use crate::phy;
+use crate::mpu::{Mpu, MemoryRegionsFull, MpuAccessPolicy, MpuCachePolicy, MpuMemoryAttributes, MpuRegionSize, MpuSubregions, ProtectedMemoryRegion};
+
+/// Number of receive buffers
+pub const RX_BUFFERS: usize = 8;
+/// Number of transmit buffers
+pub const TX_BUFFERS: usize = 4;
+/// Size of a frame buffer, a multiple of 64 bytes
+pub const BUFFER_SIZE: usize = 1536;
+/// Largest frame without FCS
+pub const MTU: usize = 1514;
+
+// Network control register
+const NCR_RXEN: u32 = 1 << 2;
+const NCR_TXEN: u32 = 1 << 3;
+const NCR_MPE: u32 = 1 << 4;
+const NCR_CLRSTAT: u32 = 1 << 5;
+const NCR_TSTART: u32 = 1 << 9;
+
+// Network configuration register
+const NCFGR_SPD: u32 = 1 << 0;
+const NCFGR_FD: u32 = 1 << 1;
+const NCFGR_CAF: u32 = 1 << 4;
+const NCFGR_NBC: u32 = 1 << 5;
+const NCFGR_MTIHEN: u32 = 1 << 6;
+const NCFGR_MAXFS: u32 = 1 << 8;
+const NCFGR_RFCS: u32 = 1 << 17;
+const NCFGR_CLK_SHIFT: u32 = 18;
+
+// Network status register
+const NSR_IDLE: u32 = 1 << 2;
+
+// PHY maintenance register, clause 22 frames
+const MAN_CLTTO: u32 = 1 << 30;
+const MAN_OP_WRITE: u32 = 1 << 28;
+const MAN_OP_READ: u32 = 2 << 28;
+const MAN_PHYA_SHIFT: u32 = 23;
+const MAN_REGA_SHIFT: u32 = 18;
+const MAN_WTN: u32 = 2 << 16;
+
+// User register, set selects MII instead of RMII
+const UR_MII: u32 = 1 << 0;
+
+// DMA configuration register
+const DCFGR_FBLDO_INCR4: u32 = 4;
+const DCFGR_RXBMS_FULL: u32 = 3 << 8;
+const DCFGR_TXPBMS: u32 = 1 << 10;
+const DCFGR_DRBS_SHIFT: u32 = 16;
+
+// 1588 timer adjust register
+const TA_ADJ: u32 = 1 << 31;
+const TA_ITDT_MASK: u32 = 0x3fff_ffff;
+
+// Interrupt status flags
+const ISR_RCOMP: u32 = 1 << 1;
+const ISR_RXUBR: u32 = 1 << 2;
+const ISR_TCOMP: u32 = 1 << 7;
+const ISR_ROVR: u32 = 1 << 10;
+
+// Receive descriptor, address word
+const RX_OWNERSHIP: u32 = 1 << 0;
+const RX_WRAP: u32 = 1 << 1;
+// Receive descriptor, status word
+const RX_LENGTH_MASK: u32 = 0x1fff;
+const RX_SOF: u32 = 1 << 14;
+const RX_EOF: u32 = 1 << 15;
+
+// Transmit descriptor, status word
+const TX_LAST: u32 = 1 << 15;
+const TX_WRAP: u32 = 1 << 30;
+const TX_USED: u32 = 1 << 31;
+
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+
+/// Ethernet error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+	/// The frame does not fit into a buffer
+	FrameTooLong,
+	/// The 1588 timer has not been started by `start_ptp_clock`
+	PtpClockStopped,
+	/// There is no specific address filter with this index
+	InvalidAddressFilter,
+	/// The 1588 timer would stop or its increment per clock cycle does not fit the register
+	InvalidPtpRate,
+	#[doc(hidden)]
+	_Extensible,
+}
+
+/// Link speed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Speed {
+	Mbps10,
+	Mbps100,
+}
+
+/// Link duplex mode
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Duplex {
+	Half,
+	Full,
+}
+
+/// Time of the 1588 timer unit
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp {
+	/// 48 bit seconds
+	pub seconds: u64,
+	pub nanoseconds: u32,
+}
+
+/// PTP event frames, every kind has its own timestamp registers
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum PtpEvent {
+	/// Sync and Delay_Req
+	Event = 0,
+	/// Pdelay_Req and Pdelay_Resp
+	PeerEvent = 1,
+}
+
+/// PTP event port of PTP over UDP
+const PTP_EVENT_PORT: u16 = 319;
+
+/// Classifies PTP over Ethernet and over UDP/IPv4 or UDP/IPv6 frames
+fn ptp_event(frame: &[u8]) -> Option<PtpEvent> {
+	let be16 = |offset: usize| frame.get(offset..offset + 2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+
+	let mut offset = 12;
+	let mut ethertype = be16(offset)?;
+	// VLAN tag
+	if ethertype == 0x8100 {
+		offset += 4;
+		ethertype = be16(offset)?;
+	}
+	offset += 2;
+
+	let udp = match ethertype {
+		0x88f7 => None,
+		0x0800 => {
+			let ihl = (*frame.get(offset)? & 0xf) as usize * 4;
+			if *frame.get(offset + 9)? != 17 {
+				return None;
+			}
+			Some(offset + ihl)
+		}
+		0x86dd => {
+			if *frame.get(offset + 6)? != 17 {
+				return None;
+			}
+			Some(offset + 40)
+		}
+		_ => return None,
+	};
+	if let Some(udp) = udp {
+		if be16(udp + 2)? != PTP_EVENT_PORT {
+			return None;
+		}
+		offset = udp + 8;
+	}
+
+	match *frame.get(offset)? & 0xf {
+		0 | 1 => Some(PtpEvent::Event),
+		2 | 3 => Some(PtpEvent::PeerEvent),
+		_ => None,
+	}
+}
+
+/// Reads the latched time of the last received or sent frame of `kind`
+fn event_timestamp(kind: PtpEvent, received: bool) -> Timestamp {
+	// NOTE(unsafe) the timestamp registers are read only
+	let gmac = unsafe { &*GMAC::ptr() };
+	let (sh, sl, n) = match (kind, received) {
+		(PtpEvent::Event, false) => (gmac.gmac_eftsh.read().bits(), gmac.gmac_eftsl.read().bits(), gmac.gmac_eftn.read().bits()),
+		(PtpEvent::Event, true) => (gmac.gmac_efrsh.read().bits(), gmac.gmac_efrsl.read().bits(), gmac.gmac_efrn.read().bits()),
+		(PtpEvent::PeerEvent, false) => (gmac.gmac_peftsh.read().bits(), gmac.gmac_peftsl.read().bits(), gmac.gmac_peftn.read().bits()),
+		(PtpEvent::PeerEvent, true) => (gmac.gmac_pefrsh.read().bits(), gmac.gmac_pefrsl.read().bits(), gmac.gmac_pefrn.read().bits()),
+	};
+
+	Timestamp {
+		seconds: ((sh & 0xffff) as u64) << 32 | sl as u64,
+		nanoseconds: n & 0x3fff_ffff,
+	}
+}
+
+/// Interrupt event
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+	/// A frame was received
+	Received,
+	/// A frame was sent
+	Transmitted,
+	/// A frame was dropped because no receive buffer was free
+	RxBufferFull,
+}
+
+impl Event {
+	fn mask(&self) -> u32 {
+		match self {
+			Event::Received => ISR_RCOMP,
+			Event::Transmitted => ISR_TCOMP,
+			Event::RxBufferFull => ISR_RXUBR | ISR_ROVR,
+		}
+	}
+}
+
+pub mod config {
+	use super::{Duplex, Speed};
+
+	pub struct EthConfig {
+		pub mac_address: [u8; 6],
+		pub speed: Speed,
+		pub duplex: Duplex,
+		/// Receives broadcast frames
+		pub broadcast: bool,
+		/// Receives all frames regardless of their destination
+		pub promiscuous: bool,
+	}
+
+	impl EthConfig {
+		pub fn new(mac_address: [u8; 6]) -> Self {
+			EthConfig {
+				mac_address,
+				speed: Speed::Mbps100,
+				duplex: Duplex::Full,
+				broadcast: true,
+				promiscuous: false,
+			}
+		}
+
+		pub fn speed(mut self, speed: Speed) -> Self {
+			self.speed = speed;
+			self
+		}
+
+		pub fn duplex(mut self, duplex: Duplex) -> Self {
+			self.duplex = duplex;
+			self
+		}
+
+		pub fn broadcast(mut self, broadcast: bool) -> Self {
+			self.broadcast = broadcast;
+			self
+		}
+
+		pub fn promiscuous(mut self, promiscuous: bool) -> Self {
+			self.promiscuous = promiscuous;
+			self
+		}
+	}
+}
+
+macro_rules! eth_pins {
+	($($Trait:ident: $PIN:ident,)+) => {
+		$(
+			pub trait $Trait {}
+			impl $Trait for $PIN<PeripheralCntr<PeriphA>> {}
+		)+
+	}
+}
+
+eth_pins! {
+	PinTxck: PD0,
+	PinTxen: PD1,
+	PinTx0: PD2,
+	PinTx1: PD3,
+	PinRxdv: PD4,
+	PinRx0: PD5,
+	PinRx1: PD6,
+	PinRxer: PD7,
+	PinCrs: PD10,
+	PinRx2: PD11,
+	PinRx3: PD12,
+	PinCol: PD13,
+	PinRxck: PD14,
+	PinTx2: PD15,
+	PinTx3: PD16,
+	PinTxer: PD17,
+	PinMdc: PD8,
+	PinMdio: PD9,
+}
+
+/// Data pins of the PHY interface
+///
+/// RMII: (REFCK, TXEN, TX0, TX1, CRSDV, RX0, RX1, RXER), REFCK and CRSDV are TXCK and RXDV.
+///
+/// MII: (TXCK, TXEN, TX0, TX1, TX2, TX3, TXER, RXCK, RXDV, RX0, RX1, RX2, RX3, RXER, CRS, COL)
+pub trait Pins {
+	const MII: bool;
+}
+
+impl<REFCK, TXEN, TX0, TX1, CRSDV, RX0, RX1, RXER> Pins for (REFCK, TXEN, TX0, TX1, CRSDV, RX0, RX1, RXER)
+where
+	REFCK: PinTxck,
+	TXEN: PinTxen,
+	TX0: PinTx0,
+	TX1: PinTx1,
+	CRSDV: PinRxdv,
+	RX0: PinRx0,
+	RX1: PinRx1,
+	RXER: PinRxer,
+{
+	const MII: bool = false;
+}
+
+impl<TXCK, TXEN, TX0, TX1, TX2, TX3, TXER, RXCK, RXDV, RX0, RX1, RX2, RX3, RXER, CRS, COL> Pins
+	for (TXCK, TXEN, TX0, TX1, TX2, TX3, TXER, RXCK, RXDV, RX0, RX1, RX2, RX3, RXER, CRS, COL)
+where
+	TXCK: PinTxck,
+	TXEN: PinTxen,
+	TX0: PinTx0,
+	TX1: PinTx1,
+	TX2: PinTx2,
+	TX3: PinTx3,
+	TXER: PinTxer,
+	RXCK: PinRxck,
+	RXDV: PinRxdv,
+	RX0: PinRx0,
+	RX1: PinRx1,
+	RX2: PinRx2,
+	RX3: PinRx3,
+	RXER: PinRxer,
+	CRS: PinCrs,
+	COL: PinCol,
+{
+	const MII: bool = true;
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct RxDescriptor {
+	addr: u32,
+	status: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct TxDescriptor {
+	addr: u32,
+	status: u32,
+}
+
+/// Descriptors and frame buffers shared with the GMAC
+///
+/// Aligned to its size, so that it can be covered by a single MPU region.
+#[repr(C, align(32768))]
+pub struct DescriptorRing {
+	rx: [RxDescriptor; RX_BUFFERS],
+	tx: [TxDescriptor; TX_BUFFERS],
+	// unused priority queues still need valid descriptors
+	dummy_rx: RxDescriptor,
+	dummy_tx: TxDescriptor,
+	rx_buffers: [[u8; BUFFER_SIZE]; RX_BUFFERS],
+	tx_buffers: [[u8; BUFFER_SIZE]; TX_BUFFERS],
+}
+
+impl DescriptorRing {
+	const SIZE: MpuRegionSize = MpuRegionSize::REGION32_K;
+
+	pub const fn new() -> Self {
+		DescriptorRing {
+			rx: [RxDescriptor { addr: 0, status: 0 }; RX_BUFFERS],
+			tx: [TxDescriptor { addr: 0, status: 0 }; TX_BUFFERS],
+			dummy_rx: RxDescriptor { addr: 0, status: 0 },
+			dummy_tx: TxDescriptor { addr: 0, status: 0 },
+			rx_buffers: [[0; BUFFER_SIZE]; RX_BUFFERS],
+			tx_buffers: [[0; BUFFER_SIZE]; TX_BUFFERS],
+		}
+	}
+}
+
+/// Receive side of the descriptor ring
+struct RxRing {
+	descriptors: &'static mut [RxDescriptor; RX_BUFFERS],
+	buffers: &'static mut [[u8; BUFFER_SIZE]; RX_BUFFERS],
+	index: usize,
+}
+
+impl RxRing {
+	fn init(&mut self) {
+		for (i, (desc, buf)) in self.descriptors.iter_mut().zip(self.buffers.iter()).enumerate() {
+			let mut addr = buf.as_ptr() as u32;
+			if i == RX_BUFFERS - 1 {
+				addr |= RX_WRAP;
+			}
+			unsafe {
+				ptr::write_volatile(&mut desc.status, 0);
+				ptr::write_volatile(&mut desc.addr, addr);
+			}
+		}
+		self.index = 0;
+	}
+
+	fn is_available(&self) -> bool {
+		unsafe { ptr::read_volatile(&self.descriptors[self.index].addr) & RX_OWNERSHIP != 0 }
+	}
+
+	fn receive<R, F>(&mut self, f: F) -> Option<R>
+	where
+		F: FnOnce(&mut [u8]) -> R,
+	{
+		loop {
+			if !self.is_available() {
+				return None;
+			}
+
+			// the buffer has to be read after the ownership bit
+			cortex_m::asm::dmb();
+			atomic::compiler_fence(Ordering::Acquire);
+
+			let index = self.index;
+			let status = unsafe { ptr::read_volatile(&self.descriptors[index].status) };
+			let len = (status & RX_LENGTH_MASK) as usize;
+			// frames spread over multiple buffers can only be longer than any valid frame
+			let result = if status & (RX_SOF | RX_EOF) == RX_SOF | RX_EOF && len <= BUFFER_SIZE {
+				Some(f(&mut self.buffers[index][..len]))
+			} else {
+				None
+			};
+
+			cortex_m::asm::dmb();
+			atomic::compiler_fence(Ordering::Release);
+
+			// hand the buffer back to the GMAC
+			let desc = &mut self.descriptors[index];
+			unsafe {
+				let addr = ptr::read_volatile(&desc.addr);
+				ptr::write_volatile(&mut desc.addr, addr & !RX_OWNERSHIP);
+			}
+			self.index = (index + 1) % RX_BUFFERS;
+
+			if result.is_some() {
+				return result;
+			}
+		}
+	}
+}
+
+/// Transmit side of the descriptor ring
+struct TxRing {
+	descriptors: &'static mut [TxDescriptor; TX_BUFFERS],
+	buffers: &'static mut [[u8; BUFFER_SIZE]; TX_BUFFERS],
+	index: usize,
+	// the last sent PTP event frame still waiting for its timestamp
+	ptp_pending: Option<PtpEvent>,
+	ptp_last: [Timestamp; 2],
+}
+
+impl TxRing {
+	fn init(&mut self) {
+		for (i, (desc, buf)) in self.descriptors.iter_mut().zip(self.buffers.iter()).enumerate() {
+			let mut status = TX_USED;
+			if i == TX_BUFFERS - 1 {
+				status |= TX_WRAP;
+			}
+			unsafe {
+				ptr::write_volatile(&mut desc.addr, buf.as_ptr() as u32);
+				ptr::write_volatile(&mut desc.status, status);
+			}
+		}
+		self.index = 0;
+	}
+
+	fn is_available(&self) -> bool {
+		unsafe { ptr::read_volatile(&self.descriptors[self.index].status) & TX_USED != 0 }
+	}
+
+	fn send<R, F>(&mut self, len: usize, f: F) -> nb::Result<R, Error>
+	where
+		F: FnOnce(&mut [u8]) -> R,
+	{
+		if len > MTU {
+			return Err(nb::Error::Other(Error::FrameTooLong));
+		}
+		if !self.is_available() {
+			return Err(nb::Error::WouldBlock);
+		}
+
+		let index = self.index;
+		let result = f(&mut self.buffers[index][..len]);
+		if let Some(kind) = ptp_event(&self.buffers[index][..len]) {
+			self.ptp_pending = Some(kind);
+		}
+
+		let mut status = len as u32 | TX_LAST;
+		if index == TX_BUFFERS - 1 {
+			status |= TX_WRAP;
+		}
+
+		// the buffer has to be written before the descriptor is handed to the GMAC
+		cortex_m::asm::dmb();
+		atomic::compiler_fence(Ordering::Release);
+		unsafe { ptr::write_volatile(&mut self.descriptors[index].status, status) };
+		cortex_m::asm::dsb();
+
+		// NOTE(unsafe) TSTART only starts the transmission, the other bits are not changed
+		unsafe { (*GMAC::ptr()).gmac_ncr.modify(|r, w| w.bits(r.bits() | NCR_TSTART)) };
+		self.index = (index + 1) % TX_BUFFERS;
+
+		Ok(result)
+	}
+
+	fn timestamp(&mut self) -> Option<Timestamp> {
+		let kind = self.ptp_pending?;
+		let timestamp = event_timestamp(kind, false);
+		// the registers still hold the previous time until the frame has left
+		if timestamp == self.ptp_last[kind as usize] {
+			return None;
+		}
+		self.ptp_last[kind as usize] = timestamp;
+		self.ptp_pending = None;
+		Some(timestamp)
+	}
+}
+
+/// Selects the MDC divider, MDC must not exceed 2.5 MHz
+fn mdc_clock_divider(mck: u32) -> u32 {
+	match mck {
+		0..=20_000_000 => 0,
+		20_000_001..=40_000_000 => 1,
+		40_000_001..=80_000_000 => 2,
+		80_000_001..=120_000_000 => 3,
+		120_000_001..=160_000_000 => 4,
+		_ => 5,
+	}
+}
+
+/// Computes the bit of the 64 bit hash register matching `addr`
+fn hash_index(addr: &[u8; 6]) -> u32 {
+	let mut index = 0;
+	for bit in 0..48 {
+		if addr[bit / 8] & (1 << (bit % 8)) != 0 {
+			index ^= 1 << (bit % 6);
+		}
+	}
+	index
+}
+
+/// GMAC abstraction
+pub struct Ethernet<PINS> {
+	gmac: GMAC,
+	pins: PINS,
+	rx: RxRing,
+	tx: TxRing,
+	// nominal 1588 timer increment per clock cycle in 2^-16 ns
+	ptp_increment: u64,
+}
+
+impl<PINS: Pins> Ethernet<PINS> {
+	/// Configures the GMAC and enables reception and transmission
+	///
+	/// `ring` is marked as non cacheable by a new MPU region, the MPU is enabled if it is not
+	/// already. The link settings have to match
+	/// the ones negotiated by the PHY.
+	pub fn new(
+		gmac: GMAC,
+		pins: PINS,
+		ring: &'static mut DescriptorRing,
+		config: config::EthConfig,
+		mpu: &mut MPU,
+		clocks: &Clocks,
+		pmc: &mut PMC,
+	) -> Result<Self, MemoryRegionsFull> {
+		mpu.add_region(ProtectedMemoryRegion {
+			base_address : &*ring as *const DescriptorRing as *const u32,
+			size : DescriptorRing::SIZE,
+			subregions : MpuSubregions::ALL,
+			executable : false,
+			permissions : MpuAccessPolicy::ReadWrite,
+			attributes : MpuMemoryAttributes::Normal {
+				shareable : true,
+				cache_policy : MpuCachePolicy::NonCacheable,
+			},
+		})?;
+		if !mpu.is_enabled() {
+			mpu.enable();
+		}
+		// lines cached before the region took effect would otherwise be evicted over the ring later
+		crate::cache::clean_invalidate_dcache(&*ring as *const DescriptorRing as usize, core::mem::size_of::<DescriptorRing>());
+
+		//enable peripheral clock in pmc
+		pmc.pmc_pcer1.write(|w| w.pid39().set_bit() );
+
+		gmac.gmac_ncr.write(|w| unsafe { w.bits(0) });
+		gmac.gmac_idr.write(|w| unsafe { w.bits(0xffff_ffff) });
+		let _ = gmac.gmac_isr.read().bits();
+		gmac.gmac_ncr.write(|w| unsafe { w.bits(NCR_CLRSTAT) });
+		gmac.gmac_tsr.write(|w| unsafe { w.bits(0xffff_ffff) });
+		gmac.gmac_rsr.write(|w| unsafe { w.bits(0xffff_ffff) });
+
+		let DescriptorRing { rx, tx, dummy_rx, dummy_tx, rx_buffers, tx_buffers } = ring;
+		let mut rx = RxRing { descriptors: rx, buffers: rx_buffers, index: 0 };
+		let mut tx = TxRing {
+			descriptors: tx,
+			buffers: tx_buffers,
+			index: 0,
+			ptp_pending: None,
+			ptp_last: [event_timestamp(PtpEvent::Event, false), event_timestamp(PtpEvent::PeerEvent, false)],
+		};
+		rx.init();
+		tx.init();
+		dummy_rx.addr = RX_OWNERSHIP | RX_WRAP;
+		dummy_tx.status = TX_USED | TX_WRAP;
+
+		let mut ncfgr = mdc_clock_divider(clocks.mck().0) << NCFGR_CLK_SHIFT | NCFGR_RFCS | NCFGR_MAXFS;
+		if config.speed == Speed::Mbps100 {
+			ncfgr |= NCFGR_SPD;
+		}
+		if config.duplex == Duplex::Full {
+			ncfgr |= NCFGR_FD;
+		}
+		if !config.broadcast {
+			ncfgr |= NCFGR_NBC;
+		}
+		if config.promiscuous {
+			ncfgr |= NCFGR_CAF;
+		}
+		gmac.gmac_ncfgr.write(|w| unsafe { w.bits(ncfgr) });
+		gmac.gmac_ur.write(|w| unsafe { w.bits(if PINS::MII { UR_MII } else { 0 }) });
+		gmac.gmac_dcfgr.write(|w| unsafe {
+			w.bits(DCFGR_FBLDO_INCR4
+				| DCFGR_RXBMS_FULL
+				| DCFGR_TXPBMS
+				| ((BUFFER_SIZE / 64) as u32) << DCFGR_DRBS_SHIFT)
+		});
+
+		gmac.gmac_rbqb.write(|w| unsafe { w.bits(rx.descriptors.as_ptr() as u32) });
+		gmac.gmac_tbqb.write(|w| unsafe { w.bits(tx.descriptors.as_ptr() as u32) });
+		for rbqbapq in gmac.gmac_rbqbapq.iter() {
+			rbqbapq.write(|w| unsafe { w.bits(&*dummy_rx as *const RxDescriptor as u32) });
+		}
+		for tbqbapq in gmac.gmac_tbqbapq.iter() {
+			tbqbapq.write(|w| unsafe { w.bits(&*dummy_tx as *const TxDescriptor as u32) });
+		}
+
+		let mut eth = Ethernet { gmac, pins, rx, tx, ptp_increment: 0 };
+		// filter 0 always exists
+		let _ = eth.set_mac_address(0, Some(config.mac_address));
+
+		eth.gmac.gmac_ncr.write(|w| unsafe { w.bits(NCR_RXEN | NCR_TXEN | NCR_MPE) });
+
+		Ok(eth)
+	}
+
+	/// Updates the link settings, e.g. after the PHY completed auto-negotiation
+	pub fn set_link(&mut self, speed: Speed, duplex: Duplex) {
+		self.gmac.gmac_ncfgr.modify(|r, w| unsafe {
+			let mut bits = r.bits() & !(NCFGR_SPD | NCFGR_FD);
+			if speed == Speed::Mbps100 {
+				bits |= NCFGR_SPD;
+			}
+			if duplex == Duplex::Full {
+				bits |= NCFGR_FD;
+			}
+			w.bits(bits)
+		});
+	}
+
+	/// Sets the specific address filter `index`, 0 to 3, `None` disables it
+	pub fn set_mac_address(&mut self, index: usize, addr: Option<[u8; 6]>) -> Result<(), Error> {
+		let sa = self.gmac.gmac_sa.get(index).ok_or(Error::InvalidAddressFilter)?;
+		match addr {
+			// writing the bottom register disables the filter until the top register is written
+			Some(addr) => unsafe {
+				sa.gmac_sab.write(|w| w.bits(u32::from_le_bytes([addr[0], addr[1], addr[2], addr[3]])));
+				sa.gmac_sat.write(|w| w.bits(u32::from(addr[4]) | u32::from(addr[5]) << 8));
+			},
+			None => sa.gmac_sab.write(|w| unsafe { w.bits(0) }),
+		}
+		Ok(())
+	}
+
+	/// Receives frames sent to the multicast address `addr`
+	///
+	/// Multicast addresses are matched by a hash, so some other multicast frames pass as well.
+	pub fn add_multicast(&mut self, addr: &[u8; 6]) {
+		let index = hash_index(addr);
+		if index < 32 {
+			self.gmac.gmac_hrb.modify(|r, w| unsafe { w.bits(r.bits() | 1 << index) });
+		} else {
+			self.gmac.gmac_hrt.modify(|r, w| unsafe { w.bits(r.bits() | 1 << (index - 32)) });
+		}
+		self.gmac.gmac_ncfgr.modify(|r, w| unsafe { w.bits(r.bits() | NCFGR_MTIHEN) });
+	}
+
+	/// Stops receiving multicast frames
+	pub fn clear_multicast(&mut self) {
+		self.gmac.gmac_ncfgr.modify(|r, w| unsafe { w.bits(r.bits() & !NCFGR_MTIHEN) });
+		self.gmac.gmac_hrb.write(|w| unsafe { w.bits(0) });
+		self.gmac.gmac_hrt.write(|w| unsafe { w.bits(0) });
+	}
+
+	/// Receives all frames regardless of their destination
+	pub fn set_promiscuous(&mut self, promiscuous: bool) {
+		self.gmac.gmac_ncfgr.modify(|r, w| unsafe {
+			if promiscuous {
+				w.bits(r.bits() | NCFGR_CAF)
+			} else {
+				w.bits(r.bits() & !NCFGR_CAF)
+			}
+		});
+	}
+
+	/// Calls `f` with the next received frame, returns `None` if no frame was received
+	pub fn receive<R, F>(&mut self, f: F) -> Option<R>
+	where
+		F: FnOnce(&[u8]) -> R,
+	{
+		self.rx.receive(|frame| f(frame))
+	}
+
+	/// Like `receive`, additionally passes the reception time of PTP event frames
+	pub fn receive_timestamped<R, F>(&mut self, f: F) -> Option<R>
+	where
+		F: FnOnce(&[u8], Option<Timestamp>) -> R,
+	{
+		self.rx.receive(|frame| {
+			let timestamp = ptp_event(frame).map(|kind| event_timestamp(kind, true));
+			f(frame, timestamp)
+		})
+	}
+
+	/// Sends a frame of `len` bytes without FCS, which is filled by `f`
+	pub fn send<R, F>(&mut self, len: usize, f: F) -> nb::Result<R, Error>
+	where
+		F: FnOnce(&mut [u8]) -> R,
+	{
+		self.tx.send(len, f)
+	}
+
+	/// Returns the transmission time of the last sent PTP event frame once it has left
+	pub fn tx_timestamp(&mut self) -> nb::Result<Timestamp, Void> {
+		self.tx.timestamp().ok_or(nb::Error::WouldBlock)
+	}
+
+	/// Starts the 1588 timer at zero, counting nanoseconds with the peripheral clock
+	pub fn start_ptp_clock(&mut self, clocks: &Clocks) {
+		self.ptp_increment = ((NANOS_PER_SECOND as u64) << 16) / clocks.mck().0 as u64;
+		self.set_ptp_increment(self.ptp_increment);
+		self.set_ptp_time(Timestamp::default());
+	}
+
+	fn set_ptp_increment(&mut self, increment: u64) {
+		self.gmac.gmac_tisubn.write(|w| unsafe { w.bits((increment & 0xffff) as u32) });
+		self.gmac.gmac_ti.write(|w| unsafe { w.bits(((increment >> 16) & 0xff) as u32) });
+	}
+
+	/// Current time of the 1588 timer
+	pub fn ptp_time(&self) -> Timestamp {
+		loop {
+			let sl = self.gmac.gmac_tsl.read().bits();
+			let sh = self.gmac.gmac_tsh.read().bits();
+			let n = self.gmac.gmac_tn.read().bits();
+			// retry if the seconds rolled over in between
+			if self.gmac.gmac_tsl.read().bits() == sl {
+				return Timestamp {
+					seconds: ((sh & 0xffff) as u64) << 32 | sl as u64,
+					nanoseconds: n & 0x3fff_ffff,
+				};
+			}
+		}
+	}
+
+	/// Sets the time of the 1588 timer
+	pub fn set_ptp_time(&mut self, time: Timestamp) {
+		self.gmac.gmac_tsh.write(|w| unsafe { w.bits((time.seconds >> 32) as u32 & 0xffff) });
+		self.gmac.gmac_tsl.write(|w| unsafe { w.bits(time.seconds as u32) });
+		self.gmac.gmac_tn.write(|w| unsafe { w.bits(time.nanoseconds) });
+	}
+
+	/// Shifts the 1588 timer by `offset` nanoseconds
+	///
+	/// Offsets below one second are applied by the hardware without losing time, larger ones
+	/// set the timer.
+	pub fn adjust_ptp_time(&mut self, offset: i64) -> Result<(), Error> {
+		if self.ptp_increment == 0 {
+			return Err(Error::PtpClockStopped);
+		}
+
+		if offset.unsigned_abs() < NANOS_PER_SECOND as u64 {
+			let mut ta = offset.unsigned_abs() as u32 & TA_ITDT_MASK;
+			if offset < 0 {
+				ta |= TA_ADJ;
+			}
+			self.gmac.gmac_ta.write(|w| unsafe { w.bits(ta) });
+		} else {
+			let now = self.ptp_time();
+			let nanos = (now.seconds as i64 * NANOS_PER_SECOND + now.nanoseconds as i64 + offset).max(0);
+			self.set_ptp_time(Timestamp {
+				seconds: (nanos / NANOS_PER_SECOND) as u64,
+				nanoseconds: (nanos % NANOS_PER_SECOND) as u32,
+			});
+		}
+		Ok(())
+	}
+
+	/// Speeds the 1588 timer up or slows it down by `ppb` parts per billion of its nominal rate
+	///
+	/// The increment per clock cycle has a resolution of 2^-16 ns, so the rate is rounded to
+	/// steps of 2^-16 ns / clock period, about 2.3 ppm at 150 MHz. A servo has to keep
+	/// correcting the remaining error with `adjust_ptp_time`. Rates that would stop the timer
+	/// or don't fit into the increment register are rejected.
+	pub fn adjust_ptp_frequency(&mut self, ppb: i32) -> Result<(), Error> {
+		if self.ptp_increment == 0 {
+			return Err(Error::PtpClockStopped);
+		}
+
+		let nominal = self.ptp_increment as i64;
+		let delta = nominal * ppb as i64;
+		// round to the nearest step instead of towards zero
+		let half = if delta < 0 { -NANOS_PER_SECOND / 2 } else { NANOS_PER_SECOND / 2 };
+		let increment = nominal + (delta + half) / NANOS_PER_SECOND;
+		// TI holds at most 255 ns
+		if increment < 1 || increment >> 16 > 0xff {
+			return Err(Error::InvalidPtpRate);
+		}
+		self.set_ptp_increment(increment as u64);
+		Ok(())
+	}
+
+	/// Starts an interrupt event
+	pub fn listen(&mut self, event: Event) {
+		self.gmac.gmac_ier.write(|w| unsafe { w.bits(event.mask()) });
+	}
+
+	/// Stops an interrupt event
+	pub fn unlisten(&mut self, event: Event) {
+		self.gmac.gmac_idr.write(|w| unsafe { w.bits(event.mask()) });
+	}
+
+	/// Clears all pending events, returns if `event` was pending
+	pub fn is_pending(&mut self, event: Event) -> bool {
+		self.gmac.gmac_isr.read().bits() & event.mask() != 0
+	}
+
+	/// Accesses the PHY registers through the management port
+	///
+	/// The port borrows the `Ethernet`, it can't outlive the enabled management port. No frames
+	/// can be sent or received while a PHY driver holds it, so the driver is created for each
+	/// access, e.g. to poll the link, and released again to get the pins back. `GenericPhy::new`
+	/// doesn't access the PHY, `Ksz8081::new` reads its identifier every time.
+	pub fn mdio<MDC: PinMdc, MDIO: PinMdio>(&self, mdc: MDC, mdio: MDIO) -> Mdio<'_, MDC, MDIO> {
+		Mdio { gmac: &self.gmac, mdc, mdio }
+	}
+
+	/// Disables the GMAC and releases it and the pins, the ring stays marked as non cacheable
+	pub fn release(self) -> (GMAC, PINS) {
+		self.gmac.gmac_ncr.write(|w| unsafe { w.bits(0) });
+		self.gmac.gmac_idr.write(|w| unsafe { w.bits(0xffff_ffff) });
+		(self.gmac, self.pins)
+	}
+}
+
+/// Management port of the GMAC
+pub struct Mdio<'a, MDC, MDIO> {
+	gmac: &'a GMAC,
+	mdc: MDC,
+	mdio: MDIO,
+}
+
+impl<'a, MDC, MDIO> Mdio<'a, MDC, MDIO> {
+	fn transfer(&mut self, man: u32) -> u16 {
+		let gmac = self.gmac;
+		gmac.gmac_man.write(|w| unsafe { w.bits(man | MAN_CLTTO | MAN_WTN) });
+		while gmac.gmac_nsr.read().bits() & NSR_IDLE == 0 {
+			//Wait for the frame to be shifted out
+		}
+		gmac.gmac_man.read().bits() as u16
+	}
+
+	/// Releases the pins
+	pub fn release(self) -> (MDC, MDIO) {
+		(self.mdc, self.mdio)
+	}
+}
+
+impl<'a, MDC, MDIO> phy::Mdio for Mdio<'a, MDC, MDIO> {
+	fn read(&mut self, phy: u8, reg: u8) -> u16 {
+		self.transfer(MAN_OP_READ
+			| ((phy & 0x1f) as u32) << MAN_PHYA_SHIFT
+			| ((reg & 0x1f) as u32) << MAN_REGA_SHIFT)
+	}
+
+	fn write(&mut self, phy: u8, reg: u8, value: u16) {
+		self.transfer(MAN_OP_WRITE
+			| ((phy & 0x1f) as u32) << MAN_PHYA_SHIFT
+			| ((reg & 0x1f) as u32) << MAN_REGA_SHIFT
+			| value as u32);
+	}
+}
+
+#[cfg(feature = "smoltcp-phy")]
+mod device {
+	use smoltcp::phy::{self, Device, DeviceCapabilities};
+	use smoltcp::time::Instant;
+
+	use super::{Ethernet, Pins, RxRing, TxRing, MTU};
+
+	impl<'a, PINS: Pins + 'a> Device<'a> for Ethernet<PINS> {
+		type RxToken = RxToken<'a>;
+		type TxToken = TxToken<'a>;
+
+		fn receive(&'a mut self) -> Option<(RxToken<'a>, TxToken<'a>)> {
+			if self.rx.is_available() && self.tx.is_available() {
+				Some((RxToken(&mut self.rx), TxToken(&mut self.tx)))
+			} else {
+				None
+			}
+		}
+
+		fn transmit(&'a mut self) -> Option<TxToken<'a>> {
+			if self.tx.is_available() {
+				Some(TxToken(&mut self.tx))
+			} else {
+				None
+			}
+		}
+
+		fn capabilities(&self) -> DeviceCapabilities {
+			let mut caps = DeviceCapabilities::default();
+			caps.max_transmission_unit = MTU;
+			caps.max_burst_size = Some(1);
+			caps
+		}
+	}
+
+	pub struct RxToken<'a>(&'a mut RxRing);
+
+	impl<'a> phy::RxToken for RxToken<'a> {
+		fn consume<R, F>(self, _timestamp: Instant, f: F) -> smoltcp::Result<R>
+		where
+			F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+		{
+			self.0.receive(f).unwrap_or(Err(smoltcp::Error::Exhausted))
+		}
+	}
+
+	pub struct TxToken<'a>(&'a mut TxRing);
+
+	impl<'a> phy::TxToken for TxToken<'a> {
+		fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> smoltcp::Result<R>
+		where
+			F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+		{
+			match self.0.send(len, f) {
+				Ok(result) => result,
+				Err(nb::Error::WouldBlock) => Err(smoltcp::Error::Exhausted),
+				Err(nb::Error::Other(_)) => Err(smoltcp::Error::Truncated),
+			}
+		}
+	}
+}
+
+#[cfg(feature = "smoltcp-phy")]
+pub use device::{RxToken, TxToken};
@@ -0,0 +1,820 @@
+//! Inter-Integrated Circuit (TWIHS)
+
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+
+use crate::target_device::{TWIHS0, TWIHS1, TWIHS2};
+use crate::target_device::PMC;
+
+use crate::gpio::{PeripheralCntr, PeriphA, PeriphC};
+use crate::gpio::pioa::{PA3, PA4};
+use crate::gpio::piob::{PB4, PB5};
+use crate::gpio::piod::{PD27, PD28};
+use crate::clock_gen::Clocks;
+use crate::time::Hertz;
+
+/// I2C error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+	/// Address or data byte was not acknowledged
+	Nack,
+	/// Another master won the bus arbitration
+	ArbitrationLost,
+	/// The transfer did not make progress in time
+	Timeout,
+	/// The buffers are longer than a single transfer can handle
+	InvalidLength,
+	/// A received byte was not read in time, only without clock stretching
+	Overrun,
+	/// No byte was available to transmit in time, only without clock stretching
+	Underrun,
+	/// SMBus packet error checking failed
+	Pec,
+	#[doc(hidden)]
+	_Extensible,
+}
+
+/// Bus speed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Speed {
+	/// 100kHz
+	Standard,
+	/// 400kHz
+	Fast,
+	/// 1MHz
+	FastPlus,
+}
+
+impl Speed {
+	/// Bus frequency
+	pub fn frequency(&self) -> Hertz {
+		match self {
+			Speed::Standard => Hertz(100_000),
+			Speed::Fast => Hertz(400_000),
+			Speed::FastPlus => Hertz(1_000_000),
+		}
+	}
+
+	/// Minimum low period of the clock in nanoseconds
+	fn min_low_ns(&self) -> u32 {
+		match self {
+			Speed::Standard => 4_700,
+			Speed::Fast => 1_300,
+			Speed::FastPlus => 500,
+		}
+	}
+}
+
+pub mod config {
+	use super::Speed;
+
+	pub struct I2cConfig {
+		pub speed: Speed,
+		pub timeout: u32,
+	}
+
+	impl I2cConfig {
+		pub fn speed(mut self, speed: Speed) -> Self {
+			self.speed = speed;
+			self
+		}
+
+		/// Number of status polls without progress before a transfer fails with `Error::Timeout`
+		pub fn timeout(mut self, polls: u32) -> Self {
+			self.timeout = polls;
+			self
+		}
+	}
+
+	impl Default for I2cConfig {
+		fn default() -> I2cConfig {
+			I2cConfig {
+				speed: Speed::Standard,
+				timeout: 100_000,
+			}
+		}
+	}
+
+	pub struct SlaveConfig {
+		pub addresses: [Option<u8>; 3],
+		pub general_call: bool,
+		pub clock_stretching: bool,
+		pub smbus: bool,
+		pub pec: bool,
+		pub smbus_default_address: bool,
+		pub smbus_host_header: bool,
+		pub(super) too_many_addresses: bool,
+	}
+
+	impl SlaveConfig {
+		/// Adds a 7 bit slave address, at most 3 addresses can be used
+		pub fn address(mut self, address: u8) -> Self {
+			match self.addresses.iter_mut().find(|a| a.is_none()) {
+				Some(slot) => *slot = Some(address),
+				// reported when the peripheral is configured
+				None => self.too_many_addresses = true,
+			}
+			self
+		}
+
+		/// Reports general call writes to the handler, otherwise they are discarded
+		pub fn general_call(mut self, enable: bool) -> Self {
+			self.general_call = enable;
+			self
+		}
+
+		/// Holds SCL low while the handler has not consumed or provided a byte
+		pub fn clock_stretching(mut self, enable: bool) -> Self {
+			self.clock_stretching = enable;
+			self
+		}
+
+		/// Enables SMBus mode
+		pub fn smbus(mut self) -> Self {
+			self.smbus = true;
+			self
+		}
+
+		/// Enables SMBus packet error checking, implies SMBus mode
+		pub fn pec(mut self) -> Self {
+			self.smbus = true;
+			self.pec = true;
+			self
+		}
+
+		/// Acknowledges the SMBus device default address (0x61)
+		pub fn smbus_default_address(mut self) -> Self {
+			self.smbus = true;
+			self.smbus_default_address = true;
+			self
+		}
+
+		/// Acknowledges the SMBus host header (0x08)
+		pub fn smbus_host_header(mut self) -> Self {
+			self.smbus = true;
+			self.smbus_host_header = true;
+			self
+		}
+	}
+
+	impl Default for SlaveConfig {
+		fn default() -> SlaveConfig {
+			SlaveConfig {
+				addresses: [None; 3],
+				general_call: false,
+				clock_stretching: true,
+				smbus: false,
+				pec: false,
+				smbus_default_address: false,
+				smbus_host_header: false,
+				too_many_addresses: false,
+			}
+		}
+	}
+
+	#[derive(Debug)]
+	pub struct InvalidConfig;
+}
+
+/// Clock waveform dividers (CKDIV, CHDIV, CLDIV)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ClockDividers {
+	pub ckdiv: u8,
+	pub chdiv: u8,
+	pub cldiv: u8,
+}
+
+/// Searches the clock waveform dividers for `speed` with a peripheral clock of `mck`
+///
+/// Each half period lasts `(xLDIV * 2^CKDIV + 3)` peripheral clock cycles. The low period is
+/// stretched to the minimum required by the bus specification, the high period gets the rest.
+pub fn calc_clock_dividers(mck: Hertz, speed: Speed) -> Result<ClockDividers, config::InvalidConfig> {
+	let period = (mck.0 + speed.frequency().0 - 1) / speed.frequency().0;
+	let min_low = ((mck.0 as u64 * speed.min_low_ns() as u64 + 999_999_999) / 1_000_000_000) as u32;
+	let low = core::cmp::max(min_low, (period + 1) / 2);
+
+	if period <= low + 3 || low <= 3 {
+		return Err(config::InvalidConfig);
+	}
+	let high = period - low;
+
+	for ckdiv in 0..=7u8 {
+		// round both periods up to never exceed the bus speed limits
+		let cldiv = ((low - 3) + (1 << ckdiv) - 1) >> ckdiv;
+		let chdiv = ((high - 3) + (1 << ckdiv) - 1) >> ckdiv;
+		if cldiv <= 255 && chdiv <= 255 {
+			return Ok(ClockDividers {
+				ckdiv,
+				chdiv: chdiv as u8,
+				cldiv: cldiv as u8,
+			});
+		}
+	}
+
+	Err(config::InvalidConfig)
+}
+
+pub trait Pins<TWIHS> {}
+pub trait PinSda<TWIHS> {}
+pub trait PinScl<TWIHS> {}
+
+impl<TWIHS, SDA, SCL> Pins<TWIHS> for (SDA, SCL)
+where
+	SDA: PinSda<TWIHS>,
+	SCL: PinScl<TWIHS>,
+{
+}
+
+macro_rules! i2c_pins {
+	($($TWIHSX:ty: SDA: [$($SDA:ty),*] SCL: [$($SCL:ty),*])+) => {
+		$(
+			$(
+				impl PinSda<$TWIHSX> for $SDA {}
+			)*
+			$(
+				impl PinScl<$TWIHSX> for $SCL {}
+			)*
+		)+
+	}
+}
+
+i2c_pins! {
+	TWIHS0:
+		SDA : [
+			PA3<PeripheralCntr<PeriphA>>
+		]
+		SCL : [
+			PA4<PeripheralCntr<PeriphA>>
+		]
+	TWIHS1:
+		SDA : [
+			PB4<PeripheralCntr<PeriphA>>
+		]
+		SCL : [
+			PB5<PeripheralCntr<PeriphA>>
+		]
+	TWIHS2:
+		SDA : [
+			PD27<PeripheralCntr<PeriphC>>
+		]
+		SCL : [
+			PD28<PeripheralCntr<PeriphC>>
+		]
+}
+
+/// I2C abstraction in master mode
+pub struct I2c<TWIHS, PINS> {
+	twihs: TWIHS,
+	pins: PINS,
+	timeout: u32,
+}
+
+macro_rules! i2c_hal {
+	($( $TWIHSX:ident: (
+			$twihsX:ident,
+			$en_reg:ident,
+			$perid:ident
+		),
+	)+) => {
+		$(
+			impl<PINS> I2c<$TWIHSX, PINS> {
+				/// Configures a TWIHS peripheral as I2C master
+				pub fn $twihsX(
+					twihs: $TWIHSX,
+					pins: PINS,
+					config: config::I2cConfig,
+					clocks: &Clocks,
+					pmc: &mut PMC,
+				) -> Result<Self, config::InvalidConfig>
+				where
+					PINS: Pins<$TWIHSX>,
+				{
+					let div = calc_clock_dividers(clocks.mck(), config.speed)?;
+
+					//enable peripheral clock in pmc
+					pmc.$en_reg.write(|w| w.$perid().set_bit() );
+
+					//reset peripheral
+					twihs.twihs_cr.write(|w| w.swrst().set_bit());
+					let _ = twihs.twihs_rhr.read().bits();
+
+					twihs.twihs_cwgr.write(|w| unsafe {
+						w.ckdiv().bits(div.ckdiv);
+						w.chdiv().bits(div.chdiv);
+						w.cldiv().bits(div.cldiv)
+					});
+
+					//master mode only
+					twihs.twihs_cr.write(|w| {
+						w.svdis().set_bit();
+						w.msen().set_bit()
+					});
+
+					Ok(I2c { twihs, pins, timeout: config.timeout })
+				}
+
+				/// Releases the TWIHS peripheral and associated pins
+				pub fn release(self) -> ($TWIHSX, PINS) {
+					self.twihs.twihs_cr.write(|w| w.msdis().set_bit());
+					(self.twihs, self.pins)
+				}
+
+				/// Polls the status register until `flag` is set, bus errors abort the wait
+				fn wait(&self, flag: u32) -> Result<(), Error> {
+					for _ in 0..self.timeout {
+						let sr = self.twihs.twihs_sr.read().bits();
+						if sr & NACK != 0 {
+							return Err(Error::Nack);
+						}
+						if sr & ARBLST != 0 {
+							return Err(Error::ArbitrationLost);
+						}
+						if sr & flag != 0 {
+							return Ok(());
+						}
+					}
+
+					Err(Error::Timeout)
+				}
+
+				fn wait_txcomp(&self) -> Result<(), Error> {
+					self.wait(TXCOMP)
+				}
+
+				fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+					for byte in bytes {
+						self.wait(TXRDY)?;
+						self.twihs.twihs_thr.write(|w| unsafe { w.txdata().bits(*byte) });
+					}
+					self.wait(TXRDY)
+				}
+
+				fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+					for byte in buffer.iter_mut() {
+						self.wait(RXRDY)?;
+						*byte = self.twihs.twihs_rhr.read().rxdata().bits();
+					}
+					Ok(())
+				}
+
+				/// Recovers the peripheral after a failed transfer
+				fn abort(&mut self, e: Error) -> Error {
+					if e != Error::ArbitrationLost {
+						self.twihs.twihs_cr.write(|w| w.stop().set_bit());
+						let _ = self.wait_txcomp();
+					}
+					self.twihs.twihs_cr.write(|w| w.acmdis().set_bit());
+					let _ = self.twihs.twihs_rhr.read().bits();
+					e
+				}
+			}
+
+			impl<PINS> Write for I2c<$TWIHSX, PINS> {
+				type Error = Error;
+
+				fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Error> {
+					self.twihs.twihs_mmr.write(|w| unsafe {
+						w.dadr().bits(addr);
+						w.mread().clear_bit();
+						w.iadrsz().bits(0)
+					});
+
+					let result = if bytes.is_empty() {
+						// address only transfer
+						self.twihs.twihs_cr.write(|w| w.quick().set_bit());
+						self.wait_txcomp()
+					} else {
+						// writing the first byte starts the transfer
+						self.write_bytes(bytes)
+							.and_then(|_| {
+								self.twihs.twihs_cr.write(|w| w.stop().set_bit());
+								self.wait_txcomp()
+							})
+					};
+
+					result.map_err(|e| self.abort(e))
+				}
+			}
+
+			impl<PINS> Read for I2c<$TWIHSX, PINS> {
+				type Error = Error;
+
+				fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Error> {
+					if buffer.is_empty() {
+						return Err(Error::InvalidLength);
+					}
+
+					self.twihs.twihs_mmr.write(|w| unsafe {
+						w.dadr().bits(addr);
+						w.mread().set_bit();
+						w.iadrsz().bits(0)
+					});
+
+					let len = buffer.len();
+					let result = if len == 1 {
+						self.twihs.twihs_cr.write(|w| {
+							w.start().set_bit();
+							w.stop().set_bit()
+						});
+						self.read_bytes(buffer)
+					} else {
+						self.twihs.twihs_cr.write(|w| w.start().set_bit());
+						// the stop condition has to be requested before the last byte is received
+						self.read_bytes(&mut buffer[..len - 1])
+							.and_then(|_| {
+								self.twihs.twihs_cr.write(|w| w.stop().set_bit());
+								self.read_bytes(&mut buffer[len - 1..])
+							})
+					};
+
+					result
+						.and_then(|_| self.wait_txcomp())
+						.map_err(|e| self.abort(e))
+				}
+			}
+
+			impl<PINS> WriteRead for I2c<$TWIHSX, PINS> {
+				type Error = Error;
+
+				fn write_read(&mut self, addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+					if bytes.is_empty() {
+						return self.read(addr, buffer);
+					}
+					if buffer.is_empty() {
+						return self.write(addr, bytes);
+					}
+					if bytes.len() > 255 || buffer.len() > 255 {
+						return Err(Error::InvalidLength);
+					}
+
+					// the alternative command mode issues the repeated start and stop by itself
+					self.twihs.twihs_cr.write(|w| w.acmen().set_bit());
+					self.twihs.twihs_mmr.write(|w| unsafe {
+						w.dadr().bits(addr);
+						w.iadrsz().bits(0)
+					});
+					self.twihs.twihs_acr.write(|w| unsafe {
+						w.datal().bits(bytes.len() as u8);
+						w.dir().clear_bit();
+						w.ndatal().bits(buffer.len() as u8);
+						w.ndir().set_bit()
+					});
+					self.twihs.twihs_cr.write(|w| w.start().set_bit());
+
+					let result = self.write_bytes(bytes)
+						.and_then(|_| self.read_bytes(buffer))
+						.and_then(|_| self.wait_txcomp());
+
+					match result {
+						Ok(()) => {
+							self.twihs.twihs_cr.write(|w| w.acmdis().set_bit());
+							Ok(())
+						}
+						Err(e) => Err(self.abort(e)),
+					}
+				}
+			}
+		)+
+	}
+}
+
+// Status flags of the TWIHS status register
+const TXCOMP: u32 = 1 << 0;
+const RXRDY: u32 = 1 << 1;
+const TXRDY: u32 = 1 << 2;
+const SVREAD: u32 = 1 << 3;
+const SVACC: u32 = 1 << 4;
+const GACC: u32 = 1 << 5;
+const OVRE: u32 = 1 << 6;
+const UNRE: u32 = 1 << 7;
+const NACK: u32 = 1 << 8;
+const ARBLST: u32 = 1 << 9;
+const EOSACC: u32 = 1 << 11;
+const PECERR: u32 = 1 << 19;
+const SMBDAM: u32 = 1 << 20;
+const SMBHHM: u32 = 1 << 21;
+
+i2c_hal! {
+	TWIHS0 : (twihs0, pmc_pcer0, pid19),
+	TWIHS1 : (twihs1, pmc_pcer0, pid20),
+	TWIHS2 : (twihs2, pmc_pcer1, pid41),
+}
+
+/// SMBus alert response address
+const ALERT_RESPONSE_ADDRESS: u8 = 0x0c;
+
+/// Address a slave access was made to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Access {
+	/// One of the configured slave addresses
+	Slave,
+	GeneralCall,
+	SmbusDefaultAddress,
+	SmbusHostHeader,
+}
+
+/// Direction of a slave access, seen from the master
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+	/// The master writes, the slave receives
+	Write,
+	/// The master reads, the slave transmits
+	Read,
+}
+
+/// Answer of the slave to a read request
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Reply {
+	Data(u8),
+	/// Transmit the packet error code of the bytes sent so far
+	Pec,
+}
+
+/// Callbacks of a slave, called from `I2cSlave::on_interrupt`
+pub trait SlaveHandler {
+	/// A master addressed the slave, also called on a repeated start changing the direction
+	fn start(&mut self, _access: Access, _direction: Direction) {}
+
+	/// A byte was written by the master
+	fn write(&mut self, access: Access, byte: u8);
+
+	/// The master requests a byte
+	fn read(&mut self, access: Access) -> Reply;
+
+	/// The access ended with a stop or a repeated start to another device
+	fn stop(&mut self) {}
+
+	/// A master read the alert response address, the SMBALERT line has to be released
+	fn alert_response(&mut self) {}
+
+	fn error(&mut self, _error: Error) {}
+}
+
+/// I2C abstraction in interrupt driven slave mode
+///
+/// The slave answers to up to 3 addresses. The TWIHS does not report which of them matched, all
+/// of them are reported as `Access::Slave`.
+pub struct I2cSlave<TWIHS, PINS> {
+	twihs: TWIHS,
+	pins: PINS,
+	address: u8,
+	general_call: bool,
+	access: Option<(Access, Direction)>,
+	written: bool,
+	alert_pending: bool,
+	alert_response: bool,
+}
+
+macro_rules! i2c_slave_hal {
+	($( $TWIHSX:ident: (
+			$twihsX_slave:ident,
+			$en_reg:ident,
+			$perid:ident
+		),
+	)+) => {
+		$(
+			impl<PINS> I2cSlave<$TWIHSX, PINS> {
+				/// Configures a TWIHS peripheral as I2C slave
+				///
+				/// The slave access interrupts are enabled, the TWIHS interrupt has to be unmasked in
+				/// the NVIC and call `on_interrupt`.
+				pub fn $twihsX_slave(
+					twihs: $TWIHSX,
+					pins: PINS,
+					config: config::SlaveConfig,
+					pmc: &mut PMC,
+				) -> Result<Self, config::InvalidConfig>
+				where
+					PINS: Pins<$TWIHSX>,
+				{
+					if config.too_many_addresses {
+						return Err(config::InvalidConfig);
+					}
+					let address = match config.addresses[0] {
+						Some(address) => address,
+						None => return Err(config::InvalidConfig),
+					};
+					if config.addresses.iter().any(|a| a.map_or(false, |a| a > 0x7f)) {
+						return Err(config::InvalidConfig);
+					}
+
+					//enable peripheral clock in pmc
+					pmc.$en_reg.write(|w| w.$perid().set_bit() );
+
+					//reset peripheral
+					twihs.twihs_cr.write(|w| w.swrst().set_bit());
+					let _ = twihs.twihs_rhr.read().bits();
+
+					// the third extra address is reserved for the alert response address
+					twihs.twihs_swmr.write(|w| unsafe {
+						w.sadr1().bits(config.addresses[1].unwrap_or(0));
+						w.sadr2().bits(config.addresses[2].unwrap_or(0));
+						w.sadr3().bits(ALERT_RESPONSE_ADDRESS)
+					});
+					twihs.twihs_smr.write(|w| unsafe {
+						w.sadr().bits(address);
+						w.sadr1en().bit(config.addresses[1].is_some());
+						w.sadr2en().bit(config.addresses[2].is_some());
+						w.sadr3en().clear_bit();
+						w.smda().bit(config.smbus_default_address);
+						w.smhh().bit(config.smbus_host_header);
+						w.sclwsdis().bit(!config.clock_stretching)
+					});
+
+					twihs.twihs_cr.write(|w| {
+						if config.smbus {
+							w.smben().set_bit();
+						}
+						if config.pec {
+							w.pecen().set_bit();
+						}
+						//slave mode only
+						w.msdis().set_bit();
+						w.sven().set_bit()
+					});
+
+					twihs.twihs_ier.write(|w| unsafe { w.bits(SVACC | OVRE | UNRE | PECERR) });
+
+					Ok(I2cSlave {
+						twihs,
+						pins,
+						address,
+						general_call: config.general_call,
+						access: None,
+						written: false,
+						alert_pending: false,
+						alert_response: false,
+					})
+				}
+
+				/// Releases the TWIHS peripheral and associated pins
+				pub fn release(self) -> ($TWIHSX, PINS) {
+					self.twihs.twihs_idr.write(|w| unsafe { w.bits(0xffff_ffff) });
+					self.twihs.twihs_cr.write(|w| w.svdis().set_bit());
+					(self.twihs, self.pins)
+				}
+
+				/// Answers the next read of the SMBus alert response address with the own address
+				///
+				/// The SMBALERT line is an ordinary gpio which the application asserts, it is released
+				/// in `SlaveHandler::alert_response`. Since the TWIHS does not report which address
+				/// matched, a read access without a preceding write is taken as alert response
+				/// request while the alert is pending.
+				pub fn raise_alert(&mut self) {
+					self.alert_pending = true;
+					self.twihs.twihs_smr.modify(|_, w| w.sadr3en().set_bit());
+				}
+
+				/// Withdraws a pending alert
+				pub fn clear_alert(&mut self) {
+					self.alert_pending = false;
+					self.twihs.twihs_smr.modify(|_, w| w.sadr3en().clear_bit());
+				}
+
+				/// Handles the pending slave events, has to be called from the TWIHS interrupt
+				pub fn on_interrupt<H: SlaveHandler>(&mut self, handler: &mut H) {
+					let sr = self.twihs.twihs_sr.read().bits();
+
+					if sr & OVRE != 0 {
+						handler.error(Error::Overrun);
+					}
+					if sr & UNRE != 0 {
+						handler.error(Error::Underrun);
+					}
+					if sr & PECERR != 0 {
+						handler.error(Error::Pec);
+					}
+
+					if sr & SVACC != 0 {
+						let access = if sr & GACC != 0 {
+							Access::GeneralCall
+						} else if sr & SMBDAM != 0 {
+							Access::SmbusDefaultAddress
+						} else if sr & SMBHHM != 0 {
+							Access::SmbusHostHeader
+						} else {
+							Access::Slave
+						};
+						let direction = if sr & SVREAD != 0 { Direction::Read } else { Direction::Write };
+						let report = access != Access::GeneralCall || self.general_call;
+
+						if self.access.is_none() {
+							// serve the data interrupts until the end of the access
+							self.twihs.twihs_idr.write(|w| unsafe { w.bits(SVACC) });
+							self.twihs.twihs_ier.write(|w| unsafe { w.bits(RXRDY | TXRDY | EOSACC) });
+						}
+						if self.access != Some((access, direction)) {
+							self.access = Some((access, direction));
+							self.alert_response = direction == Direction::Read
+								&& self.alert_pending
+								&& !self.written;
+							if report && !self.alert_response {
+								handler.start(access, direction);
+							}
+						}
+
+						match direction {
+							Direction::Write if sr & RXRDY != 0 => {
+								let byte = self.twihs.twihs_rhr.read().rxdata().bits();
+								self.written = true;
+								if report {
+									handler.write(access, byte);
+								}
+							}
+							Direction::Read if sr & TXRDY != 0 && sr & NACK == 0 => {
+								if self.alert_response {
+									self.twihs.twihs_thr.write(|w| unsafe { w.txdata().bits(self.address << 1) });
+								} else {
+									match handler.read(access) {
+										Reply::Data(byte) => self.twihs.twihs_thr.write(|w| unsafe { w.txdata().bits(byte) }),
+										Reply::Pec => self.twihs.twihs_cr.write(|w| w.pecrq().set_bit()),
+									}
+								}
+							}
+							_ => {}
+						}
+					}
+
+					if sr & EOSACC != 0 && self.access.is_some() {
+						self.twihs.twihs_idr.write(|w| unsafe { w.bits(RXRDY | TXRDY | EOSACC) });
+						self.twihs.twihs_ier.write(|w| unsafe { w.bits(SVACC) });
+
+						if self.alert_response {
+							self.clear_alert();
+							self.alert_response = false;
+							handler.alert_response();
+						} else if self.access.map_or(false, |(a, _)| a != Access::GeneralCall || self.general_call) {
+							handler.stop();
+						}
+						self.access = None;
+						self.written = false;
+					}
+				}
+			}
+		)+
+	}
+}
+
+i2c_slave_hal! {
+	TWIHS0 : (twihs0_slave, pmc_pcer0, pid19),
+	TWIHS1 : (twihs1_slave, pmc_pcer0, pid20),
+	TWIHS2 : (twihs2_slave, pmc_pcer1, pid41),
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Low and high period of the generated clock in peripheral clock cycles
+	fn half_periods(div: ClockDividers) -> (u32, u32) {
+		let low = ((div.cldiv as u32) << div.ckdiv) + 3;
+		let high = ((div.chdiv as u32) << div.ckdiv) + 3;
+		(low, high)
+	}
+
+	/// Checks the bus speed and minimum low period limits
+	fn check_limits(mck: u32, speed: Speed) -> ClockDividers {
+		let div = calc_clock_dividers(Hertz(mck), speed).unwrap();
+		let (low, high) = half_periods(div);
+
+		assert!(mck / (low + high) <= speed.frequency().0);
+		assert!(low as u64 * 1_000_000_000 >= mck as u64 * speed.min_low_ns() as u64);
+		div
+	}
+
+	#[test]
+	fn standard() {
+		assert_eq!(check_limits(150_000_000, Speed::Standard), ClockDividers { ckdiv: 2, chdiv: 187, cldiv: 187 });
+		assert_eq!(check_limits(12_000_000, Speed::Standard), ClockDividers { ckdiv: 0, chdiv: 57, cldiv: 57 });
+	}
+
+	#[test]
+	fn fast() {
+		// the minimum low period is longer than half the period
+		assert_eq!(check_limits(150_000_000, Speed::Fast), ClockDividers { ckdiv: 0, chdiv: 177, cldiv: 192 });
+		assert_eq!(check_limits(12_000_000, Speed::Fast), ClockDividers { ckdiv: 0, chdiv: 11, cldiv: 13 });
+	}
+
+	#[test]
+	fn fast_plus() {
+		assert_eq!(check_limits(150_000_000, Speed::FastPlus), ClockDividers { ckdiv: 0, chdiv: 72, cldiv: 72 });
+		assert_eq!(check_limits(12_000_000, Speed::FastPlus), ClockDividers { ckdiv: 0, chdiv: 3, cldiv: 3 });
+	}
+
+	#[test]
+	fn other_clocks() {
+		for &mck in [300_000_000, 100_000_000, 48_000_000, 32_768_000].iter() {
+			for &speed in [Speed::Standard, Speed::Fast, Speed::FastPlus].iter() {
+				check_limits(mck, speed);
+			}
+		}
+	}
+
+	#[test]
+	fn no_divider_fits() {
+		// the fixed 3 cycles per half period leave no room for the dividers
+		assert!(calc_clock_dividers(Hertz(4_000_000), Speed::FastPlus).is_err());
+		assert!(calc_clock_dividers(Hertz(500_000), Speed::Standard).is_err());
+		// slower than the bus clock
+		assert!(calc_clock_dividers(Hertz(32_768), Speed::Standard).is_err());
+	}
+}
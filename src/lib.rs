@@ -14,3 +14,20 @@ pub mod ebi;
 pub mod sdram;
 pub mod smc;
 pub mod mpu;
+mod cache;
+pub mod dma;
+pub mod spi;
+pub mod qspi;
+pub mod sfdp;
+pub mod qspi_flash;
+pub mod i2c;
+pub mod timer;
+pub mod qei;
+pub mod pwm;
+pub mod adc;
+pub mod dac;
+pub mod acc;
+pub mod eth;
+pub mod phy;
+pub mod usb;
+pub mod usb_host;
@@ -274,7 +274,7 @@ pub trait Mpu {
 	/// Set Memory Region Options for specific region
 	fn set_region(&mut self, region : ProtectedMemoryRegion, rnr : u8);
 
-	/// Add Region to free MPU slot
+	/// Add Region to the first free MPU slot, fails if all slots are in use
 	fn add_region(&mut self, region : ProtectedMemoryRegion) -> Result<(), MemoryRegionsFull>;
 }
 
@@ -437,7 +437,8 @@ impl Mpu for MPU {
 			let region_attributes = self.rasr.read();
 			//check if memory region is not used already
 			if (region_attributes & 0x1) == 0 {
-				self.set_region(region, rnr)
+				self.set_region(region, rnr);
+				return Ok(());
 			}
 		}
 
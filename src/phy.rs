@@ -0,0 +1,260 @@
+//! Ethernet PHY management
+//!
+//! PHYs are accessed through clause 22 MDIO frames, e.g. by the management port of the GMAC
+//! (`eth::Mdio`). `GenericPhy` only uses the standard IEEE 802.3 registers, PHY specific drivers
+//! like `Ksz8081` add vendor registers on top of it.
+//!
+//! `eth::Mdio` borrows the `Ethernet`, so a PHY driver on top of it only lives as long as one
+//! access, e.g. a link poll, and is then released to use the `Ethernet` again.
+
+use crate::eth::{Duplex, Speed};
+
+// Standard registers
+const BMCR: u8 = 0;
+const BMSR: u8 = 1;
+const PHYID1: u8 = 2;
+const PHYID2: u8 = 3;
+const ANAR: u8 = 4;
+const ANLPAR: u8 = 5;
+
+// Basic mode control register
+const BMCR_RESTART_AN: u16 = 1 << 9;
+const BMCR_POWER_DOWN: u16 = 1 << 11;
+const BMCR_AN_ENABLE: u16 = 1 << 12;
+const BMCR_RESET: u16 = 1 << 15;
+
+// Basic mode status register
+const BMSR_LINK: u16 = 1 << 2;
+const BMSR_AN_COMPLETE: u16 = 1 << 5;
+
+// Auto-negotiation advertisement and link partner ability
+const AN_SELECTOR_802_3: u16 = 0x0001;
+const AN_10_HALF: u16 = 1 << 5;
+const AN_10_FULL: u16 = 1 << 6;
+const AN_100_HALF: u16 = 1 << 7;
+const AN_100_FULL: u16 = 1 << 8;
+
+/// Number of BMCR polls before a reset is considered failed
+const RESET_TIMEOUT: u32 = 10_000;
+
+/// Clause 22 MDIO access
+pub trait Mdio {
+	/// Reads register `reg` of the PHY at address `phy`
+	fn read(&mut self, phy: u8, reg: u8) -> u16;
+
+	/// Writes `value` to register `reg` of the PHY at address `phy`
+	fn write(&mut self, phy: u8, reg: u8, value: u16);
+}
+
+/// PHY error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+	/// The PHY did not complete its reset
+	Timeout,
+	/// The identifier does not match the expected PHY
+	UnexpectedId(u32),
+	#[doc(hidden)]
+	_Extensible,
+}
+
+/// Negotiated link
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Link {
+	pub speed: Speed,
+	pub duplex: Duplex,
+}
+
+/// Operations common to all PHYs
+pub trait Phy {
+	/// Resets the PHY and waits for the reset to complete
+	fn reset(&mut self) -> Result<(), Error>;
+
+	/// Advertises all supported modes and restarts auto-negotiation
+	fn start_autonegotiation(&mut self);
+
+	/// Returns the link if it is up and auto-negotiation has completed
+	fn link(&mut self) -> Option<Link>;
+}
+
+/// PHY using only the IEEE 802.3 standard registers
+pub struct GenericPhy<M> {
+	mdio: M,
+	addr: u8,
+}
+
+impl<M: Mdio> GenericPhy<M> {
+	/// Creates a PHY at address `addr`
+	pub fn new(mdio: M, addr: u8) -> Self {
+		GenericPhy { mdio, addr }
+	}
+
+	pub fn read(&mut self, reg: u8) -> u16 {
+		self.mdio.read(self.addr, reg)
+	}
+
+	pub fn write(&mut self, reg: u8, value: u16) {
+		self.mdio.write(self.addr, reg, value)
+	}
+
+	/// Organizationally unique identifier, model and revision
+	pub fn id(&mut self) -> u32 {
+		(self.read(PHYID1) as u32) << 16 | self.read(PHYID2) as u32
+	}
+
+	/// Powers the PHY down or up
+	pub fn set_power_down(&mut self, power_down: bool) {
+		let bmcr = self.read(BMCR);
+		if power_down {
+			self.write(BMCR, bmcr | BMCR_POWER_DOWN);
+		} else {
+			self.write(BMCR, bmcr & !BMCR_POWER_DOWN);
+		}
+	}
+
+	/// Returns if the link is up
+	///
+	/// The link status is latched low, so the first read after a link loss reports it.
+	pub fn is_link_up(&mut self) -> bool {
+		self.read(BMSR) & BMSR_LINK != 0
+	}
+
+	/// Releases the MDIO bus
+	pub fn release(self) -> M {
+		self.mdio
+	}
+}
+
+impl<M: Mdio> Phy for GenericPhy<M> {
+	fn reset(&mut self) -> Result<(), Error> {
+		self.write(BMCR, BMCR_RESET);
+		for _ in 0..RESET_TIMEOUT {
+			if self.read(BMCR) & BMCR_RESET == 0 {
+				return Ok(());
+			}
+		}
+		Err(Error::Timeout)
+	}
+
+	fn start_autonegotiation(&mut self) {
+		self.write(ANAR, AN_SELECTOR_802_3 | AN_10_HALF | AN_10_FULL | AN_100_HALF | AN_100_FULL);
+		let bmcr = self.read(BMCR);
+		self.write(BMCR, bmcr | BMCR_AN_ENABLE | BMCR_RESTART_AN);
+	}
+
+	fn link(&mut self) -> Option<Link> {
+		let bmsr = self.read(BMSR);
+		if bmsr & (BMSR_LINK | BMSR_AN_COMPLETE) != BMSR_LINK | BMSR_AN_COMPLETE {
+			return None;
+		}
+
+		// the highest mode supported by both sides
+		let common = self.read(ANAR) & self.read(ANLPAR);
+		let (speed, duplex) = if common & AN_100_FULL != 0 {
+			(Speed::Mbps100, Duplex::Full)
+		} else if common & AN_100_HALF != 0 {
+			(Speed::Mbps100, Duplex::Half)
+		} else if common & AN_10_FULL != 0 {
+			(Speed::Mbps10, Duplex::Full)
+		} else {
+			(Speed::Mbps10, Duplex::Half)
+		};
+
+		Some(Link { speed, duplex })
+	}
+}
+
+// KSZ8081 vendor registers
+const KSZ8081_ICSR: u8 = 0x1b;
+const KSZ8081_PHYCON1: u8 = 0x1e;
+const KSZ8081_PHYCON2: u8 = 0x1f;
+
+// Interrupt control and status register, enables in the upper byte
+const KSZ8081_ICSR_LINK_UP: u16 = 1 << 0;
+const KSZ8081_ICSR_LINK_DOWN: u16 = 1 << 2;
+
+// PHY control 1, operation mode indication
+const KSZ8081_PHYCON1_MODE_MASK: u16 = 0x7;
+// PHY control 2, RMII reference clock
+const KSZ8081_PHYCON2_RMII_50MHZ: u16 = 1 << 7;
+
+/// Identifier of the KSZ8081 without the revision
+const KSZ8081_ID: u32 = 0x0022_1560;
+const ID_REVISION_MASK: u32 = 0xf;
+
+/// Microchip KSZ8081 10/100 PHY, fitted to the SAM E70 Xplained board at address 0
+pub struct Ksz8081<M> {
+	phy: GenericPhy<M>,
+}
+
+impl<M: Mdio> Ksz8081<M> {
+	/// Creates the driver for the PHY at address `addr` after checking its identifier
+	pub fn new(mdio: M, addr: u8) -> Result<Self, (Error, M)> {
+		let mut phy = GenericPhy::new(mdio, addr);
+		let id = phy.id();
+		if id & !ID_REVISION_MASK != KSZ8081_ID {
+			return Err((Error::UnexpectedId(id), phy.release()));
+		}
+		Ok(Ksz8081 { phy })
+	}
+
+	/// Selects the RMII reference clock of the KSZ8081RNA, 50 MHz or a 25 MHz crystal
+	pub fn set_rmii_clock_50mhz(&mut self, enable: bool) {
+		let con2 = self.phy.read(KSZ8081_PHYCON2);
+		if enable {
+			self.phy.write(KSZ8081_PHYCON2, con2 | KSZ8081_PHYCON2_RMII_50MHZ);
+		} else {
+			self.phy.write(KSZ8081_PHYCON2, con2 & !KSZ8081_PHYCON2_RMII_50MHZ);
+		}
+	}
+
+	/// Signals link changes on the interrupt pin
+	pub fn listen_link(&mut self) {
+		self.phy.write(KSZ8081_ICSR, (KSZ8081_ICSR_LINK_UP | KSZ8081_ICSR_LINK_DOWN) << 8);
+	}
+
+	/// Stops signalling link changes
+	pub fn unlisten_link(&mut self) {
+		self.phy.write(KSZ8081_ICSR, 0);
+	}
+
+	/// Clears the pending interrupts, returns if the link changed
+	pub fn link_changed(&mut self) -> bool {
+		self.phy.read(KSZ8081_ICSR) & (KSZ8081_ICSR_LINK_UP | KSZ8081_ICSR_LINK_DOWN) != 0
+	}
+
+	/// Access to the standard registers
+	pub fn generic(&mut self) -> &mut GenericPhy<M> {
+		&mut self.phy
+	}
+
+	/// Releases the MDIO bus
+	pub fn release(self) -> M {
+		self.phy.release()
+	}
+}
+
+impl<M: Mdio> Phy for Ksz8081<M> {
+	fn reset(&mut self) -> Result<(), Error> {
+		self.phy.reset()
+	}
+
+	fn start_autonegotiation(&mut self) {
+		self.phy.start_autonegotiation()
+	}
+
+	/// Uses the operation mode indication, which also covers forced modes
+	fn link(&mut self) -> Option<Link> {
+		if !self.phy.is_link_up() {
+			return None;
+		}
+
+		match self.phy.read(KSZ8081_PHYCON1) & KSZ8081_PHYCON1_MODE_MASK {
+			0b001 => Some(Link { speed: Speed::Mbps10, duplex: Duplex::Half }),
+			0b010 => Some(Link { speed: Speed::Mbps100, duplex: Duplex::Half }),
+			0b101 => Some(Link { speed: Speed::Mbps10, duplex: Duplex::Full }),
+			0b110 => Some(Link { speed: Speed::Mbps100, duplex: Duplex::Full }),
+			// still negotiating
+			_ => None,
+		}
+	}
+}
@@ -0,0 +1,927 @@
+//! Pulse Width Modulation (PWM)
+//!
+//! Every PWM controller has four channels, each driving a complementary pair of outputs PWMH and
+//! PWML. Channels run with their own period unless they are synchronous, synchronous channels
+//! share the counter of channel 0 and their updates are applied together.
+//!
+//! The fault protection unit forces the outputs to a safe state on a fault, and the comparison
+//! units of channel 0 generate pulses on the event lines, which can trigger ADC conversions.
+
+use core::marker::PhantomData;
+
+use embedded_hal::{Pwm as PwmTrait, PwmPin};
+
+use crate::target_device::{PWM0, PWM1};
+use crate::target_device::PMC;
+use crate::target_device::pwm0::{RegisterBlock, PWM_CH_NUM};
+
+use crate::gpio::{PeripheralCntr, PeriphA, PeriphB, PeriphC, PeriphD};
+use crate::gpio::pioa::{PA0, PA1, PA2, PA5, PA7, PA8, PA9, PA11, PA12, PA13, PA14, PA15, PA16, PA17, PA19, PA20, PA21, PA23, PA24, PA25, PA26, PA28, PA30, PA31};
+use crate::gpio::piob::{PB0, PB1, PB4, PB5, PB12, PB13};
+use crate::gpio::pioc::{PC13, PC19, PC20, PC21, PC22};
+use crate::gpio::piod::{PD0, PD1, PD2, PD3, PD4, PD5, PD6, PD7, PD8, PD9, PD10, PD11, PD20, PD21, PD22, PD23, PD24, PD25, PD26, PD27};
+use crate::clock_gen::Clocks;
+use crate::time::{Hertz, NanoSeconds};
+
+// Channel mode register
+const CMR_CALG: u32 = 1 << 8;
+const CMR_CPOL: u32 = 1 << 9;
+const CMR_DTE: u32 = 1 << 16;
+
+// Largest channel prescaler, MCK/1024
+const MAX_CPRE: u8 = 10;
+
+// Comparison mode register
+const CMPM_CEN: u32 = 1 << 0;
+const CMPM_CTR_SHIFT: u32 = 4;
+const CMPM_CPR_SHIFT: u32 = 8;
+
+// Comparison value register
+const CMPV_CVM: u32 = 1 << 24;
+const CMPV_MAX: u32 = 0x00ff_ffff;
+
+/// Number of comparison units
+pub const COMPARISON_UNITS: usize = 8;
+
+/// PWM channel
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Channel {
+	C0,
+	C1,
+	C2,
+	C3,
+}
+
+impl Channel {
+	pub fn nr(&self) -> usize {
+		match self {
+			Channel::C0 => 0,
+			Channel::C1 => 1,
+			Channel::C2 => 2,
+			Channel::C3 => 3,
+		}
+	}
+
+	fn mask(&self) -> u32 {
+		1 << self.nr()
+	}
+}
+
+/// Channel counter alignment
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Alignment {
+	/// The counter counts up and restarts, the outputs change once per period
+	Left,
+	/// The counter counts up and down, the outputs are symmetric to the period center
+	Center,
+}
+
+/// Level of PWMH during the duty cycle, PWML is the complement
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Polarity {
+	ActiveHigh,
+	ActiveLow,
+}
+
+pub mod config {
+	use super::{Alignment, Polarity};
+	use crate::time::{Hertz, NanoSeconds};
+
+	pub struct ChannelConfig {
+		pub frequency: Hertz,
+		pub alignment: Alignment,
+		pub polarity: Polarity,
+		/// Delay of the rising edges of PWMH and PWML
+		pub dead_time: Option<(NanoSeconds, NanoSeconds)>,
+		pub synchronous: bool,
+	}
+
+	impl ChannelConfig {
+		pub fn new(frequency: Hertz) -> Self {
+			ChannelConfig {
+				frequency,
+				alignment: Alignment::Left,
+				polarity: Polarity::ActiveHigh,
+				dead_time: None,
+				synchronous: false,
+			}
+		}
+
+		pub fn center_aligned(mut self) -> Self {
+			self.alignment = Alignment::Center;
+			self
+		}
+
+		pub fn polarity(mut self, polarity: Polarity) -> Self {
+			self.polarity = polarity;
+			self
+		}
+
+		/// Inserts dead-time before the rising edges of PWMH and PWML
+		pub fn dead_time(mut self, high: NanoSeconds, low: NanoSeconds) -> Self {
+			self.dead_time = Some((high, low));
+			self
+		}
+
+		/// Makes the channel synchronous to channel 0, it then uses the period of channel 0
+		///
+		/// The frequency and alignment have to match the ones of channel 0.
+		pub fn synchronous(mut self) -> Self {
+			self.synchronous = true;
+			self
+		}
+	}
+
+	/// Fault input configuration
+	pub struct FaultConfig {
+		pub polarity: FaultPolarity,
+		/// The fault stays active until it is cleared, even if the input became inactive
+		pub latched: bool,
+		pub filter: bool,
+	}
+
+	impl FaultConfig {
+		pub fn polarity(mut self, polarity: FaultPolarity) -> Self {
+			self.polarity = polarity;
+			self
+		}
+
+		pub fn latched(mut self, latched: bool) -> Self {
+			self.latched = latched;
+			self
+		}
+
+		/// Filters the input with the peripheral clock, pins only
+		pub fn filter(mut self, filter: bool) -> Self {
+			self.filter = filter;
+			self
+		}
+	}
+
+	impl Default for FaultConfig {
+		fn default() -> FaultConfig {
+			FaultConfig {
+				polarity: FaultPolarity::ActiveHigh,
+				latched: true,
+				filter: false,
+			}
+		}
+	}
+
+	/// Comparison unit configuration, compares against the counter of channel 0
+	pub struct ComparisonConfig {
+		pub value: u32,
+		/// Match while the counter counts down, center aligned channel 0 only
+		pub decrementing: bool,
+		/// The comparison is performed every `period + 1` periods of channel 0, at most 15
+		pub period: u8,
+	}
+
+	impl ComparisonConfig {
+		pub fn new(value: u32) -> Self {
+			ComparisonConfig {
+				value,
+				decrementing: false,
+				period: 0,
+			}
+		}
+
+		pub fn decrementing(mut self) -> Self {
+			self.decrementing = true;
+			self
+		}
+
+		pub fn period(mut self, period: u8) -> Self {
+			self.period = period;
+			self
+		}
+	}
+
+	#[derive(Debug)]
+	pub struct InvalidConfig;
+}
+
+/// Fault input of a PWM controller
+///
+/// Inputs 0 to 2 are the PWMFI pins of the controller, the timer input is TC0 for PWM0 and TC1
+/// for PWM1.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FaultInput {
+	Pin0,
+	Pin1,
+	Pin2,
+	MainOscillator,
+	Afec0,
+	Afec1,
+	Acc,
+	Timer,
+}
+
+impl FaultInput {
+	pub fn nr(&self) -> usize {
+		match self {
+			FaultInput::Pin0 => 0,
+			FaultInput::Pin1 => 1,
+			FaultInput::Pin2 => 2,
+			FaultInput::MainOscillator => 3,
+			FaultInput::Afec0 => 4,
+			FaultInput::Afec1 => 5,
+			FaultInput::Acc => 6,
+			FaultInput::Timer => 7,
+		}
+	}
+
+	fn mask(&self) -> u32 {
+		1 << self.nr()
+	}
+}
+
+/// Level of a fault input signalling a fault
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FaultPolarity {
+	ActiveHigh,
+	ActiveLow,
+}
+
+/// State of an output while a fault is active
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FaultOutput {
+	Low,
+	High,
+	HighImpedance,
+}
+
+/// PWM event line
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EventLine {
+	Line0,
+	Line1,
+}
+
+impl EventLine {
+	fn nr(&self) -> usize {
+		match self {
+			EventLine::Line0 => 0,
+			EventLine::Line1 => 1,
+		}
+	}
+}
+
+/// Channel prescaler (CPRE) and period (CPRD) for a channel frequency
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Period {
+	pub cpre: u8,
+	pub cprd: u16,
+	pub alignment: Alignment,
+}
+
+impl Period {
+	/// Frequency of the channel counter
+	pub fn clock(&self, mck: Hertz) -> Hertz {
+		Hertz(mck.0 >> self.cpre)
+	}
+
+	/// Frequency of the generated waveform
+	pub fn frequency(&self, mck: Hertz) -> Hertz {
+		let counts = match self.alignment {
+			Alignment::Left => self.cprd as u32,
+			Alignment::Center => 2 * self.cprd as u32,
+		};
+		Hertz(self.clock(mck).0 / counts)
+	}
+}
+
+/// Selects the smallest prescaler of `mck` which generates `freq` with a 16 bit period
+pub fn calc_period(mck: Hertz, freq: Hertz, alignment: Alignment) -> Option<Period> {
+	if freq.0 == 0 {
+		return None;
+	}
+	// center aligned channels count up and down in one period
+	let ticks_per_count = match alignment {
+		Alignment::Left => 1,
+		Alignment::Center => 2,
+	};
+
+	(0..=MAX_CPRE)
+		.map(|cpre| (cpre, (mck.0 >> cpre) / (freq.0 * ticks_per_count)))
+		.find(|(_, cprd)| *cprd >= 1 && *cprd <= 0xffff)
+		.map(|(cpre, cprd)| Period { cpre, cprd: cprd as u16, alignment })
+}
+
+/// Converts a time to ticks of the channel counter running at `clock`, rounded up
+fn ns_to_ticks(clock: Hertz, time: NanoSeconds) -> u32 {
+	((clock.0 as u64 * time.0 as u64 + 999_999_999) / 1_000_000_000) as u32
+}
+
+/// Controller instance
+pub trait Instance {
+	fn ptr() -> *const RegisterBlock;
+}
+
+pub trait PinH<PWM> {}
+pub trait PinL<PWM> {}
+pub trait PinFault<PWM> {}
+
+/// Pins of a PWM controller, a tuple of any PWMH, PWML and PWMFI pins
+pub trait Pins<PWM> {}
+
+/// Pin of a PWM controller
+pub trait PinOut<PWM> {}
+
+macro_rules! pwm_out_pins {
+	($($PIN:ident),+) => {
+		impl<PWM, $($PIN),+> Pins<PWM> for ($($PIN,)+)
+		where
+			$($PIN: PinOut<PWM>,)+
+		{
+		}
+	}
+}
+
+pwm_out_pins!(A);
+pwm_out_pins!(A, B);
+pwm_out_pins!(A, B, C);
+pwm_out_pins!(A, B, C, D);
+pwm_out_pins!(A, B, C, D, E);
+pwm_out_pins!(A, B, C, D, E, F);
+pwm_out_pins!(A, B, C, D, E, F, G);
+pwm_out_pins!(A, B, C, D, E, F, G, H);
+
+macro_rules! pwm_pins {
+	($($PWMX:ty: H: [$($H:ty),*] L: [$($L:ty),*])+) => {
+		$(
+			$(
+				impl PinH<$PWMX> for $H {}
+				impl PinOut<$PWMX> for $H {}
+			)*
+			$(
+				impl PinL<$PWMX> for $L {}
+				impl PinOut<$PWMX> for $L {}
+			)*
+		)+
+	}
+}
+
+// PWMH0 - PWMH3 and PWML0 - PWML3
+pwm_pins! {
+	PWM0:
+		H : [
+			PA0<PeripheralCntr<PeriphA>>,
+			PA11<PeripheralCntr<PeriphB>>,
+			PA23<PeripheralCntr<PeriphB>>,
+			PB0<PeripheralCntr<PeriphA>>,
+			PD11<PeripheralCntr<PeriphB>>,
+			PD20<PeripheralCntr<PeriphA>>,
+			PA2<PeripheralCntr<PeriphA>>,
+			PA12<PeripheralCntr<PeriphB>>,
+			PA24<PeripheralCntr<PeriphB>>,
+			PB1<PeripheralCntr<PeriphA>>,
+			PD21<PeripheralCntr<PeriphA>>,
+			PA13<PeripheralCntr<PeriphB>>,
+			PA25<PeripheralCntr<PeriphB>>,
+			PB4<PeripheralCntr<PeriphB>>,
+			PC19<PeripheralCntr<PeriphB>>,
+			PD22<PeripheralCntr<PeriphA>>,
+			PA7<PeripheralCntr<PeriphB>>,
+			PA14<PeripheralCntr<PeriphB>>,
+			PA17<PeripheralCntr<PeriphC>>,
+			PC13<PeripheralCntr<PeriphB>>,
+			PC21<PeripheralCntr<PeriphB>>,
+			PD23<PeripheralCntr<PeriphA>>
+		]
+		L : [
+			PA1<PeripheralCntr<PeriphA>>,
+			PA19<PeripheralCntr<PeriphB>>,
+			PB5<PeripheralCntr<PeriphB>>,
+			PD10<PeripheralCntr<PeriphB>>,
+			PD24<PeripheralCntr<PeriphA>>,
+			PA20<PeripheralCntr<PeriphB>>,
+			PB12<PeripheralCntr<PeriphA>>,
+			PD25<PeripheralCntr<PeriphA>>,
+			PA16<PeripheralCntr<PeriphC>>,
+			PA30<PeripheralCntr<PeriphA>>,
+			PB13<PeripheralCntr<PeriphA>>,
+			PD26<PeripheralCntr<PeriphA>>,
+			PA15<PeripheralCntr<PeriphC>>,
+			PC20<PeripheralCntr<PeriphB>>,
+			PC22<PeripheralCntr<PeriphB>>,
+			PD27<PeripheralCntr<PeriphA>>
+		]
+	PWM1:
+		H : [
+			PA12<PeripheralCntr<PeriphC>>,
+			PD1<PeripheralCntr<PeriphB>>,
+			PA14<PeripheralCntr<PeriphC>>,
+			PD3<PeripheralCntr<PeriphB>>,
+			PA31<PeripheralCntr<PeriphD>>,
+			PD5<PeripheralCntr<PeriphB>>,
+			PA8<PeripheralCntr<PeriphA>>,
+			PD7<PeripheralCntr<PeriphB>>
+		]
+		L : [
+			PA11<PeripheralCntr<PeriphC>>,
+			PD0<PeripheralCntr<PeriphB>>,
+			PA13<PeripheralCntr<PeriphC>>,
+			PD2<PeripheralCntr<PeriphB>>,
+			PA23<PeripheralCntr<PeriphD>>,
+			PD4<PeripheralCntr<PeriphB>>,
+			PA5<PeripheralCntr<PeriphA>>,
+			PD6<PeripheralCntr<PeriphB>>
+		]
+}
+
+macro_rules! pwm_fault_pins {
+	($($PWMX:ty: [$($FI:ty),*])+) => {
+		$(
+			$(
+				impl PinFault<$PWMX> for $FI {}
+				impl PinOut<$PWMX> for $FI {}
+			)*
+		)+
+	}
+}
+
+// PWMFI0 - PWMFI2
+pwm_fault_pins! {
+	PWM0: [
+		PA9<PeripheralCntr<PeriphC>>,
+		PD8<PeripheralCntr<PeriphB>>,
+		PD9<PeripheralCntr<PeriphB>>
+	]
+	PWM1: [
+		PA21<PeripheralCntr<PeriphC>>,
+		PA26<PeripheralCntr<PeriphD>>,
+		PA28<PeripheralCntr<PeriphD>>
+	]
+}
+
+/// Channel identifier (type state)
+pub trait ChannelId {
+	const CHANNEL: Channel;
+}
+
+/// Channel identifier (type state)
+pub struct C0;
+/// Channel identifier (type state)
+pub struct C1;
+/// Channel identifier (type state)
+pub struct C2;
+/// Channel identifier (type state)
+pub struct C3;
+
+impl ChannelId for C0 {
+	const CHANNEL: Channel = Channel::C0;
+}
+
+impl ChannelId for C1 {
+	const CHANNEL: Channel = Channel::C1;
+}
+
+impl ChannelId for C2 {
+	const CHANNEL: Channel = Channel::C2;
+}
+
+impl ChannelId for C3 {
+	const CHANNEL: Channel = Channel::C3;
+}
+
+/// PWM controller
+pub struct Pwm<PWM, PINS> {
+	pwm: PWM,
+	pins: PINS,
+	mck: Hertz,
+	periods: [Option<Period>; 4],
+	synchronous: u32,
+}
+
+/// A single channel of a split PWM controller
+pub struct PwmChannel<PWM, CH> {
+	mck: Hertz,
+	period: Option<Period>,
+	synchronous: bool,
+	_ch: PhantomData<(PWM, CH)>,
+}
+
+/// Channels of a split PWM controller
+pub struct Channels<PWM> {
+	pub c0: PwmChannel<PWM, C0>,
+	pub c1: PwmChannel<PWM, C1>,
+	pub c2: PwmChannel<PWM, C2>,
+	pub c3: PwmChannel<PWM, C3>,
+}
+
+fn channel_regs<PWM: Instance>(channel: Channel) -> &'static PWM_CH_NUM {
+	// NOTE(unsafe) every channel only accesses its own register block
+	unsafe { &(*PWM::ptr()).pwm_ch_num[channel.nr()] }
+}
+
+fn is_enabled<PWM: Instance>(channel: Channel) -> bool {
+	unsafe { (*PWM::ptr()).pwm_sr.read().bits() & channel.mask() != 0 }
+}
+
+/// Limits `duty` to the range in which the dead-times of `channel` fit into the period
+///
+/// DTH may not exceed the time OCx is high and DTL the time it is low. At the limits one output
+/// stays low, just as the dead-time would suppress it.
+fn clamp_duty<PWM: Instance>(channel: Channel, cprd: u32, duty: u32) -> u32 {
+	let ch = channel_regs::<PWM>(channel);
+	let cmr = ch.pwm_cmr.read().bits();
+	if cmr & CMR_DTE == 0 {
+		return duty;
+	}
+
+	let dt = ch.pwm_dt.read().bits();
+	let (dth, dtl) = (dt & 0xffff, dt >> 16);
+	// OCx is high for CDTY with CPOL set, otherwise for CPRD - CDTY
+	let (min, max) = if cmr & CMR_CPOL != 0 {
+		(dth, cprd.saturating_sub(dtl))
+	} else {
+		(dtl, cprd.saturating_sub(dth))
+	};
+	core::cmp::min(core::cmp::max(duty, min), max)
+}
+
+fn write_duty<PWM: Instance>(channel: Channel, duty: u16) {
+	let ch = channel_regs::<PWM>(channel);
+	let duty = clamp_duty::<PWM>(channel, ch.pwm_cprd.read().bits(), duty as u32) as u16;
+	// running channels take the update at the end of the period
+	if is_enabled::<PWM>(channel) {
+		ch.pwm_cdtyupd.write(|w| unsafe { w.bits(duty as u32) });
+	} else {
+		ch.pwm_cdty.write(|w| unsafe { w.bits(duty as u32) });
+	}
+}
+
+fn read_duty<PWM: Instance>(channel: Channel) -> u16 {
+	channel_regs::<PWM>(channel).pwm_cdty.read().bits() as u16
+}
+
+fn ratio_to_duty(period: Option<Period>, ratio: f32) -> u16 {
+	let max = period.map_or(0, |p| p.cprd) as f32;
+	let ratio = if ratio < 0.0 { 0.0 } else if ratio > 1.0 { 1.0 } else { ratio };
+	(max * ratio + 0.5) as u16
+}
+
+/// Applies the pending duty cycle and period updates of all synchronous channels at once
+fn unlock_update<PWM: Instance>() {
+	unsafe { (*PWM::ptr()).pwm_scuc.write(|w| w.updulock().set_bit()) };
+}
+
+impl<PWM: Instance, PINS> Pwm<PWM, PINS> {
+	/// Configures `channel`, the channel is left disabled with the smallest valid duty cycle
+	///
+	/// With dead-times the duty cycle is kept in the range in which both dead-times fit into
+	/// the high and low time of the waveform.
+	pub fn configure(&mut self, channel: Channel, config: config::ChannelConfig) -> Result<(), config::InvalidConfig> {
+		let period = calc_period(self.mck, config.frequency, config.alignment).ok_or(config::InvalidConfig)?;
+
+		// synchronous channels run on the counter of channel 0
+		if channel == Channel::C0 {
+			let others = self.synchronous & !Channel::C0.mask();
+			let conflict = [Channel::C1, Channel::C2, Channel::C3].iter()
+				.any(|ch| others & ch.mask() != 0 && self.periods[ch.nr()] != Some(period));
+			if conflict {
+				return Err(config::InvalidConfig);
+			}
+		} else if config.synchronous && self.periods[0] != Some(period) {
+			return Err(config::InvalidConfig);
+		}
+
+		let mut cmr = period.cpre as u32;
+		if config.alignment == Alignment::Center {
+			cmr |= CMR_CALG;
+		}
+		if config.polarity == Polarity::ActiveHigh {
+			cmr |= CMR_CPOL;
+		}
+		let dt = match config.dead_time {
+			Some((high, low)) => {
+				let clock = period.clock(self.mck);
+				let high = ns_to_ticks(clock, high);
+				let low = ns_to_ticks(clock, low);
+				// both dead-times have to fit into one period
+				if high + low > period.cprd as u32 {
+					return Err(config::InvalidConfig);
+				}
+				cmr |= CMR_DTE;
+				low << 16 | high
+			}
+			None => 0,
+		};
+
+		self.disable(channel);
+		let ch = channel_regs::<PWM>(channel);
+		ch.pwm_cmr.write(|w| unsafe { w.bits(cmr) });
+		ch.pwm_cprd.write(|w| unsafe { w.bits(period.cprd as u32) });
+		ch.pwm_dt.write(|w| unsafe { w.bits(dt) });
+		let duty = clamp_duty::<PWM>(channel, period.cprd as u32, 0);
+		ch.pwm_cdty.write(|w| unsafe { w.bits(duty) });
+
+		if config.synchronous {
+			self.synchronous |= channel.mask();
+		} else {
+			self.synchronous &= !channel.mask();
+		}
+		// synchronous channels always include channel 0, updates are unlocked manually
+		let sync = if self.synchronous != 0 { self.synchronous | 1 } else { 0 };
+		self.pwm.pwm_scm.write(|w| unsafe { w.bits(sync) });
+
+		self.periods[channel.nr()] = Some(period);
+		Ok(())
+	}
+
+	/// Sets the duty cycle as ratio of the period between 0.0 and 1.0
+	pub fn set_duty_ratio(&mut self, channel: Channel, ratio: f32) {
+		let duty = ratio_to_duty(self.periods[channel.nr()], ratio);
+		self.set_duty(channel, duty);
+	}
+
+	/// Applies the pending updates of all synchronous channels at once
+	pub fn update_synchronous(&mut self) {
+		unlock_update::<PWM>();
+	}
+
+	/// Splits the controller into independent channels
+	pub fn split(self) -> (Channels<PWM>, PWM, PINS) {
+		let synchronous = self.synchronous;
+		let sync = |ch: Channel| synchronous & ch.mask() != 0;
+		let channels = Channels {
+			c0: PwmChannel { mck: self.mck, period: self.periods[0], synchronous: sync(Channel::C0), _ch: PhantomData },
+			c1: PwmChannel { mck: self.mck, period: self.periods[1], synchronous: sync(Channel::C1), _ch: PhantomData },
+			c2: PwmChannel { mck: self.mck, period: self.periods[2], synchronous: sync(Channel::C2), _ch: PhantomData },
+			c3: PwmChannel { mck: self.mck, period: self.periods[3], synchronous: sync(Channel::C3), _ch: PhantomData },
+		};
+		(channels, self.pwm, self.pins)
+	}
+
+	/// Configures how a fault input is detected and cleared
+	pub fn configure_fault(&mut self, input: FaultInput, config: config::FaultConfig) {
+		let mask = input.mask();
+		self.pwm.pwm_fmr.modify(|r, w| {
+			let mut bits = r.bits() & !(mask | mask << 8 | mask << 16);
+			if config.polarity == FaultPolarity::ActiveHigh {
+				bits |= mask;
+			}
+			if config.latched {
+				bits |= mask << 8;
+			}
+			if config.filter {
+				bits |= mask << 16;
+			}
+			unsafe { w.bits(bits) }
+		});
+	}
+
+	/// Forces the outputs of `channel` to `high` and `low` while `input` signals a fault
+	pub fn enable_fault(&mut self, channel: Channel, input: FaultInput, high: FaultOutput, low: FaultOutput) {
+		let nr = channel.nr();
+		let level = |out: FaultOutput| (out == FaultOutput::High) as u32;
+		let hiz = |out: FaultOutput| (out == FaultOutput::HighImpedance) as u32;
+
+		self.pwm.pwm_fpv1.modify(|r, w| unsafe {
+			w.bits(r.bits() & !(1 << nr | 1 << (nr + 16)) | level(high) << nr | level(low) << (nr + 16))
+		});
+		self.pwm.pwm_fpv2.modify(|r, w| unsafe {
+			w.bits(r.bits() & !(1 << nr | 1 << (nr + 16)) | hiz(high) << nr | hiz(low) << (nr + 16))
+		});
+		self.pwm.pwm_fpe.modify(|r, w| unsafe { w.bits(r.bits() | input.mask() << (8 * nr)) });
+	}
+
+	/// Stops `input` from affecting `channel`
+	pub fn disable_fault(&mut self, channel: Channel, input: FaultInput) {
+		let nr = channel.nr();
+		self.pwm.pwm_fpe.modify(|r, w| unsafe { w.bits(r.bits() & !(input.mask() << (8 * nr))) });
+	}
+
+	/// Returns if `input` is signalling a fault or a latched fault was not cleared yet
+	pub fn is_faulted(&self, input: FaultInput) -> bool {
+		self.pwm.pwm_fsr.read().bits() & input.mask() << 8 != 0
+	}
+
+	/// Clears a latched fault, it stays active as long as the input signals the fault
+	pub fn clear_fault(&mut self, input: FaultInput) {
+		self.pwm.pwm_fcr.write(|w| unsafe { w.bits(input.mask()) });
+	}
+
+	/// Configures comparison `unit` to match the counter of channel 0
+	pub fn configure_comparison(&mut self, unit: usize, config: config::ComparisonConfig) -> Result<(), config::InvalidConfig> {
+		if unit >= COMPARISON_UNITS || config.value > CMPV_MAX || config.period > 15 {
+			return Err(config::InvalidConfig);
+		}
+
+		let mut cmpv = config.value;
+		if config.decrementing {
+			cmpv |= CMPV_CVM;
+		}
+		let cmpm = CMPM_CEN | (config.period as u32) << CMPM_CTR_SHIFT | (config.period as u32) << CMPM_CPR_SHIFT;
+
+		let cmp = &self.pwm.pwm_cmp[unit];
+		// running comparisons take the update at the end of the period of channel 0
+		if cmp.pwm_cmpm.read().bits() & CMPM_CEN != 0 {
+			cmp.pwm_cmpvupd.write(|w| unsafe { w.bits(cmpv) });
+			cmp.pwm_cmpmupd.write(|w| unsafe { w.bits(cmpm) });
+		} else {
+			cmp.pwm_cmpv.write(|w| unsafe { w.bits(cmpv) });
+			cmp.pwm_cmpm.write(|w| unsafe { w.bits(cmpm) });
+		}
+
+		Ok(())
+	}
+
+	/// Disables comparison `unit`
+	pub fn disable_comparison(&mut self, unit: usize) {
+		self.pwm.pwm_cmp[unit].pwm_cmpm.write(|w| unsafe { w.bits(0) });
+	}
+
+	/// Returns if comparison `unit` matched since the last call, clears all match flags
+	pub fn comparison_matched(&mut self, unit: usize) -> bool {
+		self.pwm.pwm_isr2.read().bits() & 1 << (unit + 8) != 0
+	}
+
+	/// Generates a pulse on `line` whenever comparison `unit` matches
+	///
+	/// The event lines of PWM0 can trigger AFEC0, the ones of PWM1 AFEC1.
+	pub fn connect_event_line(&mut self, line: EventLine, unit: usize) {
+		self.pwm.pwm_elmr[line.nr()].modify(|r, w| unsafe { w.bits(r.bits() | 1 << unit) });
+	}
+
+	/// Stops comparison `unit` from generating pulses on `line`
+	pub fn disconnect_event_line(&mut self, line: EventLine, unit: usize) {
+		self.pwm.pwm_elmr[line.nr()].modify(|r, w| unsafe { w.bits(r.bits() & !(1 << unit)) });
+	}
+
+	/// Disables all channels and releases the controller and pins
+	pub fn release(self) -> (PWM, PINS) {
+		self.pwm.pwm_dis.write(|w| unsafe { w.bits(0xf) });
+		(self.pwm, self.pins)
+	}
+}
+
+impl<PWM: Instance, PINS> PwmTrait for Pwm<PWM, PINS> {
+	type Channel = Channel;
+	type Time = Hertz;
+	type Duty = u16;
+
+	fn disable(&mut self, channel: Channel) {
+		self.pwm.pwm_dis.write(|w| unsafe { w.bits(channel.mask()) });
+	}
+
+	fn enable(&mut self, channel: Channel) {
+		self.pwm.pwm_ena.write(|w| unsafe { w.bits(channel.mask()) });
+	}
+
+	/// Frequency of channel 0
+	fn get_period(&self) -> Hertz {
+		self.periods[0].map_or(Hertz(0), |p| p.frequency(self.mck))
+	}
+
+	fn get_duty(&self, channel: Channel) -> u16 {
+		read_duty::<PWM>(channel)
+	}
+
+	/// Maximum duty cycle of channel 0, other channels may differ unless they are synchronous
+	fn get_max_duty(&self) -> u16 {
+		self.periods[0].map_or(0, |p| p.cprd)
+	}
+
+	fn set_duty(&mut self, channel: Channel, duty: u16) {
+		write_duty::<PWM>(channel, duty);
+	}
+
+	/// Sets the frequency of all configured channels, the duty cycles are kept as ratio
+	fn set_period<P>(&mut self, period: P)
+	where
+		P: Into<Hertz>,
+	{
+		let freq = period.into();
+		for &channel in [Channel::C0, Channel::C1, Channel::C2, Channel::C3].iter() {
+			let old = match self.periods[channel.nr()] {
+				Some(p) => p,
+				None => continue,
+			};
+			let new = match calc_period(self.mck, freq, old.alignment) {
+				Some(p) => p,
+				None => continue,
+			};
+			let ch = channel_regs::<PWM>(channel);
+			let cmr = ch.pwm_cmr.read().bits();
+			let duty = (ch.pwm_cdty.read().bits() as u64 * new.cprd as u64 / old.cprd as u64) as u32;
+			let duty = clamp_duty::<PWM>(channel, new.cprd as u32, duty);
+
+			// the prescaler can not be updated while the channel is running
+			let enabled = is_enabled::<PWM>(channel);
+			if new.cpre != old.cpre && enabled {
+				self.disable(channel);
+				while is_enabled::<PWM>(channel) {
+					//Wait for the end of the current period
+				}
+			}
+			if is_enabled::<PWM>(channel) {
+				ch.pwm_cprdupd.write(|w| unsafe { w.bits(new.cprd as u32) });
+				ch.pwm_cdtyupd.write(|w| unsafe { w.bits(duty) });
+			} else {
+				ch.pwm_cmr.write(|w| unsafe { w.bits(cmr & !0xf | new.cpre as u32) });
+				ch.pwm_cprd.write(|w| unsafe { w.bits(new.cprd as u32) });
+				ch.pwm_cdty.write(|w| unsafe { w.bits(duty) });
+				if enabled {
+					self.enable(channel);
+				}
+			}
+			self.periods[channel.nr()] = Some(new);
+		}
+		if self.synchronous != 0 {
+			unlock_update::<PWM>();
+		}
+	}
+}
+
+impl<PWM: Instance, CH: ChannelId> PwmChannel<PWM, CH> {
+	/// Sets the duty cycle as ratio of the period between 0.0 and 1.0
+	pub fn set_duty_ratio(&mut self, ratio: f32) {
+		let duty = ratio_to_duty(self.period, ratio);
+		self.set_duty(duty);
+	}
+
+	/// Channel counter frequency
+	pub fn clock(&self) -> Hertz {
+		self.period.map_or(Hertz(0), |p| p.clock(self.mck))
+	}
+
+	/// Synchronous channels need `update_synchronous` to apply a new duty cycle
+	pub fn is_synchronous(&self) -> bool {
+		self.synchronous
+	}
+
+	/// Applies the pending updates of all synchronous channels at once
+	pub fn update_synchronous(&mut self) {
+		unlock_update::<PWM>();
+	}
+}
+
+impl<PWM: Instance, CH: ChannelId> PwmPin for PwmChannel<PWM, CH> {
+	type Duty = u16;
+
+	fn disable(&mut self) {
+		unsafe { (*PWM::ptr()).pwm_dis.write(|w| w.bits(CH::CHANNEL.mask())) };
+	}
+
+	fn enable(&mut self) {
+		unsafe { (*PWM::ptr()).pwm_ena.write(|w| w.bits(CH::CHANNEL.mask())) };
+	}
+
+	fn get_duty(&self) -> u16 {
+		read_duty::<PWM>(CH::CHANNEL)
+	}
+
+	fn get_max_duty(&self) -> u16 {
+		self.period.map_or(0, |p| p.cprd)
+	}
+
+	fn set_duty(&mut self, duty: u16) {
+		write_duty::<PWM>(CH::CHANNEL, duty);
+	}
+}
+
+macro_rules! pwm_hal {
+	($( $PWMX:ident: (
+			$pwmX:ident,
+			$en_reg:ident,
+			$perid:ident
+		),
+	)+) => {
+		$(
+			impl Instance for $PWMX {
+				fn ptr() -> *const RegisterBlock {
+					$PWMX::ptr()
+				}
+			}
+
+			impl<PINS> Pwm<$PWMX, PINS> {
+				/// Enables the PWM controller with all channels disabled
+				pub fn $pwmX(pwm: $PWMX, pins: PINS, clocks: &Clocks, pmc: &mut PMC) -> Self
+				where
+					PINS: Pins<$PWMX>,
+				{
+					//enable peripheral clock in pmc
+					pmc.$en_reg.write(|w| w.$perid().set_bit() );
+
+					pwm.pwm_dis.write(|w| unsafe { w.bits(0xf) });
+					pwm.pwm_scm.write(|w| unsafe { w.bits(0) });
+
+					Pwm {
+						pwm,
+						pins,
+						mck: clocks.mck(),
+						periods: [None; 4],
+						synchronous: 0,
+					}
+				}
+			}
+		)+
+	}
+}
+
+pwm_hal! {
+	PWM0 : (pwm0, pmc_pcer0, pid31),
+	PWM1 : (pwm1, pmc_pcer1, pid60),
+}
@@ -0,0 +1,282 @@
+//! Quadrature Encoder Interface (TC quadrature decoder)
+//!
+//! The quadrature decoder of a TC uses channel 0 to count the position and channel 1 to count
+//! the rotations, i.e. the index pulses. PHA and PHB are connected to TIOA0 and TIOB0, the
+//! optional index to TIOB1.
+//!
+//! Reading the quadrature status clears the pending events, so every read, including the one
+//! of `direction`, latches them until they are consumed by `is_pending`.
+
+use core::cell::Cell;
+
+use embedded_hal::{Direction, Qei as QeiTrait};
+
+use crate::target_device::tc0::RegisterBlock;
+use crate::timer::{Ch0, Ch1, Instance, PinTioa, PinTiob, Timer};
+
+// Block mode register
+const BMR_QDEN: u32 = 1 << 8;
+const BMR_POSEN: u32 = 1 << 9;
+const BMR_EDGPHA: u32 = 1 << 12;
+const BMR_INVA: u32 = 1 << 13;
+const BMR_INVB: u32 = 1 << 14;
+const BMR_INVIDX: u32 = 1 << 15;
+const BMR_SWAP: u32 = 1 << 16;
+const BMR_MAXFILT_SHIFT: u32 = 20;
+
+// Channel mode register, capture mode clocked by XC0
+const CMR_TCCLKS_XC0: u32 = 5;
+const CMR_ETRGEDG_RISING: u32 = 1 << 8;
+const CMR_ABETRG: u32 = 1 << 10;
+
+// Quadrature decoder interrupt and status flags
+const QISR_IDX: u32 = 1 << 0;
+const QISR_DIRCHG: u32 = 1 << 1;
+const QISR_QERR: u32 = 1 << 2;
+const QISR_DIR: u32 = 1 << 8;
+const QISR_EVENTS: u32 = QISR_IDX | QISR_DIRCHG | QISR_QERR;
+
+/// Quadrature decoder interrupt event
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+	/// An index pulse was detected
+	Index,
+	/// The rotation direction changed
+	DirectionChange,
+	/// A contradictory sequence of PHA and PHB was detected
+	QuadratureError,
+}
+
+impl Event {
+	fn mask(&self) -> u32 {
+		match self {
+			Event::Index => QISR_IDX,
+			Event::DirectionChange => QISR_DIRCHG,
+			Event::QuadratureError => QISR_QERR,
+		}
+	}
+}
+
+pub mod config {
+	/// Counted edges of the encoder signals
+	#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+	pub enum Edges {
+		/// Edges of PHA and PHB, four counts per encoder period
+		Both,
+		/// Edges of PHA only, two counts per encoder period
+		PhaOnly,
+	}
+
+	pub struct QeiConfig {
+		pub edges: Edges,
+		pub filter: u8,
+		pub swap: bool,
+		pub invert_a: bool,
+		pub invert_b: bool,
+		pub invert_index: bool,
+	}
+
+	impl QeiConfig {
+		pub fn edges(mut self, edges: Edges) -> Self {
+			self.edges = edges;
+			self
+		}
+
+		/// Rejects pulses shorter than `(filter + 1)` peripheral clock cycles, at most 63
+		pub fn filter(mut self, filter: u8) -> Self {
+			self.filter = filter;
+			self
+		}
+
+		/// Swaps PHA and PHB, which inverts the counting direction
+		pub fn swap(mut self, swap: bool) -> Self {
+			self.swap = swap;
+			self
+		}
+
+		pub fn invert_a(mut self, invert: bool) -> Self {
+			self.invert_a = invert;
+			self
+		}
+
+		pub fn invert_b(mut self, invert: bool) -> Self {
+			self.invert_b = invert;
+			self
+		}
+
+		pub fn invert_index(mut self, invert: bool) -> Self {
+			self.invert_index = invert;
+			self
+		}
+	}
+
+	impl Default for QeiConfig {
+		fn default() -> QeiConfig {
+			QeiConfig {
+				edges: Edges::Both,
+				filter: 0,
+				swap: false,
+				invert_a: false,
+				invert_b: false,
+				invert_index: false,
+			}
+		}
+	}
+
+	#[derive(Debug)]
+	pub struct InvalidConfig;
+}
+
+/// Encoder pins, (PHA, PHB) or (PHA, PHB, IDX)
+pub trait Pins<TC> {
+	/// The index resets the position and counts the rotations
+	const INDEX: bool;
+}
+
+impl<TC, PHA, PHB> Pins<TC> for (PHA, PHB)
+where
+	PHA: PinTioa<TC, Ch0>,
+	PHB: PinTiob<TC, Ch0>,
+{
+	const INDEX: bool = false;
+}
+
+impl<TC, PHA, PHB, IDX> Pins<TC> for (PHA, PHB, IDX)
+where
+	PHA: PinTioa<TC, Ch0>,
+	PHB: PinTiob<TC, Ch0>,
+	IDX: PinTiob<TC, Ch1>,
+{
+	const INDEX: bool = true;
+}
+
+/// Quadrature decoder using channels 0 and 1 of a TC
+pub struct Qei<TC, PINS> {
+	position: Timer<TC, Ch0>,
+	rotation: Timer<TC, Ch1>,
+	pins: PINS,
+	/// Events read from QISR but not consumed yet
+	pending: Cell<u32>,
+}
+
+impl<TC: Instance, PINS: Pins<TC>> Qei<TC, PINS> {
+	fn regs() -> &'static RegisterBlock {
+		// NOTE(unsafe) the block registers are only used by the owner of channels 0 and 1
+		unsafe { &*TC::ptr() }
+	}
+
+	/// Configures the quadrature decoder in position mode
+	pub fn new(position: Timer<TC, Ch0>, rotation: Timer<TC, Ch1>, pins: PINS, config: config::QeiConfig) -> Result<Self, config::InvalidConfig> {
+		if config.filter > 63 {
+			return Err(config::InvalidConfig);
+		}
+
+		let tc = Self::regs();
+		let mut bmr = BMR_QDEN | BMR_POSEN | (config.filter as u32) << BMR_MAXFILT_SHIFT;
+		if config.edges == config::Edges::PhaOnly {
+			bmr |= BMR_EDGPHA;
+		}
+		if config.swap {
+			bmr |= BMR_SWAP;
+		}
+		if config.invert_a {
+			bmr |= BMR_INVA;
+		}
+		if config.invert_b {
+			bmr |= BMR_INVB;
+		}
+		if config.invert_index {
+			bmr |= BMR_INVIDX;
+		}
+		tc.tc_bmr.write(|w| unsafe { w.bits(bmr) });
+
+		// the position is cleared by the index, which is internally routed to TIOA0
+		let position_cmr = if PINS::INDEX {
+			CMR_TCCLKS_XC0 | CMR_ETRGEDG_RISING | CMR_ABETRG
+		} else {
+			CMR_TCCLKS_XC0
+		};
+		tc.tc_channel[0].tc_cmr.write(|w| unsafe { w.bits(position_cmr) });
+		tc.tc_channel[1].tc_cmr.write(|w| unsafe { w.bits(CMR_TCCLKS_XC0) });
+
+		// clear pending status flags
+		let _ = tc.tc_qisr.read().bits();
+
+		for ch in tc.tc_channel[..2].iter() {
+			ch.tc_ccr.write(|w| {
+				w.clken().set_bit();
+				w.swtrg().set_bit()
+			});
+		}
+
+		Ok(Qei { position, rotation, pins, pending: Cell::new(0) })
+	}
+
+	/// Position within the current rotation, or since start without index
+	pub fn position(&self) -> u16 {
+		Self::regs().tc_channel[0].tc_cv.read().bits() as u16
+	}
+
+	/// Number of index pulses seen
+	pub fn rotations(&self) -> u16 {
+		Self::regs().tc_channel[1].tc_cv.read().bits() as u16
+	}
+
+	/// Resets position and rotation counters
+	pub fn reset(&mut self) {
+		for ch in Self::regs().tc_channel[..2].iter() {
+			ch.tc_ccr.write(|w| w.swtrg().set_bit());
+		}
+	}
+
+	/// Starts an interrupt event
+	pub fn listen(&mut self, event: Event) {
+		Self::regs().tc_qier.write(|w| unsafe { w.bits(event.mask()) });
+	}
+
+	/// Stops an interrupt event
+	pub fn unlisten(&mut self, event: Event) {
+		Self::regs().tc_qidr.write(|w| unsafe { w.bits(event.mask()) });
+	}
+
+	/// Returns if `event` occurred since it was last checked and clears it
+	pub fn is_pending(&mut self, event: Event) -> bool {
+		let pending = self.read_status() & event.mask() != 0;
+		self.pending.set(self.pending.get() & !event.mask());
+		pending
+	}
+
+	/// Reads QISR and latches the events its read clears
+	fn read_status(&self) -> u32 {
+		let qisr = Self::regs().tc_qisr.read().bits();
+		self.pending.set(self.pending.get() | qisr & QISR_EVENTS);
+		qisr | self.pending.get()
+	}
+
+	/// Disables the decoder and releases the channels and pins
+	pub fn release(mut self) -> (Timer<TC, Ch0>, Timer<TC, Ch1>, PINS) {
+		let tc = Self::regs();
+		tc.tc_qidr.write(|w| unsafe { w.bits(QISR_EVENTS) });
+		tc.tc_bmr.write(|w| unsafe { w.bits(0) });
+		self.position.stop();
+		self.rotation.stop();
+		(self.position, self.rotation, self.pins)
+	}
+}
+
+impl<TC: Instance, PINS: Pins<TC>> QeiTrait for Qei<TC, PINS> {
+	type Count = u16;
+
+	fn count(&self) -> u16 {
+		self.position()
+	}
+
+	/// The events cleared by the status read stay pending for `is_pending`
+	fn direction(&self) -> Direction {
+		if self.read_status() & QISR_DIR != 0 {
+			Direction::Downcounting
+		} else {
+			Direction::Upcounting
+		}
+	}
+}
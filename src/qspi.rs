@@ -0,0 +1,460 @@
+//! Quad Serial Peripheral Interface
+//!
+//! The peripheral is operated in serial memory mode. Every access consists of an instruction
+//! frame (instruction, address, option, dummy cycles and data) as expected by serial flash
+//! memories. Flash contents can either be accessed indirectly through `Qspi::read`/`Qspi::write`
+//! or memory mapped at `0x8000_0000`.
+//!
+//! The memory region is cacheable. Indirect reads invalidate the D-cache over the accessed bytes
+//! and entering memory mapped mode invalidates the mapped range, so stale data is
+//! never returned as long as the region stays write-through (the default memory map) or is
+//! configured as non-cacheable through the MPU. Do not map it write-back, dirty lines would
+//! otherwise be written to the flash on eviction.
+
+use core::ptr;
+use core::slice;
+
+use cortex_m::asm;
+
+pub use embedded_hal::spi::{Mode, Phase, Polarity, MODE_0, MODE_3};
+
+use crate::target_device::{QSPI, PMC};
+
+use crate::cache;
+
+use crate::gpio::{PeripheralCntr, PeriphA};
+use crate::gpio::pioa::{PA11, PA12, PA13, PA14, PA17};
+use crate::gpio::piod::{PD31};
+use crate::clock_gen::Clocks;
+use crate::time::{Hertz, NanoSeconds, PicoSeconds};
+
+/// Start of the QSPI memory region
+const QSPI_MEMORY_START: u32 = 0x8000_0000;
+
+pub mod config {
+	use crate::time::{Hertz, NanoSeconds};
+	use crate::time::U32Ext;
+	use super::{Mode, MODE_0};
+
+	pub struct QspiConfig {
+		pub mode: Mode,
+		pub baudrate: Hertz,
+		pub delay_before_qsck: NanoSeconds,
+	}
+
+	impl QspiConfig {
+		pub fn mode(mut self, mode: Mode) -> Self {
+			self.mode = mode;
+			self
+		}
+
+		pub fn baudrate(mut self, baudrate: Hertz) -> Self {
+			self.baudrate = baudrate;
+			self
+		}
+
+		/// Delay between chip select assertion and the first clock edge
+		pub fn delay_before_qsck(mut self, delay: NanoSeconds) -> Self {
+			self.delay_before_qsck = delay;
+			self
+		}
+	}
+
+	impl Default for QspiConfig {
+		fn default() -> QspiConfig {
+			QspiConfig {
+				mode: MODE_0,
+				baudrate: 1_000_000_u32.hz(),
+				delay_before_qsck: 0_u32.ns(),
+			}
+		}
+	}
+
+	#[derive(Debug)]
+	pub struct InvalidConfig;
+}
+
+/// Number of data lines used for the parts of an instruction frame
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Width {
+	/// Instruction, address and data on one line
+	Single,
+	/// Data on two lines
+	DualOutput,
+	/// Data on four lines
+	QuadOutput,
+	/// Address and data on two lines
+	DualIo,
+	/// Address and data on four lines
+	QuadIo,
+	/// Instruction, address and data on two lines
+	DualCmd,
+	/// Instruction, address and data on four lines
+	QuadCmd,
+}
+
+impl Width {
+	fn bits(&self) -> u8 {
+		match self {
+			Width::Single => 0,
+			Width::DualOutput => 1,
+			Width::QuadOutput => 2,
+			Width::DualIo => 3,
+			Width::QuadIo => 4,
+			Width::DualCmd => 5,
+			Width::QuadCmd => 6,
+		}
+	}
+}
+
+/// Length of the address part of an instruction frame
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AddressLength {
+	Bits24,
+	Bits32,
+}
+
+/// Length of the option part of an instruction frame
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OptionLength {
+	Bits1,
+	Bits2,
+	Bits4,
+	Bits8,
+}
+
+impl OptionLength {
+	fn bits(&self) -> u8 {
+		match self {
+			OptionLength::Bits1 => 0,
+			OptionLength::Bits2 => 1,
+			OptionLength::Bits4 => 2,
+			OptionLength::Bits8 => 3,
+		}
+	}
+}
+
+/// Describes a single instruction frame
+#[derive(Debug, Copy, Clone)]
+pub struct Command {
+	instruction: Option<u8>,
+	width: Width,
+	address: Option<(u32, AddressLength)>,
+	option: Option<(u8, OptionLength)>,
+	dummy_cycles: u8,
+	continuous_read: bool,
+}
+
+impl Command {
+	/// Instruction frame only sending `instruction` on a single line
+	pub fn new(instruction: u8) -> Self {
+		Command {
+			instruction: Some(instruction),
+			width: Width::Single,
+			address: None,
+			option: None,
+			dummy_cycles: 0,
+			continuous_read: false,
+		}
+	}
+
+	/// Instruction frame without instruction, used in continuous read mode
+	pub fn without_instruction() -> Self {
+		Command {
+			instruction: None,
+			..Command::new(0)
+		}
+	}
+
+	pub fn width(mut self, width: Width) -> Self {
+		self.width = width;
+		self
+	}
+
+	/// Sends a 24 bit address
+	pub fn address(mut self, address: u32) -> Self {
+		self.address = Some((address & 0x00ff_ffff, AddressLength::Bits24));
+		self
+	}
+
+	/// Sends a 32 bit address
+	pub fn address_32bit(mut self, address: u32) -> Self {
+		self.address = Some((address, AddressLength::Bits32));
+		self
+	}
+
+	/// Sends an option code (mode bits) after the address
+	pub fn option(mut self, option: u8, length: OptionLength) -> Self {
+		self.option = Some((option, length));
+		self
+	}
+
+	/// Idle clock cycles between the address/option and the data
+	pub fn dummy_cycles(mut self, cycles: u8) -> Self {
+		assert!(cycles <= 31);
+		self.dummy_cycles = cycles;
+		self
+	}
+
+	/// Enables the continuous read mode of the flash, only used for memory mapped reads
+	pub fn continuous_read(mut self, enable: bool) -> Self {
+		self.continuous_read = enable;
+		self
+	}
+}
+
+/// Transfer type of an instruction frame
+#[derive(Copy, Clone)]
+enum TransferType {
+	Read,
+	ReadMemory,
+	Write,
+}
+
+pub trait Pins<QSPI> {}
+pub trait PinCs<QSPI> {}
+pub trait PinSck<QSPI> {}
+pub trait PinIo0<QSPI> {}
+pub trait PinIo1<QSPI> {}
+pub trait PinIo2<QSPI> {}
+pub trait PinIo3<QSPI> {}
+
+/// Pins for single and dual mode
+impl<CS, SCK, IO0, IO1> Pins<QSPI> for (CS, SCK, IO0, IO1)
+where
+	CS: PinCs<QSPI>,
+	SCK: PinSck<QSPI>,
+	IO0: PinIo0<QSPI>,
+	IO1: PinIo1<QSPI>,
+{
+}
+
+/// Pins for quad mode
+impl<CS, SCK, IO0, IO1, IO2, IO3> Pins<QSPI> for (CS, SCK, IO0, IO1, IO2, IO3)
+where
+	CS: PinCs<QSPI>,
+	SCK: PinSck<QSPI>,
+	IO0: PinIo0<QSPI>,
+	IO1: PinIo1<QSPI>,
+	IO2: PinIo2<QSPI>,
+	IO3: PinIo3<QSPI>,
+{
+}
+
+impl PinCs<QSPI> for PA11<PeripheralCntr<PeriphA>> {}
+impl PinSck<QSPI> for PA14<PeripheralCntr<PeriphA>> {}
+impl PinIo0<QSPI> for PA13<PeripheralCntr<PeriphA>> {}
+impl PinIo1<QSPI> for PA12<PeripheralCntr<PeriphA>> {}
+impl PinIo2<QSPI> for PA17<PeripheralCntr<PeriphA>> {}
+impl PinIo3<QSPI> for PD31<PeripheralCntr<PeriphA>> {}
+
+/// QSPI abstraction in serial memory mode
+pub struct Qspi<PINS> {
+	qspi: QSPI,
+	pins: PINS,
+}
+
+/// QSPI with the flash mapped into the address space
+pub struct QspiMemoryMapped<PINS> {
+	qspi: Qspi<PINS>,
+	size: usize,
+}
+
+impl<PINS> Qspi<PINS> {
+	/// Configures the QSPI peripheral in serial memory mode
+	pub fn qspi(
+		qspi: QSPI,
+		pins: PINS,
+		config: config::QspiConfig,
+		clocks: &Clocks,
+		pmc: &mut PMC,
+	) -> Result<Self, config::InvalidConfig>
+	where
+		PINS: Pins<QSPI>,
+	{
+		// the serial clock is mck / (SCBR + 1)
+		let clk = clocks.mck();
+		if config.baudrate.0 == 0 {
+			return Err(config::InvalidConfig);
+		}
+		let div = (clk.0 + config.baudrate.0 - 1) / config.baudrate.0;
+		if div == 0 || div > 256 {
+			return Err(config::InvalidConfig);
+		}
+		let dlybs = calc_delay_val(clk, config.delay_before_qsck)?;
+
+		//enable peripheral clock in pmc
+		pmc.pmc_pcer1.write(|w| w.pid43().set_bit() );
+
+		//reset peripheral
+		qspi.qspi_cr.write(|w| w.qspidis().set_bit());
+		qspi.qspi_cr.write(|w| w.swrst().set_bit());
+
+		//serial memory mode, chip select is released with LASTXFER
+		qspi.qspi_mr.write(|w| unsafe {
+			w.smm().set_bit();
+			w.csmode().bits(1);
+			w.nbbits().bits(0)
+		});
+
+		qspi.qspi_scr.write(|w| unsafe {
+			match config.mode.polarity {
+				Polarity::IdleLow => w.cpol().clear_bit(),
+				Polarity::IdleHigh => w.cpol().set_bit(),
+			};
+			match config.mode.phase {
+				Phase::CaptureOnFirstTransition => w.cpha().clear_bit(),
+				Phase::CaptureOnSecondTransition => w.cpha().set_bit(),
+			};
+			w.scbr().bits((div - 1) as u8);
+			w.dlybs().bits(dlybs)
+		});
+
+		qspi.qspi_cr.write(|w| w.qspien().set_bit());
+
+		Ok(Qspi { qspi, pins })
+	}
+
+	/// Sends an instruction frame without data, e.g. write enable or erase commands
+	pub fn command(&mut self, command: &Command) {
+		self.start_frame(command, TransferType::Write, false);
+		self.end_frame();
+	}
+
+	/// Sends an instruction frame and reads the data that follows into `buffer`
+	pub fn read(&mut self, command: &Command, buffer: &mut [u8]) {
+		self.start_frame(command, TransferType::Read, !buffer.is_empty());
+
+		// lines allocated by earlier accesses would return old data
+		cache::invalidate_dcache(QSPI_MEMORY_START as usize, buffer.len());
+
+		let mem = QSPI_MEMORY_START as *const u8;
+		for (i, b) in buffer.iter_mut().enumerate() {
+			// NOTE(unsafe) the QSPI memory region is always mapped
+			*b = unsafe { ptr::read_volatile(mem.add(i)) };
+		}
+
+		self.end_frame();
+	}
+
+	/// Sends an instruction frame followed by the data in `buffer`
+	pub fn write(&mut self, command: &Command, buffer: &[u8]) {
+		self.start_frame(command, TransferType::Write, !buffer.is_empty());
+
+		let mem = QSPI_MEMORY_START as *mut u8;
+		for (i, b) in buffer.iter().enumerate() {
+			// NOTE(unsafe) the QSPI memory region is always mapped
+			unsafe { ptr::write_volatile(mem.add(i), *b) };
+		}
+
+		self.end_frame();
+	}
+
+	/// Maps `size` bytes of the flash to `0x8000_0000`, every read access issues `command`
+	///
+	/// The address part of `command` is taken from the accessed location.
+	pub fn into_memory_mapped(self, command: &Command, size: usize) -> QspiMemoryMapped<PINS> {
+		self.start_frame(command, TransferType::ReadMemory, true);
+
+		// the flash may have been changed since the region was last read
+		cache::invalidate_dcache(QSPI_MEMORY_START as usize, size);
+
+		QspiMemoryMapped { qspi: self, size }
+	}
+
+	/// Releases the QSPI peripheral and associated pins
+	pub fn release(self) -> (QSPI, PINS) {
+		self.qspi.qspi_cr.write(|w| w.qspidis().set_bit());
+		(self.qspi, self.pins)
+	}
+
+	fn start_frame(&self, command: &Command, tfrtyp: TransferType, data: bool) {
+		if let Some((address, _)) = command.address {
+			self.qspi.qspi_iar.write(|w| unsafe { w.addr().bits(address) });
+		}
+
+		self.qspi.qspi_icr.write(|w| unsafe {
+			w.inst().bits(command.instruction.unwrap_or(0));
+			w.opt().bits(command.option.map(|(o, _)| o).unwrap_or(0))
+		});
+
+		self.qspi.qspi_ifr.write(|w| unsafe {
+			w.width().bits(command.width.bits());
+			w.insten().bit(command.instruction.is_some());
+			w.addren().bit(command.address.is_some());
+			w.opten().bit(command.option.is_some());
+			w.dataen().bit(data);
+			if let Some((_, length)) = command.option {
+				w.optl().bits(length.bits());
+			}
+			match command.address {
+				Some((_, AddressLength::Bits32)) => w.addrl().set_bit(),
+				_ => w.addrl().clear_bit(),
+			};
+			w.tfrtyp().bits(match tfrtyp {
+				TransferType::Read => 0,
+				TransferType::ReadMemory => 1,
+				TransferType::Write => 2,
+			});
+			w.crm().bit(command.continuous_read);
+			w.nbdum().bits(command.dummy_cycles)
+		});
+
+		// synchronise the frame configuration with the system bus before data is accessed
+		let _ = self.qspi.qspi_ifr.read().bits();
+	}
+
+	fn end_frame(&self) {
+		// make sure all data accesses have been performed before the frame is closed
+		asm::dsb();
+		asm::isb();
+
+		self.qspi.qspi_cr.write(|w| w.lastxfer().set_bit());
+		while self.qspi.qspi_sr.read().instre().bit_is_clear() {
+			//Wait for the end of the instruction frame
+		}
+	}
+}
+
+impl<PINS> QspiMemoryMapped<PINS> {
+	/// Start of the mapped flash
+	pub fn start_address(&self) -> *const u8 {
+		QSPI_MEMORY_START as *const u8
+	}
+
+	/// Size of the mapped flash
+	pub fn size(&self) -> usize {
+		self.size
+	}
+
+	/// Contents of the mapped flash
+	pub fn as_slice(&self) -> &[u8] {
+		// NOTE(unsafe) the region is read only and stays mapped while self is alive
+		unsafe { slice::from_raw_parts(self.start_address(), self.size) }
+	}
+
+	/// Ends memory mapped mode, e.g. to program the flash
+	pub fn release(self) -> Qspi<PINS> {
+		// the instruction frame only starts with the first access, without it the end of the
+		// frame is never signalled
+		cache::invalidate_dcache(QSPI_MEMORY_START as usize, 1);
+		// NOTE(unsafe) the region is mapped while self is alive
+		let _ = unsafe { ptr::read_volatile(self.start_address()) };
+
+		self.qspi.end_frame();
+		self.qspi
+	}
+}
+
+// DLYBS counts peripheral clock cycles
+fn calc_delay_val(clk: Hertz, delay: NanoSeconds) -> Result<u8, config::InvalidConfig> {
+	if delay.0 == 0 {
+		return Ok(0);
+	}
+	let cycle_duration: PicoSeconds = clk.into();
+	let cycles = cycle_duration.cycles(delay.into());
+	if cycles > 255 {
+		return Err(config::InvalidConfig);
+	}
+	Ok(cycles as u8)
+}
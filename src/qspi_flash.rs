@@ -0,0 +1,293 @@
+//! Serial NOR flash on the QSPI peripheral
+//!
+//! Page size, erase granularities, address length and the quad enable method are discovered
+//! from the SFDP tables of the flash, so any JESD216 compliant flash can be used.
+
+use embedded_storage::nor_flash::{check_erase, check_read, check_write};
+use embedded_storage::nor_flash::{ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash};
+
+use crate::qspi::{Command, Qspi, QspiMemoryMapped, Width};
+use crate::sfdp;
+use crate::sfdp::{AddressBytes, FlashParameters, QuadEnable};
+
+const WRITE_ENABLE: u8 = 0x06;
+const READ_STATUS_1: u8 = 0x05;
+const READ_STATUS_2: u8 = 0x35;
+const READ_STATUS_2_ALT: u8 = 0x3f;
+const WRITE_STATUS: u8 = 0x01;
+const WRITE_STATUS_2: u8 = 0x31;
+const WRITE_STATUS_2_ALT: u8 = 0x3e;
+const PAGE_PROGRAM: u8 = 0x02;
+const FAST_READ: u8 = 0x0b;
+const ENTER_4_BYTE_MODE: u8 = 0xb7;
+
+/// Write in progress bit of status register 1
+const STATUS_BUSY: u8 = 1 << 0;
+
+/// Flash error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+	/// SFDP tables could not be parsed
+	Sfdp(sfdp::Error),
+	/// The flash does not support 4KiB erases
+	UnsupportedEraseSize,
+	/// Arguments are not aligned to the read, write or erase size
+	NotAligned,
+	/// Arguments are outside of the flash
+	OutOfBounds,
+}
+
+impl From<sfdp::Error> for Error {
+	fn from(e: sfdp::Error) -> Self {
+		Error::Sfdp(e)
+	}
+}
+
+impl From<NorFlashErrorKind> for Error {
+	fn from(e: NorFlashErrorKind) -> Self {
+		match e {
+			NorFlashErrorKind::NotAligned => Error::NotAligned,
+			_ => Error::OutOfBounds,
+		}
+	}
+}
+
+impl NorFlashError for Error {
+	fn kind(&self) -> NorFlashErrorKind {
+		match self {
+			Error::NotAligned => NorFlashErrorKind::NotAligned,
+			Error::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+			_ => NorFlashErrorKind::Other,
+		}
+	}
+}
+
+/// Serial NOR flash configured from its SFDP tables
+pub struct QspiFlash<PINS> {
+	qspi: Qspi<PINS>,
+	params: FlashParameters,
+	four_byte_address: bool,
+	read: Command,
+}
+
+impl<PINS> QspiFlash<PINS> {
+	/// Reads the SFDP tables of the flash attached to `qspi` and sets it up accordingly
+	///
+	/// Quad output reads are used if the flash supports them, quad mode is enabled in the flash
+	/// as described by its parameter table.
+	pub fn new(mut qspi: Qspi<PINS>) -> Result<Self, Error> {
+		let params = read_parameters(&mut qspi)?;
+
+		// the embedded-storage traits require a fixed erase size
+		if !params.erase_types.iter().any(|e| e.map(|e| e.size) == Some(4096)) {
+			return Err(Error::UnsupportedEraseSize);
+		}
+
+		let mut flash = QspiFlash {
+			qspi,
+			params,
+			four_byte_address: false,
+			read: Command::new(FAST_READ).dummy_cycles(8),
+		};
+
+		match params.address_bytes {
+			AddressBytes::Three => {}
+			AddressBytes::ThreeOrFour => {
+				if params.size > 1 << 24 {
+					flash.write_enable();
+					flash.qspi.command(&Command::new(ENTER_4_BYTE_MODE));
+					flash.four_byte_address = true;
+				}
+			}
+			AddressBytes::Four => flash.four_byte_address = true,
+		}
+
+		// mode clocks are sent as dummy cycles, which leaves the flash outside of continuous read mode
+		if let Some(r) = params.fast_read_1_1_4 {
+			flash.enable_quad()?;
+			flash.read = Command::new(r.instruction)
+				.width(Width::QuadOutput)
+				.dummy_cycles(r.dummy_cycles + r.mode_cycles);
+		} else if let Some(r) = params.fast_read_1_1_2 {
+			flash.read = Command::new(r.instruction)
+				.width(Width::DualOutput)
+				.dummy_cycles(r.dummy_cycles + r.mode_cycles);
+		}
+
+		Ok(flash)
+	}
+
+	/// Parameters discovered from the SFDP tables
+	pub fn parameters(&self) -> &FlashParameters {
+		&self.params
+	}
+
+	/// Maps the whole flash to `0x8000_0000` using the discovered read instruction
+	pub fn into_memory_mapped(self) -> QspiMemoryMapped<PINS> {
+		let command = self.with_address(self.read, 0);
+		self.qspi.into_memory_mapped(&command, self.params.size as usize)
+	}
+
+	/// Releases the QSPI peripheral
+	pub fn release(self) -> Qspi<PINS> {
+		self.qspi
+	}
+
+	fn with_address(&self, command: Command, address: u32) -> Command {
+		if self.four_byte_address {
+			command.address_32bit(address)
+		} else {
+			command.address(address)
+		}
+	}
+
+	fn write_enable(&mut self) {
+		self.qspi.command(&Command::new(WRITE_ENABLE));
+	}
+
+	fn read_register(&mut self, instruction: u8) -> u8 {
+		let mut value = [0u8; 1];
+		self.qspi.read(&Command::new(instruction), &mut value);
+		value[0]
+	}
+
+	fn wait_ready(&mut self) {
+		while self.read_register(READ_STATUS_1) & STATUS_BUSY != 0 {
+			//Wait for the program or erase operation to finish
+		}
+	}
+
+	fn enable_quad(&mut self) -> Result<(), Error> {
+		let (read, write, bit) = match self.params.quad_enable {
+			QuadEnable::None => return Ok(()),
+			QuadEnable::Sr1Bit6 => {
+				let sr1 = self.read_register(READ_STATUS_1);
+				if sr1 & (1 << 6) != 0 {
+					return Ok(());
+				}
+				self.write_enable();
+				self.qspi.write(&Command::new(WRITE_STATUS), &[sr1 | (1 << 6)]);
+				self.wait_ready();
+				return Ok(());
+			}
+			QuadEnable::Sr2Bit7 => (READ_STATUS_2_ALT, WRITE_STATUS_2_ALT, 7),
+			QuadEnable::Sr2Bit1WriteSr2 => (READ_STATUS_2, WRITE_STATUS_2, 1),
+			QuadEnable::Sr2Bit1WriteSr1Sr2 | QuadEnable::Sr2Bit1ReadSr2WriteSr1Sr2 => {
+				let sr1 = self.read_register(READ_STATUS_1);
+				let sr2 = self.read_register(READ_STATUS_2);
+				if sr2 & (1 << 1) != 0 {
+					return Ok(());
+				}
+				self.write_enable();
+				self.qspi.write(&Command::new(WRITE_STATUS), &[sr1, sr2 | (1 << 1)]);
+				self.wait_ready();
+				return Ok(());
+			}
+		};
+
+		let sr = self.read_register(read);
+		if sr & (1 << bit) == 0 {
+			self.write_enable();
+			self.qspi.write(&Command::new(write), &[sr | (1 << bit)]);
+			self.wait_ready();
+		}
+
+		Ok(())
+	}
+}
+
+fn read_sfdp<PINS>(qspi: &mut Qspi<PINS>, address: u32, buffer: &mut [u8]) {
+	let command = Command::new(sfdp::READ_SFDP)
+		.address(address)
+		.dummy_cycles(sfdp::READ_SFDP_DUMMY_CYCLES);
+	qspi.read(&command, buffer);
+}
+
+fn read_parameters<PINS>(qspi: &mut Qspi<PINS>) -> Result<FlashParameters, Error> {
+	// only the first parameter headers are searched, the basic table is mandatory the first one
+	const MAX_HEADERS: usize = 8;
+
+	let mut header = [0u8; sfdp::HEADER_LEN];
+	read_sfdp(qspi, 0, &mut header);
+	let header = sfdp::parse_header(&header)?;
+
+	let count = core::cmp::min(header.parameter_headers, MAX_HEADERS);
+	let mut headers = [0u8; sfdp::HEADER_LEN * MAX_HEADERS];
+	let headers = &mut headers[..count * sfdp::HEADER_LEN];
+	read_sfdp(qspi, sfdp::HEADER_LEN as u32, headers);
+	let basic = sfdp::find_basic_table(headers, count)?;
+
+	let len = core::cmp::min(basic.length, sfdp::BASIC_TABLE_MAX_DWORDS) * 4;
+	let mut table = [0u8; sfdp::BASIC_TABLE_MAX_DWORDS * 4];
+	read_sfdp(qspi, basic.pointer, &mut table[..len]);
+
+	Ok(sfdp::parse_basic_table(&table[..len])?)
+}
+
+impl<PINS> ErrorType for QspiFlash<PINS> {
+	type Error = Error;
+}
+
+impl<PINS> ReadNorFlash for QspiFlash<PINS> {
+	const READ_SIZE: usize = 1;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Error> {
+		check_read(self, offset, bytes.len())?;
+
+		let command = self.with_address(self.read, offset);
+		self.qspi.read(&command, bytes);
+
+		Ok(())
+	}
+
+	fn capacity(&self) -> usize {
+		self.params.size as usize
+	}
+}
+
+impl<PINS> NorFlash for QspiFlash<PINS> {
+	const WRITE_SIZE: usize = 1;
+
+	const ERASE_SIZE: usize = 4096;
+
+	fn erase(&mut self, from: u32, to: u32) -> Result<(), Error> {
+		check_erase(self, from, to)?;
+
+		let mut address = from;
+		while address < to {
+			// the largest erase that fits is used to speed up erasing large areas
+			let erase = self.params.best_erase(address, to - address).ok_or(Error::NotAligned)?;
+
+			self.write_enable();
+			let command = self.with_address(Command::new(erase.instruction), address);
+			self.qspi.command(&command);
+			self.wait_ready();
+
+			address += erase.size;
+		}
+
+		Ok(())
+	}
+
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Error> {
+		check_write(self, offset, bytes.len())?;
+
+		let page_size = self.params.page_size;
+		let mut address = offset;
+		let mut data = bytes;
+		while !data.is_empty() {
+			// a page program must not cross a page boundary
+			let len = core::cmp::min(data.len(), (page_size - address % page_size) as usize);
+
+			self.write_enable();
+			let command = self.with_address(Command::new(PAGE_PROGRAM), address);
+			self.qspi.write(&command, &data[..len]);
+			self.wait_ready();
+
+			address += len as u32;
+			data = &data[len..];
+		}
+
+		Ok(())
+	}
+}
@@ -12,10 +12,11 @@ use crate::target_device::{USART0, USART1, USART2};
 use crate::target_device::PMC;
 
 use crate::gpio::{PeripheralCntr, PeriphA, PeriphB, PeriphC, PeriphD};
-use crate::gpio::pioa::{PA4, PA5, PA6, PA9, PA10, PA21, PA23};
-use crate::gpio::piob::{PB0, PB1, PB4, PB13};
+use crate::gpio::pioa::{PA4, PA5, PA6, PA9, PA10, PA21, PA23, PA24};
+use crate::gpio::piob::{PB0, PB1, PB3, PB4, PB13};
 use crate::gpio::piod::{PD3, PD15, PD16, PD17, PD18, PD19, PD25, PD26, PD28, PD30, PD31};
 use crate::clock_gen::Clocks;
+use crate::dma::{Channel, ChannelId, CircTransfer, DescriptorView0, DmaPeripheral, ReadBuffer, Transfer, WriteBuffer};
 
 /// Serial error
 #[derive(Debug)]
@@ -33,12 +34,13 @@ pub enum Error {
 }
 
 /// Interrupt event
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Event {
     /// New data has been received
     Rxne,
     /// New data can be sent
     Txe,
-    /// Idle line state detected
+    /// Idle line state detected, only available on USARTs with a receiver timeout configured
     Idle,
 }
 
@@ -108,6 +110,14 @@ pub mod config {
 		}
     }
 
+    /// Clock direction in synchronous mode
+    pub enum SyncRole {
+        /// Baudrate is generated internally and driven on the CK pin
+        Master,
+        /// Baudrate is taken from the CK pin
+        Slave,
+    }
+
     #[derive(Debug)]
     pub struct InvalidConfig;
 
@@ -120,6 +130,107 @@ pub mod config {
             }
         }
     }
+
+    pub struct UsartConfig {
+        pub baudrate: Bps,
+        pub parity: Parity,
+        pub wordlength: WordLength,
+        pub stopbits: StopBits,
+        pub timeguard: u8,
+        pub receiver_timeout: u32,
+    }
+
+    impl UsartConfig {
+        pub fn baudrate(mut self, baudrate: Bps) -> Self {
+            self.baudrate = baudrate;
+            self
+        }
+
+        pub fn parity_none(mut self) -> Self {
+            self.parity = Parity::ParityNone;
+            self
+        }
+
+        pub fn parity_even(mut self) -> Self {
+            self.parity = Parity::ParityEven;
+            self
+        }
+
+        pub fn parity_odd(mut self) -> Self {
+            self.parity = Parity::ParityOdd;
+            self
+        }
+
+        pub fn parity_space(mut self) -> Self {
+            self.parity = Parity::ParitySpace;
+            self
+        }
+
+        pub fn parity_mark(mut self) -> Self {
+            self.parity = Parity::ParityMark;
+            self
+        }
+
+        /// Use the parity bit to mark address characters (multidrop mode)
+        pub fn parity_multidrop(mut self) -> Self {
+            self.parity = Parity::ParityMultidrop;
+            self
+        }
+
+        pub fn wordlength_5(mut self) -> Self {
+            self.wordlength = WordLength::DataBits5;
+            self
+        }
+
+        pub fn wordlength_6(mut self) -> Self {
+            self.wordlength = WordLength::DataBits6;
+            self
+        }
+
+        pub fn wordlength_7(mut self) -> Self {
+            self.wordlength = WordLength::DataBits7;
+            self
+        }
+
+        pub fn wordlength_8(mut self) -> Self {
+            self.wordlength = WordLength::DataBits8;
+            self
+        }
+
+        /// 1.5 stop bits are only available in asynchronous mode
+        pub fn stopbits(mut self, stopbits: StopBits) -> Self {
+            self.stopbits = stopbits;
+            self
+        }
+
+        /// Idle bit periods inserted after each character, in RS-485 mode this is the
+        /// turnaround time the RTS line stays asserted after the last stop bit
+        pub fn timeguard(mut self, bits: u8) -> Self {
+            self.timeguard = bits;
+            self
+        }
+
+        /// Idle bit periods after a received character until the `Idle` event is raised,
+        /// 0 disables the receiver timeout
+        pub fn receiver_timeout(mut self, bits: u32) -> Self {
+            self.receiver_timeout = bits;
+            self
+        }
+    }
+
+    impl Default for UsartConfig {
+        fn default() -> UsartConfig {
+            let baudrate = 19_200_u32.bps();
+            UsartConfig {
+                baudrate,
+                parity: Parity::ParityNone,
+                wordlength: WordLength::DataBits8,
+                stopbits: StopBits::STOP1,
+                timeguard: 0,
+                receiver_timeout: 0,
+            }
+        }
+    }
 }
 
 
@@ -127,6 +238,7 @@ pub trait Pins<USART> {}
 pub trait PinTx<USART> {}
 pub trait PinRx<USART> {}
 pub trait PinCk<USART> {}
+pub trait PinRts<USART> {}
 
 impl<USART, TX, RX> Pins<USART> for (TX, RX)
 where
@@ -135,15 +247,35 @@ where
 {
 }
 
+/// Pins required for synchronous operation, the clock pin is mandatory
+pub trait SyncPins<USART> {}
+
+impl<USART, TX, RX, CK> SyncPins<USART> for (TX, RX, CK)
+where
+    TX: PinTx<USART>,
+    RX: PinRx<USART>,
+    CK: PinCk<USART>,
+{
+}
+
+/// Pins required for RS-485 operation, RTS drives the transceiver enable
+pub trait Rs485Pins<USART> {}
+
+impl<USART, TX, RX, RTS> Rs485Pins<USART> for (TX, RX, RTS)
+where
+    TX: PinTx<USART>,
+    RX: PinRx<USART>,
+    RTS: PinRts<USART>,
+{
+}
+
 /// A filler type for when the Tx pin is unnecessary
 pub struct NoTx;
 /// A filler type for when the Rx pin is unnecessary
 pub struct NoRx;
-/// A filler type for when the Ck pin is unnecessary
-pub struct NoCk;
 
 macro_rules! usart_pins {
-    ($($USARTX:ty: TX: [$($TX:ty),*] RX: [$($RX:ty),*] CK: [$($CK:ty),*])+) => {
+    ($($USARTX:ty: TX: [$($TX:ty),*] RX: [$($RX:ty),*] CK: [$($CK:ty),*] RTS: [$($RTS:ty),*])+) => {
         $(
             $(
                 impl PinTx<$USARTX> for $TX {}
@@ -154,6 +286,9 @@ macro_rules! usart_pins {
             $(
                 impl PinCk<$USARTX> for $CK {}
             )*
+            $(
+                impl PinRts<$USARTX> for $RTS {}
+            )*
         )+
     }
 }
@@ -182,8 +317,10 @@ usart_pins! {
 			NoRx
 		]
 		CK : [
-			PB13<PeripheralCntr<PeriphC>>,
-			NoCk
+			PB13<PeripheralCntr<PeriphC>>
+		]
+		RTS : [
+			PB3<PeripheralCntr<PeriphC>>
 		]
 	USART1:
 		TX : [
@@ -195,8 +332,10 @@ usart_pins! {
 			NoRx
 		]
 		CK : [
-			PA23<PeripheralCntr<PeriphA>>,
-			NoCk
+			PA23<PeripheralCntr<PeriphA>>
+		]
+		RTS : [
+			PA24<PeripheralCntr<PeriphA>>
 		]
 	USART2:
 		TX : [
@@ -208,8 +347,10 @@ usart_pins! {
 			NoRx
 		]
 		CK : [
-			PD17<PeripheralCntr<PeriphB>>,
-			NoCk
+			PD17<PeripheralCntr<PeriphB>>
+		]
+		RTS : [
+			PD18<PeripheralCntr<PeriphB>>
 		]
 }
 
@@ -479,6 +620,605 @@ uart_hal! {
 	UART4 : (uart4, pmc_pcer1, pid46),
 }
 
+/// USART operating modes supported by the driver
+enum UsartMode {
+	Asynchronous,
+	Synchronous(config::SyncRole),
+	Rs485,
+}
+
+macro_rules! usart_hal {
+	($( $USARTX:ident: (
+			$usartX:ident,
+			$usartX_sync:ident,
+			$usartX_rs485:ident,
+			$en_reg:ident,
+			$perid:ident
+		),
+	)+) => {
+		$(
+			/// Configures a USART peripheral to provide serial communication
+			impl<PINS> Serial<$USARTX, PINS> {
+				/// Asynchronous mode, baudrate is generated from the master clock
+				pub fn $usartX(
+					usart: $USARTX,
+					pins: PINS,
+					config: config::UsartConfig,
+					clocks: &Clocks,
+					pmc: &mut PMC,
+				) -> Result<Self, config::InvalidConfig>
+				where
+					PINS: Pins<$USARTX>,
+				{
+					Self::configure(usart, pins, config, UsartMode::Asynchronous, clocks, pmc)
+				}
+
+				/// Synchronous mode, as master the baudrate clock is driven on the CK pin
+				pub fn $usartX_sync(
+					usart: $USARTX,
+					pins: PINS,
+					config: config::UsartConfig,
+					role: config::SyncRole,
+					clocks: &Clocks,
+					pmc: &mut PMC,
+				) -> Result<Self, config::InvalidConfig>
+				where
+					PINS: SyncPins<$USARTX>,
+				{
+					Self::configure(usart, pins, config, UsartMode::Synchronous(role), clocks, pmc)
+				}
+
+				/// RS-485 mode, RTS is driven high while transmitting and for the configured
+				/// timeguard afterwards so it can be used as transceiver driver enable
+				pub fn $usartX_rs485(
+					usart: $USARTX,
+					pins: PINS,
+					config: config::UsartConfig,
+					clocks: &Clocks,
+					pmc: &mut PMC,
+				) -> Result<Self, config::InvalidConfig>
+				where
+					PINS: Rs485Pins<$USARTX>,
+				{
+					Self::configure(usart, pins, config, UsartMode::Rs485, clocks, pmc)
+				}
+
+				fn configure(
+					usart: $USARTX,
+					pins: PINS,
+					config: config::UsartConfig,
+					mode: UsartMode,
+					clocks: &Clocks,
+					pmc: &mut PMC,
+				) -> Result<Self, config::InvalidConfig> {
+					use self::config::*;
+
+					//1.5 stop bits are only supported in asynchronous mode
+					if let (UsartMode::Synchronous(_), StopBits::STOP1P5) = (&mode, &config.stopbits) {
+						return Err(InvalidConfig);
+					}
+					if config.receiver_timeout > 0x1_ffff {
+						return Err(InvalidConfig);
+					}
+
+					//enable peripheral clock in pmc
+					pmc.$en_reg.write(|w| w.$perid().set_bit() );
+
+					//reset peripheral
+					usart.us_cr.write(|w| {
+						w.rstrx().set_bit();
+						w.rsttx().set_bit();
+						w.rxdis().set_bit();
+						w.txdis().set_bit();
+						w.rststa().set_bit()
+					});
+
+					//calc correct baudrate div
+					match mode {
+						UsartMode::Asynchronous | UsartMode::Rs485 => {
+							//16x oversampling, the remainder goes into the fractional part
+							let div8 = clocks.mck().0 / (2 * config.baudrate.0);
+							let cd = div8 / 8;
+							if cd == 0 || cd > 0xffff {
+								return Err(InvalidConfig);
+							}
+							usart.us_brgr.write(|w| unsafe {
+								w.cd().bits(cd as u16);
+								w.fp().bits((div8 % 8) as u8)
+							});
+						}
+						UsartMode::Synchronous(SyncRole::Master) => {
+							let cd = clocks.mck().0 / config.baudrate.0;
+							if cd == 0 || cd > 0xffff {
+								return Err(InvalidConfig);
+							}
+							usart.us_brgr.write(|w| unsafe { w.cd().bits(cd as u16) });
+						}
+						UsartMode::Synchronous(SyncRole::Slave) => {
+							//baudrate is defined by the clock on the CK pin
+						}
+					}
+
+					//timeguard, also defines the RS-485 turnaround time
+					usart.us_ttgr.write(|w| unsafe { w.tg().bits(config.timeguard) });
+
+					//receiver timeout used for idle line detection
+					usart.us_rtor.write(|w| unsafe { w.to().bits(config.receiver_timeout) });
+
+					//set mode
+					usart.us_mr.write(|w| unsafe {
+						//normal or rs485 mode
+						match mode {
+							UsartMode::Rs485 => w.usart_mode().bits(1),
+							_ => w.usart_mode().bits(0),
+						};
+						w.chmode().bits(0);
+
+						//clock source, slaves are clocked from the CK pin
+						match mode {
+							UsartMode::Synchronous(SyncRole::Slave) => w.usclks().bits(3),
+							_ => w.usclks().bits(0),
+						};
+						match mode {
+							UsartMode::Asynchronous | UsartMode::Rs485 => {
+								w.sync().clear_bit();
+								w.clko().clear_bit()
+							}
+							UsartMode::Synchronous(SyncRole::Master) => {
+								w.sync().set_bit();
+								w.clko().set_bit()
+							}
+							UsartMode::Synchronous(SyncRole::Slave) => {
+								w.sync().set_bit();
+								w.clko().clear_bit()
+							}
+						};
+						w.over().clear_bit();
+
+						w.chrl().bits( match config.wordlength {
+							WordLength::DataBits5 => 0,
+							WordLength::DataBits6 => 1,
+							WordLength::DataBits7 => 2,
+							WordLength::DataBits8 => 3,
+						});
+						w.mode9().clear_bit();
+
+						w.par().bits( match config.parity {
+							Parity::ParityEven => 0,
+							Parity::ParityOdd => 1,
+							Parity::ParitySpace => 2,
+							Parity::ParityMark => 3,
+							Parity::ParityNone => 4,
+							Parity::ParityMultidrop => 6,
+						});
+
+						w.nbstop().bits( match config.stopbits {
+							StopBits::STOP1 => 0,
+							StopBits::STOP1P5 => 1,
+							StopBits::STOP2 => 2,
+						})
+					});
+
+					//enable receiver and transmitter
+					usart.us_cr.write(|w| {
+						w.txen().set_bit();
+						w.rxen().set_bit()
+					});
+
+					Ok(Serial{usart, pins})
+				}
+
+				/// Splits the `Serial` abstraction into a transmitter and a receiver half
+				pub fn split(self) -> (Tx<$USARTX>, Rx<$USARTX>) {
+
+					(Tx {
+						_usart: PhantomData,
+					},
+					Rx {
+						_usart: PhantomData,
+					},)
+				}
+
+				/// Releases the USART peripheral and associated pins
+				pub fn release(self) -> ($USARTX, PINS) {
+					(self.usart, self.pins)
+				}
+
+				/// Writes all bytes and returns once the last stop bit has left the line
+				pub fn write_all(&mut self, buffer: &[u8]) -> Result<(), Error> {
+					let mut tx: Tx<$USARTX> = Tx {
+						_usart: PhantomData,
+					};
+					tx.write_all(buffer)
+				}
+			}
+
+			impl Tx<$USARTX> {
+				/// Writes all bytes and returns once the last stop bit has left the line
+				///
+				/// In RS-485 mode the transceiver is released when this returns
+				pub fn write_all(&mut self, buffer: &[u8]) -> Result<(), Error> {
+					for byte in buffer {
+						nb::block!(self.write(*byte))?;
+					}
+					// TXEMPTY is only set once the shift register and the timeguard are done
+					nb::block!(self.flush())
+				}
+			}
+
+			impl<PINS> serial::Read<u8> for Serial<$USARTX, PINS> {
+				type Error = Error;
+
+				fn read(&mut self) -> nb::Result<u8, Error> {
+					let mut rx: Rx<$USARTX> = Rx {
+						_usart: PhantomData,
+					};
+					rx.read()
+				}
+			}
+
+			impl serial::Read<u8> for Rx<$USARTX> {
+				type Error = Error;
+
+				fn read(&mut self) -> nb::Result<u8, Error> {
+					// NOTE(unsafe) atomic read with no side effects
+					let sr = unsafe { (*$USARTX::ptr()).us_csr.read() };
+
+					// Error flags are sticky and need to be cleared by a status reset
+					if sr.pare().bit_is_set()
+						|| sr.frame().bit_is_set()
+						|| sr.ovre().bit_is_set()
+					{
+						unsafe {
+							(*$USARTX::ptr()).us_rhr.read();
+							(*$USARTX::ptr()).us_cr.write(|w| w.rststa().set_bit());
+						}
+					}
+
+					Err(if sr.pare().bit_is_set() {
+						nb::Error::Other(Error::Parity)
+					} else if sr.frame().bit_is_set() {
+						nb::Error::Other(Error::Framing)
+					} else if sr.ovre().bit_is_set() {
+						nb::Error::Other(Error::Overrun)
+					} else if sr.rxrdy().bit_is_set() {
+						// NOTE(read_volatile) see `write_volatile` below
+						return Ok(unsafe { ptr::read_volatile(&(*$USARTX::ptr()).us_rhr as *const _ as *const _) });
+					} else {
+						nb::Error::WouldBlock
+					})
+				}
+			}
+
+			impl<PINS> serial::Write<u8> for Serial<$USARTX, PINS> {
+				type Error = Error;
+
+				fn flush(&mut self) -> nb::Result<(), Self::Error> {
+					let mut tx: Tx<$USARTX> = Tx {
+						_usart: PhantomData,
+					};
+					tx.flush()
+				}
+
+				fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+					let mut tx: Tx<$USARTX> = Tx {
+						_usart: PhantomData,
+					};
+					tx.write(byte)
+				}
+			}
+
+			impl serial::Write<u8> for Tx<$USARTX> {
+				type Error = Error;
+
+				fn flush(&mut self) -> nb::Result<(), Self::Error> {
+					// NOTE(unsafe) atomic read with no side effects
+					let sr = unsafe { (*$USARTX::ptr()).us_csr.read() };
+
+					if sr.txempty().bit_is_set() {
+						Ok(())
+					} else {
+						Err(nb::Error::WouldBlock)
+					}
+				}
+
+				fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+					// NOTE(unsafe) atomic read with no side effects
+					let sr = unsafe { (*$USARTX::ptr()).us_csr.read() };
+
+					if sr.txrdy().bit_is_set() {
+						// NOTE(unsafe) atomic write to stateless register
+						// NOTE(write_volatile) 8-bit write that's not possible through the svd2rust API
+						unsafe { ptr::write_volatile(&(*$USARTX::ptr()).us_thr as *const _ as *mut _, byte) }
+						Ok(())
+					} else {
+						Err(nb::Error::WouldBlock)
+					}
+				}
+			}
+		)+
+	}
+}
+
+usart_hal! {
+	USART0 : (usart0, usart0_sync, usart0_rs485, pmc_pcer0, pid13),
+	USART1 : (usart1, usart1_sync, usart1_rs485, pmc_pcer0, pid14),
+	USART2 : (usart2, usart2_sync, usart2_rs485, pmc_pcer0, pid15),
+}
+
+/// DMA transmission, owns the transmitter until the transfer is done
+pub struct TxDma<USART, CH: ChannelId, BUF> {
+	tx: Tx<USART>,
+	transfer: Transfer<CH, BUF>,
+}
+
+impl<USART, CH: ChannelId, BUF> TxDma<USART, CH, BUF> {
+	/// Returns true once all bytes have been handed to the peripheral
+	pub fn is_done(&self) -> bool {
+		self.transfer.is_done()
+	}
+
+	/// Blocks until the transfer is done and releases all resources
+	pub fn wait(self) -> (BUF, Channel<CH>, Tx<USART>) {
+		let (buffer, channel) = self.transfer.wait();
+		(buffer, channel, self.tx)
+	}
+
+	/// Stops the transfer and releases all resources
+	pub fn abort(self) -> (BUF, Channel<CH>, Tx<USART>) {
+		let (buffer, channel) = self.transfer.abort();
+		(buffer, channel, self.tx)
+	}
+}
+
+/// DMA reception, owns the receiver until the transfer is done
+pub struct RxDma<USART, CH: ChannelId, BUF> {
+	rx: Rx<USART>,
+	transfer: Transfer<CH, BUF>,
+}
+
+impl<USART, CH: ChannelId, BUF> RxDma<USART, CH, BUF> {
+	/// Returns true once the buffer has been filled
+	pub fn is_done(&self) -> bool {
+		self.transfer.is_done()
+	}
+
+	/// Blocks until the buffer has been filled and releases all resources
+	pub fn wait(self) -> (BUF, Channel<CH>, Rx<USART>) {
+		let (buffer, channel) = self.transfer.wait();
+		(buffer, channel, self.rx)
+	}
+
+	/// Stops the transfer and releases all resources
+	pub fn abort(self) -> (BUF, Channel<CH>, Rx<USART>) {
+		let (buffer, channel) = self.transfer.abort();
+		(buffer, channel, self.rx)
+	}
+}
+
+/// Continuous DMA reception into a ring buffer
+pub struct RxCircDma<USART, CH: ChannelId, BUF: WriteBuffer<Word = u8>> {
+	rx: Rx<USART>,
+	transfer: CircTransfer<CH, BUF>,
+}
+
+impl<USART, CH: ChannelId, BUF: WriteBuffer<Word = u8>> RxCircDma<USART, CH, BUF> {
+	/// Number of received bytes that have not been read yet
+	pub fn available(&self) -> usize {
+		self.transfer.available()
+	}
+
+	/// Copies received bytes into `buffer` and returns the number of bytes copied
+	pub fn read(&mut self, buffer: &mut [u8]) -> usize {
+		self.transfer.read(buffer)
+	}
+
+	/// Stops the reception and releases all resources
+	pub fn stop(self) -> (BUF, &'static mut DescriptorView0, Channel<CH>, Rx<USART>) {
+		let (buffer, descriptor, channel) = self.transfer.stop();
+		(buffer, descriptor, channel, self.rx)
+	}
+}
+
+macro_rules! serial_dma {
+	($( $USARTX:ident: (
+			$thr:ident,
+			$rhr:ident,
+			$dma_tx:ident,
+			$dma_rx:ident
+		),
+	)+) => {
+		$(
+			impl Tx<$USARTX> {
+				/// Transmits `buffer` using the DMA `channel`
+				pub fn write_all_dma<CH, B>(self, buffer: B, channel: Channel<CH>) -> TxDma<$USARTX, CH, B>
+				where
+					CH: ChannelId,
+					B: ReadBuffer<Word = u8>,
+				{
+					// NOTE(unsafe) only the address of the register is used
+					let thr = unsafe { &(*$USARTX::ptr()).$thr as *const _ as u32 };
+					let transfer = channel.write_to_peripheral(DmaPeripheral::$dma_tx, thr, buffer);
+
+					TxDma { tx: self, transfer }
+				}
+			}
+
+			impl Rx<$USARTX> {
+				/// Fills `buffer` with received bytes using the DMA `channel`
+				pub fn read_exact_dma<CH, B>(self, buffer: B, channel: Channel<CH>) -> RxDma<$USARTX, CH, B>
+				where
+					CH: ChannelId,
+					B: WriteBuffer<Word = u8>,
+				{
+					// NOTE(unsafe) only the address of the register is used
+					let rhr = unsafe { &(*$USARTX::ptr()).$rhr as *const _ as u32 };
+					let transfer = channel.read_from_peripheral(DmaPeripheral::$dma_rx, rhr, buffer);
+
+					RxDma { rx: self, transfer }
+				}
+
+				/// Continuously receives into the ring buffer `buffer` using the DMA `channel`
+				pub fn circular_read_dma<CH, B>(
+					self,
+					buffer: B,
+					descriptor: &'static mut DescriptorView0,
+					channel: Channel<CH>
+				) -> RxCircDma<$USARTX, CH, B>
+				where
+					CH: ChannelId,
+					B: WriteBuffer<Word = u8>,
+				{
+					// NOTE(unsafe) only the address of the register is used
+					let rhr = unsafe { &(*$USARTX::ptr()).$rhr as *const _ as u32 };
+					let transfer = channel.circular_from_peripheral(DmaPeripheral::$dma_rx, rhr, buffer, descriptor);
+
+					RxCircDma { rx: self, transfer }
+				}
+			}
+		)+
+	}
+}
+
+serial_dma! {
+	UART0 : (uart_thr, uart_rhr, Uart0Tx, Uart0Rx),
+	UART1 : (uart_thr, uart_rhr, Uart1Tx, Uart1Rx),
+	UART2 : (uart_thr, uart_rhr, Uart2Tx, Uart2Rx),
+	UART3 : (uart_thr, uart_rhr, Uart3Tx, Uart3Rx),
+	UART4 : (uart_thr, uart_rhr, Uart4Tx, Uart4Rx),
+	USART0 : (us_thr, us_rhr, Usart0Tx, Usart0Rx),
+	USART1 : (us_thr, us_rhr, Usart1Tx, Usart1Rx),
+	USART2 : (us_thr, us_rhr, Usart2Tx, Usart2Rx),
+}
+
+// Interrupt flags shared by the status and interrupt registers of UART and USART
+const RXRDY: u32 = 1 << 0;
+const TXRDY: u32 = 1 << 1;
+const TIMEOUT: u32 = 1 << 8;
+
+macro_rules! serial_interrupts {
+	($( $USARTX:ident: (
+			$ier:ident,
+			$idr:ident,
+			$imr:ident,
+			$sr:ident,
+			$idle:expr
+		),
+	)+) => {
+		$(
+			serial_interrupts!(@half Rx, $USARTX, $ier, $idr, $imr, $sr, $idle);
+			serial_interrupts!(@half Tx, $USARTX, $ier, $idr, $imr, $sr, $idle);
+
+			impl<PINS> Serial<$USARTX, PINS> {
+				/// Starts listening for an interrupt event
+				///
+				/// Fails for events the peripheral does not support, i.e. `Idle` on UARTs.
+				pub fn listen(&mut self, event: Event) -> Result<(), config::InvalidConfig> {
+					let mut rx: Rx<$USARTX> = Rx {
+						_usart: PhantomData,
+					};
+					rx.listen(event)
+				}
+
+				/// Stops listening for an interrupt event
+				pub fn unlisten(&mut self, event: Event) {
+					let mut rx: Rx<$USARTX> = Rx {
+						_usart: PhantomData,
+					};
+					rx.unlisten(event)
+				}
+
+				/// Returns true if the event is enabled and its status flag is set
+				pub fn is_pending(&self, event: Event) -> bool {
+					let rx: Rx<$USARTX> = Rx {
+						_usart: PhantomData,
+					};
+					rx.is_pending(event)
+				}
+			}
+		)+
+	};
+	(@half $Half:ident, $USARTX:ident, $ier:ident, $idr:ident, $imr:ident, $sr:ident, $idle:expr) => {
+		impl $Half<$USARTX> {
+			fn event_mask(event: Event) -> u32 {
+				match event {
+					Event::Rxne => RXRDY,
+					Event::Txe => TXRDY,
+					Event::Idle => $idle,
+				}
+			}
+
+			/// Starts listening for an interrupt event
+			///
+			/// Fails for events the peripheral does not support, i.e. `Idle` on UARTs.
+			pub fn listen(&mut self, event: Event) -> Result<(), config::InvalidConfig> {
+				let mask = Self::event_mask(event);
+				if mask == 0 {
+					return Err(config::InvalidConfig);
+				}
+				// NOTE(unsafe) write-one register, does not affect other interrupts
+				unsafe { (*$USARTX::ptr()).$ier.write(|w| w.bits(mask)) };
+				Ok(())
+			}
+
+			/// Stops listening for an interrupt event
+			pub fn unlisten(&mut self, event: Event) {
+				// NOTE(unsafe) write-one register, does not affect other interrupts
+				unsafe { (*$USARTX::ptr()).$idr.write(|w| w.bits(Self::event_mask(event))) }
+			}
+
+			/// Returns true if the event is enabled and its status flag is set
+			pub fn is_pending(&self, event: Event) -> bool {
+				// NOTE(unsafe) atomic reads with no side effects
+				let (sr, imr) = unsafe { ((*$USARTX::ptr()).$sr.read().bits(), (*$USARTX::ptr()).$imr.read().bits()) };
+				sr & imr & Self::event_mask(event) != 0
+			}
+		}
+	};
+}
+
+serial_interrupts! {
+	// UARTs have no receiver timeout, the `Idle` event is not supported
+	UART0 : (uart_ier, uart_idr, uart_imr, uart_sr, 0),
+	UART1 : (uart_ier, uart_idr, uart_imr, uart_sr, 0),
+	UART2 : (uart_ier, uart_idr, uart_imr, uart_sr, 0),
+	UART3 : (uart_ier, uart_idr, uart_imr, uart_sr, 0),
+	UART4 : (uart_ier, uart_idr, uart_imr, uart_sr, 0),
+	USART0 : (us_ier, us_idr, us_imr, us_csr, TIMEOUT),
+	USART1 : (us_ier, us_idr, us_imr, us_csr, TIMEOUT),
+	USART2 : (us_ier, us_idr, us_imr, us_csr, TIMEOUT),
+}
+
+macro_rules! usart_idle {
+	($($USARTX:ident,)+) => {
+		$(
+			impl Rx<$USARTX> {
+				/// Clears the `Idle` event, the next timeout starts with the next received character
+				pub fn clear_idle(&mut self) {
+					// NOTE(unsafe) write-one register, only affects the receiver timeout
+					unsafe { (*$USARTX::ptr()).us_cr.write(|w| w.sttto().set_bit()) }
+				}
+			}
+
+			impl<PINS> Serial<$USARTX, PINS> {
+				/// Clears the `Idle` event, the next timeout starts with the next received character
+				pub fn clear_idle(&mut self) {
+					let mut rx: Rx<$USARTX> = Rx {
+						_usart: PhantomData,
+					};
+					rx.clear_idle()
+				}
+			}
+		)+
+	}
+}
+
+usart_idle! {
+	USART0,
+	USART1,
+	USART2,
+}
+
 impl<USART, PINS> fmt::Write for Serial<USART, PINS>
 	where
 	    Serial<USART, PINS>: crate::hal::serial::Write<u8>,
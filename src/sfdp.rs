@@ -0,0 +1,513 @@
+//! Serial Flash Discoverable Parameters (JESD216)
+//!
+//! Plain parser for the SFDP structures of serial NOR flashes, it does not touch any hardware.
+//! The raw bytes are read by a flash driver, e.g. `qspi_flash`, with the read SFDP instruction.
+
+/// Instruction reading the SFDP area, followed by a 24 bit address and 8 dummy cycles
+pub const READ_SFDP: u8 = 0x5a;
+
+/// Dummy cycles between the address and data of a `READ_SFDP` instruction
+pub const READ_SFDP_DUMMY_CYCLES: u8 = 8;
+
+/// "SFDP" in little endian
+const SIGNATURE: u32 = 0x5044_4653;
+
+/// Id of the JEDEC basic flash parameter table
+pub const BASIC_TABLE_ID: u16 = 0xff00;
+
+/// Length of the SFDP header and of every parameter header
+pub const HEADER_LEN: usize = 8;
+
+/// Number of DWORDs of the basic flash parameter table used by this parser
+pub const BASIC_TABLE_MAX_DWORDS: usize = 16;
+
+/// SFDP parsing error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+	/// Header does not start with the SFDP signature
+	InvalidSignature,
+	/// Not enough bytes to parse the structure
+	TooShort,
+	/// No basic flash parameter table found
+	MissingBasicTable,
+	/// Flash density is larger than the 4GiB addressable with 32 bits
+	UnsupportedDensity,
+}
+
+/// SFDP header
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Header {
+	pub minor: u8,
+	pub major: u8,
+	/// Number of parameter headers following the SFDP header
+	pub parameter_headers: usize,
+}
+
+/// Parameter header, points to a parameter table
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ParameterHeader {
+	pub id: u16,
+	pub minor: u8,
+	pub major: u8,
+	/// Length of the table in DWORDs
+	pub length: usize,
+	/// Byte address of the table in the SFDP area
+	pub pointer: u32,
+}
+
+/// Address bytes accepted by the flash
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AddressBytes {
+	Three,
+	ThreeOrFour,
+	Four,
+}
+
+/// Fast read instruction with its dummy and mode clock cycles
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FastRead {
+	pub instruction: u8,
+	pub dummy_cycles: u8,
+	pub mode_cycles: u8,
+}
+
+/// Erase instruction and the size it erases
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EraseType {
+	pub instruction: u8,
+	/// Erased size in bytes
+	pub size: u32,
+}
+
+/// Method to set the quad enable bit required for quad data transfers
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QuadEnable {
+	/// No quad enable bit, or the method is not described by the table
+	None,
+	/// Bit 1 of status register 2, written together with status register 1 by 0x01
+	Sr2Bit1WriteSr1Sr2,
+	/// Bit 6 of status register 1, written by 0x01
+	Sr1Bit6,
+	/// Bit 7 of status register 2, read by 0x3f and written by 0x3e
+	Sr2Bit7,
+	/// Bit 1 of status register 2 read by 0x35, written together with status register 1 by 0x01
+	Sr2Bit1ReadSr2WriteSr1Sr2,
+	/// Bit 1 of status register 2 read by 0x35 and written by 0x31
+	Sr2Bit1WriteSr2,
+}
+
+/// Parameters discovered from the basic flash parameter table
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FlashParameters {
+	/// Flash size in bytes
+	pub size: u32,
+	/// Page program size in bytes
+	pub page_size: u32,
+	pub address_bytes: AddressBytes,
+	/// Erase types sorted as in the table, unused entries are `None`
+	pub erase_types: [Option<EraseType>; 4],
+	pub fast_read_1_1_2: Option<FastRead>,
+	pub fast_read_1_2_2: Option<FastRead>,
+	pub fast_read_1_1_4: Option<FastRead>,
+	pub fast_read_1_4_4: Option<FastRead>,
+	pub quad_enable: QuadEnable,
+}
+
+impl FlashParameters {
+	/// Smallest supported erase type
+	pub fn smallest_erase(&self) -> Option<EraseType> {
+		self.erase_types.iter().filter_map(|e| *e).min_by_key(|e| e.size)
+	}
+
+	/// Largest erase type that fits at `offset` and does not erase more than `len` bytes
+	pub fn best_erase(&self, offset: u32, len: u32) -> Option<EraseType> {
+		self.erase_types.iter()
+			.filter_map(|e| *e)
+			.filter(|e| offset % e.size == 0 && e.size <= len)
+			.max_by_key(|e| e.size)
+	}
+}
+
+fn dword(bytes: &[u8], index: usize) -> u32 {
+	let i = index * 4;
+	u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+}
+
+/// Parses the 8 byte SFDP header at address 0 of the SFDP area
+pub fn parse_header(bytes: &[u8]) -> Result<Header, Error> {
+	if bytes.len() < HEADER_LEN {
+		return Err(Error::TooShort);
+	}
+	if dword(bytes, 0) != SIGNATURE {
+		return Err(Error::InvalidSignature);
+	}
+
+	Ok(Header {
+		minor: bytes[4],
+		major: bytes[5],
+		// NPH is zero based
+		parameter_headers: bytes[6] as usize + 1,
+	})
+}
+
+/// Parses an 8 byte parameter header
+pub fn parse_parameter_header(bytes: &[u8]) -> Result<ParameterHeader, Error> {
+	if bytes.len() < HEADER_LEN {
+		return Err(Error::TooShort);
+	}
+
+	Ok(ParameterHeader {
+		id: (bytes[7] as u16) << 8 | bytes[0] as u16,
+		minor: bytes[1],
+		major: bytes[2],
+		length: bytes[3] as usize,
+		pointer: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], 0]),
+	})
+}
+
+/// Finds the basic flash parameter table in `headers`, the bytes following the SFDP header
+///
+/// If multiple revisions are present the most recent one is returned.
+pub fn find_basic_table(headers: &[u8], count: usize) -> Result<ParameterHeader, Error> {
+	let mut basic : Option<ParameterHeader> = None;
+
+	for i in 0..count {
+		let start = i * HEADER_LEN;
+		if headers.len() < start + HEADER_LEN {
+			return Err(Error::TooShort);
+		}
+		let header = parse_parameter_header(&headers[start..start + HEADER_LEN])?;
+		if header.id != BASIC_TABLE_ID {
+			continue;
+		}
+		let newer = match basic {
+			None => true,
+			Some(b) => (header.major, header.minor) > (b.major, b.minor),
+		};
+		if newer {
+			basic = Some(header);
+		}
+	}
+
+	basic.ok_or(Error::MissingBasicTable)
+}
+
+fn fast_read(bits: u32) -> FastRead {
+	FastRead {
+		instruction: (bits >> 8) as u8,
+		dummy_cycles: (bits & 0x1f) as u8,
+		mode_cycles: ((bits >> 5) & 0x7) as u8,
+	}
+}
+
+fn erase_type(bits: u32) -> Option<EraseType> {
+	let exponent = bits & 0xff;
+	// an exponent of 0 marks an unused erase type
+	if exponent == 0 || exponent >= 32 {
+		return None;
+	}
+	Some(EraseType {
+		instruction: (bits >> 8) as u8,
+		size: 1 << exponent,
+	})
+}
+
+/// Parses the basic flash parameter table, `bytes` holds the raw table as read from the flash
+///
+/// Tables of JESD216 revision 0 (9 DWORDs) and later are supported.
+pub fn parse_basic_table(bytes: &[u8]) -> Result<FlashParameters, Error> {
+	let dwords = bytes.len() / 4;
+	if dwords < 9 {
+		return Err(Error::TooShort);
+	}
+
+	let dw1 = dword(bytes, 0);
+	let dw2 = dword(bytes, 1);
+
+	let address_bytes = match (dw1 >> 17) & 0x3 {
+		0 => AddressBytes::Three,
+		1 => AddressBytes::ThreeOrFour,
+		_ => AddressBytes::Four,
+	};
+
+	// density is given in bits, either directly or as a power of two
+	let bits : u64 = if dw2 & 0x8000_0000 == 0 {
+		dw2 as u64 + 1
+	} else {
+		let exponent = dw2 & 0x7fff_ffff;
+		if exponent > 35 {
+			return Err(Error::UnsupportedDensity);
+		}
+		1 << exponent
+	};
+	let size = bits / 8;
+	if size > 1 << 32 || size == 0 {
+		return Err(Error::UnsupportedDensity);
+	}
+	// a size of exactly 4GiB is clamped, the last byte can not be addressed anyway
+	let size = core::cmp::min(size, u32::max_value() as u64) as u32;
+
+	let dw3 = dword(bytes, 2);
+	let dw4 = dword(bytes, 3);
+
+	let fast_read_1_1_2 = if dw1 & (1 << 16) != 0 { Some(fast_read(dw4 & 0xffff)) } else { None };
+	let fast_read_1_2_2 = if dw1 & (1 << 20) != 0 { Some(fast_read(dw4 >> 16)) } else { None };
+	let fast_read_1_4_4 = if dw1 & (1 << 21) != 0 { Some(fast_read(dw3 & 0xffff)) } else { None };
+	let fast_read_1_1_4 = if dw1 & (1 << 22) != 0 { Some(fast_read(dw3 >> 16)) } else { None };
+
+	let dw8 = dword(bytes, 7);
+	let dw9 = dword(bytes, 8);
+	let mut erase_types = [
+		erase_type(dw8 & 0xffff),
+		erase_type(dw8 >> 16),
+		erase_type(dw9 & 0xffff),
+		erase_type(dw9 >> 16),
+	];
+
+	// revision 0 tables only describe the 4KiB erase in DWORD 1
+	if erase_types.iter().all(|e| e.is_none()) && dw1 & 0x3 == 0x1 {
+		erase_types[0] = Some(EraseType {
+			instruction: (dw1 >> 8) as u8,
+			size: 4096,
+		});
+	}
+
+	// page size was added with revision A, older flashes use 256 bytes pages
+	let page_size = if dwords >= 11 {
+		1 << ((dword(bytes, 10) >> 4) & 0xf)
+	} else {
+		256
+	};
+
+	let quad_enable = if dwords >= 15 {
+		match (dword(bytes, 14) >> 20) & 0x7 {
+			1 => QuadEnable::Sr2Bit1WriteSr1Sr2,
+			2 => QuadEnable::Sr1Bit6,
+			3 => QuadEnable::Sr2Bit7,
+			4 => QuadEnable::Sr2Bit1WriteSr1Sr2,
+			5 => QuadEnable::Sr2Bit1ReadSr2WriteSr1Sr2,
+			6 => QuadEnable::Sr2Bit1WriteSr2,
+			_ => QuadEnable::None,
+		}
+	} else {
+		QuadEnable::None
+	};
+
+	Ok(FlashParameters {
+		size,
+		page_size,
+		address_bytes,
+		erase_types,
+		fast_read_1_1_2,
+		fast_read_1_2_2,
+		fast_read_1_1_4,
+		fast_read_1_4_4,
+		quad_enable,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// SFDP area of a W25Q128JV (JESD216B), one basic table of 16 DWORDs at 0x80
+	const W25Q128JV: [u8; 0xc0] = [
+		0x53, 0x46, 0x44, 0x50, 0x06, 0x01, 0x00, 0xff, 0x00, 0x06, 0x01, 0x10, 0x80, 0x00, 0x00, 0xff,
+		0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+		0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+		0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+		0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+		0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+		0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+		0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+		0xe5, 0x20, 0xf9, 0xff, 0xff, 0xff, 0xff, 0x07, 0x44, 0xeb, 0x08, 0x6b, 0x08, 0x3b, 0x42, 0xbb,
+		0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0xff, 0xff, 0x40, 0xeb, 0x0c, 0x20, 0x0f, 0x52,
+		0x10, 0xd8, 0x00, 0x00, 0x36, 0x02, 0xa6, 0x00, 0x82, 0xea, 0x14, 0xc9, 0xe9, 0x63, 0x76, 0x33,
+		0x7a, 0x75, 0x7a, 0x75, 0xf7, 0xa2, 0xd5, 0x5c, 0x19, 0xf7, 0x4d, 0xff, 0xe9, 0x30, 0xf8, 0x80,
+	];
+
+	/// SFDP area of an MX25L3206E (JESD216 revision 0), a 9 DWORD basic table at 0x30 and a
+	/// vendor table at 0x60
+	const MX25L3206E: [u8; 0x70] = [
+		0x53, 0x46, 0x44, 0x50, 0x00, 0x01, 0x01, 0xff, 0x00, 0x00, 0x01, 0x09, 0x30, 0x00, 0x00, 0xff,
+		0xc2, 0x00, 0x01, 0x04, 0x60, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+		0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+		0xe5, 0x20, 0xf1, 0xff, 0xff, 0xff, 0xff, 0x01, 0x44, 0xeb, 0x08, 0x6b, 0x08, 0x3b, 0x04, 0xbb,
+		0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0xff, 0xff, 0xff, 0x00, 0xff, 0x0c, 0x20, 0x0f, 0x52,
+		0x10, 0xd8, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+		0x00, 0x36, 0x00, 0x27, 0xf4, 0x4f, 0xff, 0xff, 0xd9, 0xc8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+	];
+
+	fn basic_table(sfdp: &[u8]) -> &[u8] {
+		let header = parse_header(sfdp).unwrap();
+		let basic = find_basic_table(&sfdp[HEADER_LEN..], header.parameter_headers).unwrap();
+		let start = basic.pointer as usize;
+		&sfdp[start..start + basic.length * 4]
+	}
+
+	fn set_dword(table: &mut [u8], index: usize, value: u32) {
+		table[index * 4..index * 4 + 4].copy_from_slice(&value.to_le_bytes());
+	}
+
+	#[test]
+	fn header() {
+		let header = parse_header(&W25Q128JV).unwrap();
+		assert_eq!(header, Header { minor: 6, major: 1, parameter_headers: 1 });
+
+		let header = parse_header(&MX25L3206E).unwrap();
+		assert_eq!(header, Header { minor: 0, major: 1, parameter_headers: 2 });
+	}
+
+	#[test]
+	fn invalid_header() {
+		let mut sfdp = W25Q128JV;
+		sfdp[0] = 0;
+		assert_eq!(parse_header(&sfdp), Err(Error::InvalidSignature));
+		assert_eq!(parse_header(&W25Q128JV[..4]), Err(Error::TooShort));
+	}
+
+	#[test]
+	fn parameter_headers() {
+		let basic = find_basic_table(&MX25L3206E[HEADER_LEN..], 2).unwrap();
+		assert_eq!(basic, ParameterHeader { id: BASIC_TABLE_ID, minor: 0, major: 1, length: 9, pointer: 0x30 });
+
+		let vendor = parse_parameter_header(&MX25L3206E[2 * HEADER_LEN..]).unwrap();
+		assert_eq!(vendor, ParameterHeader { id: 0xffc2, minor: 0, major: 1, length: 4, pointer: 0x60 });
+
+		// the headers of the vendor table do not contain a basic table
+		assert_eq!(find_basic_table(&MX25L3206E[2 * HEADER_LEN..], 1), Err(Error::MissingBasicTable));
+		assert_eq!(find_basic_table(&MX25L3206E[HEADER_LEN..2 * HEADER_LEN], 2), Err(Error::TooShort));
+	}
+
+	#[test]
+	fn multiple_basic_table_revisions() {
+		let headers: [u8; 24] = [
+			0x00, 0x00, 0x01, 0x09, 0x30, 0x00, 0x00, 0xff,
+			0x00, 0x06, 0x01, 0x10, 0x80, 0x00, 0x00, 0xff,
+			0x00, 0x05, 0x01, 0x10, 0xc0, 0x00, 0x00, 0xff,
+		];
+		let basic = find_basic_table(&headers, 3).unwrap();
+		assert_eq!((basic.major, basic.minor, basic.pointer), (1, 6, 0x80));
+	}
+
+	#[test]
+	fn revision_b_table() {
+		let params = parse_basic_table(basic_table(&W25Q128JV)).unwrap();
+
+		assert_eq!(params.size, 16 * 1024 * 1024);
+		assert_eq!(params.page_size, 256);
+		assert_eq!(params.address_bytes, AddressBytes::Three);
+		assert_eq!(params.erase_types, [
+			Some(EraseType { instruction: 0x20, size: 4096 }),
+			Some(EraseType { instruction: 0x52, size: 32 * 1024 }),
+			Some(EraseType { instruction: 0xd8, size: 64 * 1024 }),
+			None,
+		]);
+		assert_eq!(params.fast_read_1_1_2, Some(FastRead { instruction: 0x3b, dummy_cycles: 8, mode_cycles: 0 }));
+		assert_eq!(params.fast_read_1_2_2, Some(FastRead { instruction: 0xbb, dummy_cycles: 2, mode_cycles: 2 }));
+		assert_eq!(params.fast_read_1_1_4, Some(FastRead { instruction: 0x6b, dummy_cycles: 8, mode_cycles: 0 }));
+		assert_eq!(params.fast_read_1_4_4, Some(FastRead { instruction: 0xeb, dummy_cycles: 4, mode_cycles: 2 }));
+		assert_eq!(params.quad_enable, QuadEnable::Sr2Bit1WriteSr1Sr2);
+
+		assert_eq!(params.smallest_erase(), Some(EraseType { instruction: 0x20, size: 4096 }));
+		assert_eq!(params.best_erase(0x1_0000, 0x1_0000).map(|e| e.size), Some(64 * 1024));
+		assert_eq!(params.best_erase(0x8000, 0x1_0000).map(|e| e.size), Some(32 * 1024));
+		assert_eq!(params.best_erase(0x1000, 0x1_0000).map(|e| e.size), Some(4096));
+		assert_eq!(params.best_erase(0x100, 0x1000), None);
+	}
+
+	#[test]
+	fn revision_a_table() {
+		// JESD216A tables have the same 16 DWORDs, only the 4-byte address details differ
+		let mut table = [0u8; 64];
+		table.copy_from_slice(basic_table(&W25Q128JV));
+		// 256 MiB flash requiring 4 byte addresses, 512 byte pages
+		set_dword(&mut table, 0, 0xfffb_20e5);
+		set_dword(&mut table, 1, 0x7fff_ffff);
+		set_dword(&mut table, 10, 0xc914_ea92);
+
+		let params = parse_basic_table(&table).unwrap();
+		assert_eq!(params.size, 256 * 1024 * 1024);
+		assert_eq!(params.address_bytes, AddressBytes::ThreeOrFour);
+		assert_eq!(params.page_size, 512);
+	}
+
+	#[test]
+	fn revision_0_table() {
+		let table = basic_table(&MX25L3206E);
+		assert_eq!(table.len(), 36);
+
+		let params = parse_basic_table(table).unwrap();
+		assert_eq!(params.size, 4 * 1024 * 1024);
+		// page size and quad enable are not part of revision 0
+		assert_eq!(params.page_size, 256);
+		assert_eq!(params.quad_enable, QuadEnable::None);
+		assert_eq!(params.erase_types[2], Some(EraseType { instruction: 0xd8, size: 64 * 1024 }));
+		assert_eq!(params.fast_read_1_2_2, Some(FastRead { instruction: 0xbb, dummy_cycles: 4, mode_cycles: 0 }));
+
+		assert_eq!(parse_basic_table(&table[..32]), Err(Error::TooShort));
+	}
+
+	#[test]
+	fn density() {
+		let mut table = [0u8; 64];
+		table.copy_from_slice(basic_table(&W25Q128JV));
+
+		// 2 Gbit as a power of two
+		set_dword(&mut table, 1, 0x8000_001f);
+		assert_eq!(parse_basic_table(&table).unwrap().size, 256 * 1024 * 1024);
+
+		// 32 Gbit is clamped to the addressable range
+		set_dword(&mut table, 1, 0x8000_0023);
+		assert_eq!(parse_basic_table(&table).unwrap().size, u32::max_value());
+
+		set_dword(&mut table, 1, 0x8000_0024);
+		assert_eq!(parse_basic_table(&table), Err(Error::UnsupportedDensity));
+
+		// 1 Mbit as a count of bits
+		set_dword(&mut table, 1, 0x000f_ffff);
+		assert_eq!(parse_basic_table(&table).unwrap().size, 128 * 1024);
+	}
+
+	#[test]
+	fn erase_type_fallback() {
+		let mut table = [0u8; 36];
+		table.copy_from_slice(basic_table(&MX25L3206E));
+		set_dword(&mut table, 7, 0);
+		set_dword(&mut table, 8, 0);
+
+		let params = parse_basic_table(&table).unwrap();
+		assert_eq!(params.erase_types, [Some(EraseType { instruction: 0x20, size: 4096 }), None, None, None]);
+
+		// no 4 KiB erase either
+		set_dword(&mut table, 0, 0xfff1_ffe7);
+		let params = parse_basic_table(&table).unwrap();
+		assert_eq!(params.erase_types, [None; 4]);
+		assert_eq!(params.smallest_erase(), None);
+	}
+
+	#[test]
+	fn quad_enable() {
+		let mut table = [0u8; 64];
+		table.copy_from_slice(basic_table(&W25Q128JV));
+		let dw15 = 0xff4d_f719 & !(0x7 << 20);
+
+		let expected = [
+			QuadEnable::None,
+			QuadEnable::Sr2Bit1WriteSr1Sr2,
+			QuadEnable::Sr1Bit6,
+			QuadEnable::Sr2Bit7,
+			QuadEnable::Sr2Bit1WriteSr1Sr2,
+			QuadEnable::Sr2Bit1ReadSr2WriteSr1Sr2,
+			QuadEnable::Sr2Bit1WriteSr2,
+			QuadEnable::None,
+		];
+		for (qer, expected) in expected.iter().enumerate() {
+			set_dword(&mut table, 14, dw15 | (qer as u32) << 20);
+			assert_eq!(parse_basic_table(&table).unwrap().quad_enable, *expected);
+		}
+
+		// DWORD 15 is missing in shorter tables
+		assert_eq!(parse_basic_table(&table[..14 * 4]).unwrap().quad_enable, QuadEnable::None);
+	}
+}
@@ -0,0 +1,683 @@
+//! Serial Peripheral Interface
+
+use core::ptr;
+
+use embedded_hal::spi;
+pub use embedded_hal::spi::{Mode, Phase, Polarity, MODE_0, MODE_1, MODE_2, MODE_3};
+
+use crate::target_device::{SPI0, SPI1};
+use crate::target_device::PMC;
+
+use crate::gpio::{PeripheralCntr, PeriphA, PeriphB, PeriphC, PeriphD};
+use crate::gpio::pioa::{PA31};
+use crate::gpio::piob::{PB2};
+use crate::gpio::pioc::{PC24, PC25, PC26, PC27, PC28, PC29, PC30};
+use crate::gpio::piod::{PD0, PD1, PD2, PD12, PD20, PD21, PD22, PD25, PD27};
+use crate::clock_gen::Clocks;
+use crate::time::{Hertz, NanoSeconds, PicoSeconds};
+use crate::dma::{Channel, ChannelId, CircTransfer, DescriptorView0, DmaPeripheral, ReadBuffer, Transfer, WriteBuffer};
+
+/// SPI error
+#[derive(Debug)]
+pub enum Error {
+	/// RX buffer overrun
+	Overrun,
+	/// Mode fault, another master drove NSS low
+	ModeFault,
+	/// TX buffer underrun, the master clocked data before it was provided (slave mode only)
+	Underrun,
+	#[doc(hidden)]
+	_Extensible,
+}
+
+pub mod config {
+	use crate::time::{Hertz, NanoSeconds};
+	use crate::time::U32Ext;
+	use super::{Mode, MODE_0};
+
+	/// Settings applied to a single chip select line
+	pub struct ChipSelectConfig {
+		pub mode: Mode,
+		pub baudrate: Hertz,
+		pub bits: u8,
+		pub keep_active: bool,
+		pub delay_before_spck: NanoSeconds,
+		pub delay_between_transfers: NanoSeconds,
+	}
+
+	impl ChipSelectConfig {
+		pub fn mode(mut self, mode: Mode) -> Self {
+			self.mode = mode;
+			self
+		}
+
+		pub fn baudrate(mut self, baudrate: Hertz) -> Self {
+			self.baudrate = baudrate;
+			self
+		}
+
+		/// Bits per transfer, between 8 and 16
+		pub fn bits(mut self, bits: u8) -> Self {
+			self.bits = bits;
+			self
+		}
+
+		/// Keep the chip select asserted between transfers until `Spi::deselect` is called
+		pub fn keep_active(mut self, keep_active: bool) -> Self {
+			self.keep_active = keep_active;
+			self
+		}
+
+		/// Delay between chip select assertion and the first clock edge
+		pub fn delay_before_spck(mut self, delay: NanoSeconds) -> Self {
+			self.delay_before_spck = delay;
+			self
+		}
+
+		/// Delay between two consecutive transfers
+		pub fn delay_between_transfers(mut self, delay: NanoSeconds) -> Self {
+			self.delay_between_transfers = delay;
+			self
+		}
+	}
+
+	impl Default for ChipSelectConfig {
+		fn default() -> ChipSelectConfig {
+			ChipSelectConfig {
+				mode: MODE_0,
+				baudrate: 1_000_000_u32.hz(),
+				bits: 8,
+				keep_active: false,
+				delay_before_spck: 0_u32.ns(),
+				delay_between_transfers: 0_u32.ns(),
+			}
+		}
+	}
+
+	/// Settings of the peripheral in slave mode, the clock is provided by the master
+	pub struct SlaveConfig {
+		pub mode: Mode,
+		pub bits: u8,
+	}
+
+	impl SlaveConfig {
+		pub fn mode(mut self, mode: Mode) -> Self {
+			self.mode = mode;
+			self
+		}
+
+		/// Bits per transfer, between 8 and 16
+		pub fn bits(mut self, bits: u8) -> Self {
+			self.bits = bits;
+			self
+		}
+	}
+
+	impl Default for SlaveConfig {
+		fn default() -> SlaveConfig {
+			SlaveConfig {
+				mode: MODE_0,
+				bits: 8,
+			}
+		}
+	}
+
+	#[derive(Debug)]
+	pub struct InvalidConfig;
+}
+
+/// Hardware chip select lines
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChipSelect {
+	Cs0,
+	Cs1,
+	Cs2,
+	Cs3,
+}
+
+impl ChipSelect {
+	/// get chip select number
+	pub fn nr(&self) -> usize {
+		match self {
+			ChipSelect::Cs0 => 0,
+			ChipSelect::Cs1 => 1,
+			ChipSelect::Cs2 => 2,
+			ChipSelect::Cs3 => 3,
+		}
+	}
+
+	/// Value of the PCS field selecting this line without decoder
+	fn pcs(&self) -> u8 {
+		!(1 << self.nr()) & 0xf
+	}
+
+	/// Lowest chip select line in the bit mask `chip_selects`
+	fn first(chip_selects: u8) -> ChipSelect {
+		match chip_selects.trailing_zeros() {
+			1 => ChipSelect::Cs1,
+			2 => ChipSelect::Cs2,
+			3 => ChipSelect::Cs3,
+			_ => ChipSelect::Cs0,
+		}
+	}
+}
+
+pub trait Pins<SPI> {
+	/// Bit mask of the chip select lines which can be used for transfers
+	const CHIP_SELECTS: u8;
+}
+pub trait PinSck<SPI> {}
+pub trait PinMiso<SPI> {}
+pub trait PinMosi<SPI> {}
+pub trait PinNpcs0<SPI> {}
+pub trait PinNpcs1<SPI> {}
+pub trait PinNpcs2<SPI> {}
+pub trait PinNpcs3<SPI> {}
+pub trait PinNss<SPI> {}
+
+/// Chip select pins driven by the peripheral, a single pin or a tuple of up to four pins
+pub trait NpcsPins<SPI> {
+	/// Bit mask of the chip select lines muxed out
+	const CHIP_SELECTS: u8;
+}
+
+impl<SPI, A: NpcsPins<SPI>, B: NpcsPins<SPI>> NpcsPins<SPI> for (A, B) {
+	const CHIP_SELECTS: u8 = A::CHIP_SELECTS | B::CHIP_SELECTS;
+}
+
+impl<SPI, A: NpcsPins<SPI>, B: NpcsPins<SPI>, C: NpcsPins<SPI>> NpcsPins<SPI> for (A, B, C) {
+	const CHIP_SELECTS: u8 = A::CHIP_SELECTS | B::CHIP_SELECTS | C::CHIP_SELECTS;
+}
+
+impl<SPI, A: NpcsPins<SPI>, B: NpcsPins<SPI>, C: NpcsPins<SPI>, D: NpcsPins<SPI>> NpcsPins<SPI> for (A, B, C, D) {
+	const CHIP_SELECTS: u8 = A::CHIP_SELECTS | B::CHIP_SELECTS | C::CHIP_SELECTS | D::CHIP_SELECTS;
+}
+
+/// Chip select lines are driven by software, e.g. through gpio pins
+impl<SPI, SCK, MISO, MOSI> Pins<SPI> for (SCK, MISO, MOSI)
+where
+	SCK: PinSck<SPI>,
+	MISO: PinMiso<SPI>,
+	MOSI: PinMosi<SPI>,
+{
+	const CHIP_SELECTS: u8 = 0xf;
+}
+
+/// Chip select lines driven by the peripheral, only these can be selected
+impl<SPI, SCK, MISO, MOSI, NPCS> Pins<SPI> for (SCK, MISO, MOSI, NPCS)
+where
+	SCK: PinSck<SPI>,
+	MISO: PinMiso<SPI>,
+	MOSI: PinMosi<SPI>,
+	NPCS: NpcsPins<SPI>,
+{
+	const CHIP_SELECTS: u8 = NPCS::CHIP_SELECTS;
+}
+
+/// Pins used in slave mode, NSS is the chip select driven by the master
+pub trait SlavePins<SPI> {}
+
+impl<SPI, SCK, MISO, MOSI, NSS> SlavePins<SPI> for (SCK, MISO, MOSI, NSS)
+where
+	SCK: PinSck<SPI>,
+	MISO: PinMiso<SPI>,
+	MOSI: PinMosi<SPI>,
+	NSS: PinNss<SPI>,
+{
+}
+
+/// A filler type for when the Miso pin is unnecessary
+pub struct NoMiso;
+/// A filler type for when the Mosi pin is unnecessary
+pub struct NoMosi;
+
+macro_rules! spi_pins {
+	($($SPIX:ty: SCK: [$($SCK:ty),*] MISO: [$($MISO:ty),*] MOSI: [$($MOSI:ty),*]
+		NPCS0: [$($NPCS0:ty),*] NPCS1: [$($NPCS1:ty),*] NPCS2: [$($NPCS2:ty),*] NPCS3: [$($NPCS3:ty),*]
+		NSS: [$($NSS:ty),*])+) => {
+		$(
+			$(
+				impl PinSck<$SPIX> for $SCK {}
+			)*
+			$(
+				impl PinMiso<$SPIX> for $MISO {}
+			)*
+			$(
+				impl PinMosi<$SPIX> for $MOSI {}
+			)*
+			$(
+				impl PinNpcs0<$SPIX> for $NPCS0 {}
+				impl NpcsPins<$SPIX> for $NPCS0 { const CHIP_SELECTS: u8 = 1 << 0; }
+			)*
+			$(
+				impl PinNpcs1<$SPIX> for $NPCS1 {}
+				impl NpcsPins<$SPIX> for $NPCS1 { const CHIP_SELECTS: u8 = 1 << 1; }
+			)*
+			$(
+				impl PinNpcs2<$SPIX> for $NPCS2 {}
+				impl NpcsPins<$SPIX> for $NPCS2 { const CHIP_SELECTS: u8 = 1 << 2; }
+			)*
+			$(
+				impl PinNpcs3<$SPIX> for $NPCS3 {}
+				impl NpcsPins<$SPIX> for $NPCS3 { const CHIP_SELECTS: u8 = 1 << 3; }
+			)*
+			$(
+				impl PinNss<$SPIX> for $NSS {}
+			)*
+		)+
+	}
+}
+
+spi_pins! {
+	SPI0:
+		SCK : [
+			PD22<PeripheralCntr<PeriphB>>
+		]
+		MISO : [
+			PD20<PeripheralCntr<PeriphB>>,
+			NoMiso
+		]
+		MOSI : [
+			PD21<PeripheralCntr<PeriphB>>,
+			NoMosi
+		]
+		NPCS0 : [
+			PB2<PeripheralCntr<PeriphD>>
+		]
+		NPCS1 : [
+			PA31<PeripheralCntr<PeriphA>>,
+			PD25<PeripheralCntr<PeriphB>>
+		]
+		NPCS2 : [
+			PD12<PeripheralCntr<PeriphC>>
+		]
+		NPCS3 : [
+			PD27<PeripheralCntr<PeriphB>>
+		]
+		NSS : [
+			PB2<PeripheralCntr<PeriphD>>
+		]
+	SPI1:
+		SCK : [
+			PC24<PeripheralCntr<PeriphC>>
+		]
+		MISO : [
+			PC26<PeripheralCntr<PeriphC>>,
+			NoMiso
+		]
+		MOSI : [
+			PC27<PeripheralCntr<PeriphC>>,
+			NoMosi
+		]
+		NPCS0 : [
+			PC25<PeripheralCntr<PeriphC>>
+		]
+		NPCS1 : [
+			PC28<PeripheralCntr<PeriphC>>,
+			PD0<PeripheralCntr<PeriphC>>
+		]
+		NPCS2 : [
+			PC29<PeripheralCntr<PeriphC>>,
+			PD1<PeripheralCntr<PeriphC>>
+		]
+		NPCS3 : [
+			PC30<PeripheralCntr<PeriphC>>,
+			PD2<PeripheralCntr<PeriphC>>
+		]
+		NSS : [
+			PC25<PeripheralCntr<PeriphC>>
+		]
+}
+
+/// SPI abstraction in master mode
+pub struct Spi<SPI, PINS> {
+	spi: SPI,
+	pins: PINS,
+	clk: Hertz,
+}
+
+// Bits per transfer are encoded as an offset to 8
+fn calc_bits_val(bits: u8) -> Result<u8, config::InvalidConfig> {
+	if bits < 8 || bits > 16 {
+		return Err(config::InvalidConfig);
+	}
+	Ok(bits - 8)
+}
+
+// The serial clock is derived from the peripheral clock by a divider between 1 and 255
+fn calc_scbr_val(clk: Hertz, baudrate: Hertz) -> Result<u8, config::InvalidConfig> {
+	if baudrate.0 == 0 {
+		return Err(config::InvalidConfig);
+	}
+	// round up so the requested baudrate is never exceeded
+	let scbr = (clk.0 + baudrate.0 - 1) / baudrate.0;
+	if scbr == 0 || scbr > 255 {
+		return Err(config::InvalidConfig);
+	}
+	Ok(scbr as u8)
+}
+
+// DLYBS counts peripheral clock cycles, DLYBCT counts multiples of 32 peripheral clock cycles
+fn calc_delay_val(clk: Hertz, delay: NanoSeconds, unit: u32) -> Result<u8, config::InvalidConfig> {
+	if delay.0 == 0 {
+		return Ok(0);
+	}
+	let cycle_duration: PicoSeconds = clk.into();
+	let cycles = cycle_duration.cycles(delay.into());
+	let v = (cycles + unit - 1) / unit;
+	if v > 255 {
+		return Err(config::InvalidConfig);
+	}
+	Ok(v as u8)
+}
+
+macro_rules! spi_hal {
+	($( $SPIX:ident: (
+			$spiX:ident,
+			$en_reg:ident,
+			$perid:ident
+		),
+	)+) => {
+		$(
+			impl<PINS: Pins<$SPIX>> Spi<$SPIX, PINS> {
+				/// Configures a SPI peripheral as master with fixed peripheral select
+				///
+				/// The lowest chip select line of `pins` is selected.
+				pub fn $spiX(
+					spi: $SPIX,
+					pins: PINS,
+					clocks: &Clocks,
+					pmc: &mut PMC,
+				) -> Self {
+					//enable peripheral clock in pmc
+					pmc.$en_reg.write(|w| w.$perid().set_bit() );
+
+					//reset peripheral
+					spi.spi_cr.write(|w| w.spidis().set_bit());
+					spi.spi_cr.write(|w| w.swrst().set_bit());
+
+					//master mode, fixed peripheral, no mode fault detection since NSS is not used
+					spi.spi_mr.write(|w| unsafe {
+						w.mstr().set_bit();
+						w.ps().clear_bit();
+						w.pcsdec().clear_bit();
+						w.modfdis().set_bit();
+						w.pcs().bits(ChipSelect::first(PINS::CHIP_SELECTS).pcs())
+					});
+
+					spi.spi_cr.write(|w| w.spien().set_bit());
+
+					Spi { spi, pins, clk: clocks.mck() }
+				}
+
+				/// Applies `config` to the chip select line `cs`, which has to be part of the pins
+				pub fn configure_cs(
+					&mut self,
+					cs: ChipSelect,
+					config: config::ChipSelectConfig,
+				) -> Result<(), config::InvalidConfig> {
+					Self::check_cs(cs)?;
+					let bits = calc_bits_val(config.bits)?;
+					let scbr = calc_scbr_val(self.clk, config.baudrate)?;
+					let dlybs = calc_delay_val(self.clk, config.delay_before_spck, 1)?;
+					let dlybct = calc_delay_val(self.clk, config.delay_between_transfers, 32)?;
+
+					self.spi.spi_csr[cs.nr()].write(|w| unsafe {
+						match config.mode.polarity {
+							Polarity::IdleLow => w.cpol().clear_bit(),
+							Polarity::IdleHigh => w.cpol().set_bit(),
+						};
+						// NCPHA is the inverted clock phase
+						match config.mode.phase {
+							Phase::CaptureOnFirstTransition => w.ncpha().set_bit(),
+							Phase::CaptureOnSecondTransition => w.ncpha().clear_bit(),
+						};
+						if config.keep_active {
+							w.csaat().set_bit();
+						} else {
+							w.csaat().clear_bit();
+						}
+						w.bits().bits(bits);
+						w.scbr().bits(scbr);
+						w.dlybs().bits(dlybs);
+						w.dlybct().bits(dlybct)
+					});
+
+					Ok(())
+				}
+
+				/// Selects the chip select line used for the following transfers
+				///
+				/// Fails if the line is not part of the pins.
+				pub fn select(&mut self, cs: ChipSelect) -> Result<(), config::InvalidConfig> {
+					Self::check_cs(cs)?;
+					// wait for the last transfer to leave the shift register
+					while self.spi.spi_sr.read().txempty().bit_is_clear() {}
+					self.spi.spi_mr.modify(|_, w| unsafe { w.pcs().bits(cs.pcs()) });
+					Ok(())
+				}
+
+				fn check_cs(cs: ChipSelect) -> Result<(), config::InvalidConfig> {
+					if PINS::CHIP_SELECTS & 1 << cs.nr() == 0 {
+						return Err(config::InvalidConfig);
+					}
+					Ok(())
+				}
+
+				/// Releases a chip select kept active with `ChipSelectConfig::keep_active`
+				pub fn deselect(&mut self) {
+					while self.spi.spi_sr.read().txempty().bit_is_clear() {}
+					self.spi.spi_cr.write(|w| w.lastxfer().set_bit());
+				}
+
+				/// Releases the SPI peripheral and associated pins
+				pub fn release(self) -> ($SPIX, PINS) {
+					self.spi.spi_cr.write(|w| w.spidis().set_bit());
+					(self.spi, self.pins)
+				}
+			}
+
+			spi_hal!(@word $SPIX, u8);
+			spi_hal!(@word $SPIX, u16);
+		)+
+	};
+	(@word $SPIX:ident, $W:ty) => {
+		impl<PINS> spi::FullDuplex<$W> for Spi<$SPIX, PINS> {
+			type Error = Error;
+
+			fn read(&mut self) -> nb::Result<$W, Error> {
+				let sr = self.spi.spi_sr.read();
+
+				Err(if sr.ovres().bit_is_set() {
+					nb::Error::Other(Error::Overrun)
+				} else if sr.modf().bit_is_set() {
+					nb::Error::Other(Error::ModeFault)
+				} else if sr.rdrf().bit_is_set() {
+					// NOTE(read_volatile) only the data part of the register is read
+					return Ok(unsafe { ptr::read_volatile(&self.spi.spi_rdr as *const _ as *const $W) });
+				} else {
+					nb::Error::WouldBlock
+				})
+			}
+
+			fn send(&mut self, word: $W) -> nb::Result<(), Error> {
+				let sr = self.spi.spi_sr.read();
+
+				Err(if sr.ovres().bit_is_set() {
+					nb::Error::Other(Error::Overrun)
+				} else if sr.modf().bit_is_set() {
+					nb::Error::Other(Error::ModeFault)
+				} else if sr.tdre().bit_is_set() {
+					self.spi.spi_tdr.write(|w| unsafe { w.td().bits(word as u16) });
+					return Ok(());
+				} else {
+					nb::Error::WouldBlock
+				})
+			}
+		}
+
+		impl<PINS> embedded_hal::blocking::spi::transfer::Default<$W> for Spi<$SPIX, PINS> {}
+
+		impl<PINS> embedded_hal::blocking::spi::write::Default<$W> for Spi<$SPIX, PINS> {}
+	};
+}
+
+spi_hal! {
+	SPI0 : (spi0, pmc_pcer0, pid21),
+	SPI1 : (spi1, pmc_pcer1, pid42),
+}
+
+/// SPI abstraction in slave mode
+pub struct SpiSlave<SPI, PINS> {
+	spi: SPI,
+	pins: PINS,
+}
+
+/// SPI slave receiving into a ring buffer through DMA
+pub struct SpiSlaveDma<SPI, PINS, CH: ChannelId, BUF: WriteBuffer> {
+	slave: SpiSlave<SPI, PINS>,
+	transfer: CircTransfer<CH, BUF>,
+	/// NSS rising edge read together with an error, reported by the next `end_of_frame`
+	nss_released: bool,
+	/// Underrun read together with an overrun, reported by the next `end_of_frame`
+	underrun: bool,
+}
+
+macro_rules! spi_slave {
+	($( $SPIX:ident: (
+			$spiX_slave:ident,
+			$en_reg:ident,
+			$perid:ident,
+			$dma_tx:ident,
+			$dma_rx:ident
+		),
+	)+) => {
+		$(
+			impl<PINS> SpiSlave<$SPIX, PINS> {
+				/// Configures a SPI peripheral as slave
+				pub fn $spiX_slave(
+					spi: $SPIX,
+					pins: PINS,
+					config: config::SlaveConfig,
+					pmc: &mut PMC,
+				) -> Result<Self, config::InvalidConfig>
+				where
+					PINS: SlavePins<$SPIX>,
+				{
+					let bits = calc_bits_val(config.bits)?;
+
+					//enable peripheral clock in pmc
+					pmc.$en_reg.write(|w| w.$perid().set_bit() );
+
+					//reset peripheral
+					spi.spi_cr.write(|w| w.spidis().set_bit());
+					spi.spi_cr.write(|w| w.swrst().set_bit());
+
+					spi.spi_mr.write(|w| w.mstr().clear_bit());
+
+					//in slave mode only the settings of chip select 0 are used
+					spi.spi_csr[0].write(|w| unsafe {
+						match config.mode.polarity {
+							Polarity::IdleLow => w.cpol().clear_bit(),
+							Polarity::IdleHigh => w.cpol().set_bit(),
+						};
+						match config.mode.phase {
+							Phase::CaptureOnFirstTransition => w.ncpha().set_bit(),
+							Phase::CaptureOnSecondTransition => w.ncpha().clear_bit(),
+						};
+						w.bits().bits(bits)
+					});
+
+					spi.spi_cr.write(|w| w.spien().set_bit());
+
+					Ok(SpiSlave { spi, pins })
+				}
+
+				/// Starts receiving into the ring buffer `buffer` using the DMA `channel`
+				pub fn into_dma<CH, B>(
+					self,
+					buffer: B,
+					descriptor: &'static mut DescriptorView0,
+					channel: Channel<CH>,
+				) -> SpiSlaveDma<$SPIX, PINS, CH, B>
+				where
+					CH: ChannelId,
+					B: WriteBuffer,
+				{
+					// drop stale data and flags from before the transfer was set up
+					let _ = self.spi.spi_rdr.read().bits();
+					let _ = self.spi.spi_sr.read().bits();
+
+					let rdr = &self.spi.spi_rdr as *const _ as u32;
+					let transfer = channel.circular_from_peripheral(DmaPeripheral::$dma_rx, rdr, buffer, descriptor);
+
+					SpiSlaveDma { slave: self, transfer, nss_released: false, underrun: false }
+				}
+
+				/// Releases the SPI peripheral and associated pins
+				pub fn release(self) -> ($SPIX, PINS) {
+					self.spi.spi_cr.write(|w| w.spidis().set_bit());
+					(self.spi, self.pins)
+				}
+			}
+
+			impl<PINS, CH, BUF> SpiSlaveDma<$SPIX, PINS, CH, BUF>
+			where
+				CH: ChannelId,
+				BUF: WriteBuffer,
+			{
+				/// Number of received words that have not been read yet
+				pub fn available(&self) -> usize {
+					self.transfer.available()
+				}
+
+				/// Copies received words into `buffer` and returns the number of words copied
+				pub fn read(&mut self, buffer: &mut [BUF::Word]) -> usize {
+					self.transfer.read(buffer)
+				}
+
+				/// Returns true if the master released NSS since the last call, which marks the end of a frame
+				///
+				/// Overrun and underrun are reported first, the flags are cleared by this call. An
+				/// underrun or end of frame read together with an overrun is kept and returned by the
+				/// next calls.
+				pub fn end_of_frame(&mut self) -> Result<bool, Error> {
+					let sr = self.slave.spi.spi_sr.read();
+					self.nss_released |= sr.nssr().bit_is_set();
+					self.underrun |= sr.undes().bit_is_set();
+
+					if sr.ovres().bit_is_set() {
+						Err(Error::Overrun)
+					} else if core::mem::replace(&mut self.underrun, false) {
+						Err(Error::Underrun)
+					} else {
+						Ok(core::mem::replace(&mut self.nss_released, false))
+					}
+				}
+
+				/// Pre-loads the data sent to the master during the next transaction
+				///
+				/// Once the buffer has been sent the last word is repeated and an underrun is reported.
+				pub fn load_response<TXCH, B>(&mut self, buffer: B, channel: Channel<TXCH>) -> Transfer<TXCH, B>
+				where
+					TXCH: ChannelId,
+					B: ReadBuffer,
+				{
+					let tdr = &self.slave.spi.spi_tdr as *const _ as u32;
+					channel.write_to_peripheral(DmaPeripheral::$dma_tx, tdr, buffer)
+				}
+
+				/// Stops the reception and releases all resources
+				pub fn stop(self) -> (SpiSlave<$SPIX, PINS>, BUF, &'static mut DescriptorView0, Channel<CH>) {
+					let (buffer, descriptor, channel) = self.transfer.stop();
+					(self.slave, buffer, descriptor, channel)
+				}
+			}
+		)+
+	}
+}
+
+spi_slave! {
+	SPI0 : (spi0_slave, pmc_pcer0, pid21, Spi0Tx, Spi0Rx),
+	SPI1 : (spi1_slave, pmc_pcer1, pid42, Spi1Tx, Spi1Rx),
+}
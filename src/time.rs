@@ -177,6 +177,21 @@ impl Into<PicoSeconds> for MicroSeconds {
 	}
 }
 
+impl Into<MicroSeconds> for MilliSeconds {
+	fn into(self) -> MicroSeconds{
+		let period = self.0;
+		MicroSeconds(period * 1_000)
+	}
+}
+
+impl Into<MicroSeconds> for Hertz {
+	fn into(self) -> MicroSeconds{
+		let freq = self.0;
+		assert!(freq != 0 && freq <= 1_000_000);
+		MicroSeconds(1_000_000 / freq)
+	}
+}
+
 
 impl PicoSeconds {
 	pub fn cycles(self, time:PicoSeconds) -> u32{
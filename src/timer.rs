@@ -0,0 +1,584 @@
+//! Timer Counter (TC)
+//!
+//! Every TC instance has three independent 16 bit channels. A channel in waveform mode counts up
+//! to RC and restarts, which is used to implement `CountDown` and `Periodic`. In capture mode the
+//! counter value is latched into RA and RB on edges of TIOA to measure external signals.
+
+use core::marker::PhantomData;
+
+use embedded_hal::timer::{CountDown, Periodic};
+use void::Void;
+
+use crate::target_device::{TC0, TC1, TC2, TC3};
+use crate::target_device::PMC;
+use crate::target_device::tc0::{RegisterBlock, TC_CHANNEL};
+
+use crate::gpio::{PeripheralCntr, PeriphB, PeriphC};
+use crate::gpio::pioa::{PA0, PA1, PA15, PA16, PA26, PA27};
+use crate::gpio::pioc::{PC5, PC6, PC8, PC9, PC11, PC12, PC23, PC24, PC26, PC27, PC29, PC30};
+use crate::gpio::piod::{PD21, PD22};
+use crate::gpio::pioe::{PE0, PE1, PE3, PE4};
+use crate::clock_gen::Clocks;
+use crate::time::{Hertz, MicroSeconds};
+use crate::dma::{Channel, ChannelId as DmaChannelId, DmaPeripheral, Transfer, WriteBuffer};
+
+// Status flags of the channel status register
+const COVFS: u32 = 1 << 0;
+const LOVRS: u32 = 1 << 1;
+const CPCS: u32 = 1 << 4;
+const LDRAS: u32 = 1 << 5;
+const LDRBS: u32 = 1 << 6;
+
+// Channel mode register, waveform mode
+const CMR_WAVE: u32 = 1 << 15;
+const CMR_WAVSEL_UP_RC: u32 = 2 << 13;
+// TIOA is set on RA compare and cleared on RC compare
+const CMR_ACPA_SET: u32 = 1 << 16;
+const CMR_ACPC_CLEAR: u32 = 2 << 18;
+
+// Channel mode register, capture mode
+const CMR_ETRGEDG_SHIFT: u32 = 8;
+const CMR_ABETRG: u32 = 1 << 10;
+const CMR_LDRA_SHIFT: u32 = 16;
+const CMR_LDRB_SHIFT: u32 = 18;
+
+/// Clock source of a timer channel (TCCLKS)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClockSource {
+	/// Programmable clock 6, has to be set up in the PMC by the application
+	Pck6,
+	MckDiv8,
+	MckDiv32,
+	MckDiv128,
+	Slck,
+}
+
+impl ClockSource {
+	fn tcclks(&self) -> u32 {
+		match self {
+			ClockSource::Pck6 => 0,
+			ClockSource::MckDiv8 => 1,
+			ClockSource::MckDiv32 => 2,
+			ClockSource::MckDiv128 => 3,
+			ClockSource::Slck => 4,
+		}
+	}
+}
+
+/// Clock source and RC value for a timer period
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ClockSelection {
+	pub source: ClockSource,
+	pub frequency: Hertz,
+	/// The counter restarts after RC + 1 ticks
+	pub rc: u16,
+}
+
+/// Selects the fastest clock source which can count `period`
+///
+/// Returns `None` if the period is too long or too short for all clock sources.
+pub fn select_clock(period: MicroSeconds, mck: Hertz, slck: Hertz, pck6: Option<Hertz>) -> Option<ClockSelection> {
+	let mut sources = [
+		Some((ClockSource::MckDiv8, Hertz(mck.0 / 8))),
+		Some((ClockSource::MckDiv32, Hertz(mck.0 / 32))),
+		Some((ClockSource::MckDiv128, Hertz(mck.0 / 128))),
+		pck6.map(|f| (ClockSource::Pck6, f)),
+		Some((ClockSource::Slck, slck)),
+	];
+	// highest resolution first
+	sources.sort_unstable_by(|a, b| b.map(|s| (s.1).0).cmp(&a.map(|s| (s.1).0)));
+
+	sources.iter()
+		.filter_map(|s| *s)
+		.find_map(|(source, frequency)| {
+			let ticks = (period.0 as u64 * frequency.0 as u64 + 500_000) / 1_000_000;
+			if ticks >= 1 && ticks <= 0x1_0000 {
+				Some(ClockSelection { source, frequency, rc: (ticks - 1) as u16 })
+			} else {
+				None
+			}
+		})
+}
+
+/// Interrupt event
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+	/// The timer period elapsed (RC compare)
+	Timeout,
+	/// The counter overflowed
+	Overflow,
+	/// RA was loaded in capture mode
+	LoadRa,
+	/// RB was loaded in capture mode
+	LoadRb,
+	/// RA or RB was loaded again before it was read
+	LoadOverrun,
+}
+
+impl Event {
+	fn mask(&self) -> u32 {
+		match self {
+			Event::Timeout => CPCS,
+			Event::Overflow => COVFS,
+			Event::LoadRa => LDRAS,
+			Event::LoadRb => LDRBS,
+			Event::LoadOverrun => LOVRS,
+		}
+	}
+}
+
+/// Timer counter instance
+pub trait Instance {
+	fn ptr() -> *const RegisterBlock;
+
+	/// XDMAC request of the capture registers
+	const DMA: DmaPeripheral;
+}
+
+/// Channel identifier (type state)
+pub trait ChannelId {
+	const NR: usize;
+}
+
+/// Channel identifier (type state)
+pub struct Ch0;
+/// Channel identifier (type state)
+pub struct Ch1;
+/// Channel identifier (type state)
+pub struct Ch2;
+
+impl ChannelId for Ch0 {
+	const NR: usize = 0;
+}
+
+impl ChannelId for Ch1 {
+	const NR: usize = 1;
+}
+
+impl ChannelId for Ch2 {
+	const NR: usize = 2;
+}
+
+/// Owned handle of a single timer channel
+pub struct Timer<TC, CH> {
+	mck: Hertz,
+	slck: Hertz,
+	pck6: Option<Hertz>,
+	trigger_output: bool,
+	_tc: PhantomData<(TC, CH)>,
+}
+
+impl<TC: Instance, CH: ChannelId> Timer<TC, CH> {
+	fn regs() -> &'static TC_CHANNEL {
+		// NOTE(unsafe) every channel only accesses its own register block
+		unsafe { &(*TC::ptr()).tc_channel[CH::NR] }
+	}
+
+	/// Adds PCK6 with frequency `freq` to the clock sources considered by `start`
+	///
+	/// PCK6 is not configured by `clock_gen`, it has to be set up by the application.
+	pub fn use_pck6(&mut self, freq: Hertz) {
+		self.pck6 = Some(freq);
+	}
+
+	/// Generates a pulse on the internal TIOA every period, used to trigger ADC conversions
+	///
+	/// Takes effect with the next call of `start`.
+	pub fn trigger_output(&mut self, enable: bool) {
+		self.trigger_output = enable;
+	}
+
+	/// Starts an interrupt event
+	pub fn listen(&mut self, event: Event) {
+		Self::regs().tc_ier.write(|w| unsafe { w.bits(event.mask()) });
+	}
+
+	/// Stops an interrupt event
+	pub fn unlisten(&mut self, event: Event) {
+		Self::regs().tc_idr.write(|w| unsafe { w.bits(event.mask()) });
+	}
+
+	/// Clears all pending events
+	pub fn clear_interrupt(&mut self) {
+		let _ = Self::regs().tc_sr.read().bits();
+	}
+
+	/// Current counter value
+	pub fn counter(&self) -> u16 {
+		Self::regs().tc_cv.read().bits() as u16
+	}
+
+	/// Stops the counter
+	pub fn stop(&mut self) {
+		Self::regs().tc_ccr.write(|w| w.clkdis().set_bit());
+	}
+}
+
+impl<TC: Instance, CH: ChannelId> CountDown for Timer<TC, CH> {
+	type Time = MicroSeconds;
+
+	/// Starts the timer, panics if no clock source can count `timeout`
+	fn start<T>(&mut self, timeout: T)
+	where
+		T: Into<MicroSeconds>,
+	{
+		let selection = select_clock(timeout.into(), self.mck, self.slck, self.pck6)
+			.expect("timer period out of range");
+		let ch = Self::regs();
+
+		let mut cmr = selection.source.tcclks() | CMR_WAVE | CMR_WAVSEL_UP_RC;
+		if self.trigger_output {
+			cmr |= CMR_ACPA_SET | CMR_ACPC_CLEAR;
+		}
+
+		ch.tc_ccr.write(|w| w.clkdis().set_bit());
+		ch.tc_cmr.write(|w| unsafe { w.bits(cmr) });
+		ch.tc_ra.write(|w| unsafe { w.bits(selection.rc as u32 / 2) });
+		ch.tc_rc.write(|w| unsafe { w.bits(selection.rc as u32) });
+
+		// clear pending status flags
+		let _ = ch.tc_sr.read().bits();
+
+		ch.tc_ccr.write(|w| {
+			w.clken().set_bit();
+			w.swtrg().set_bit()
+		});
+	}
+
+	fn wait(&mut self) -> nb::Result<(), Void> {
+		if Self::regs().tc_sr.read().bits() & CPCS == 0 {
+			Err(nb::Error::WouldBlock)
+		} else {
+			Ok(())
+		}
+	}
+}
+
+impl<TC: Instance, CH: ChannelId> Periodic for Timer<TC, CH> {}
+
+pub trait PinTioa<TC, CH> {}
+pub trait PinTiob<TC, CH> {}
+
+macro_rules! timer_pins {
+	($($TCX:ty, $CH:ty: TIOA: [$($TIOA:ty),*] TIOB: [$($TIOB:ty),*])+) => {
+		$(
+			$(
+				impl PinTioa<$TCX, $CH> for $TIOA {}
+			)*
+			$(
+				impl PinTiob<$TCX, $CH> for $TIOB {}
+			)*
+		)+
+	}
+}
+
+timer_pins! {
+	TC0, Ch0: TIOA: [ PA0<PeripheralCntr<PeriphB>> ] TIOB: [ PA1<PeripheralCntr<PeriphB>> ]
+	TC0, Ch1: TIOA: [ PA15<PeripheralCntr<PeriphB>> ] TIOB: [ PA16<PeripheralCntr<PeriphB>> ]
+	TC0, Ch2: TIOA: [ PA26<PeripheralCntr<PeriphB>> ] TIOB: [ PA27<PeripheralCntr<PeriphB>> ]
+	TC1, Ch0: TIOA: [ PC23<PeripheralCntr<PeriphB>> ] TIOB: [ PC24<PeripheralCntr<PeriphB>> ]
+	TC1, Ch1: TIOA: [ PC26<PeripheralCntr<PeriphB>> ] TIOB: [ PC27<PeripheralCntr<PeriphB>> ]
+	TC1, Ch2: TIOA: [ PC29<PeripheralCntr<PeriphB>> ] TIOB: [ PC30<PeripheralCntr<PeriphB>> ]
+	TC2, Ch0: TIOA: [ PC5<PeripheralCntr<PeriphB>> ] TIOB: [ PC6<PeripheralCntr<PeriphB>> ]
+	TC2, Ch1: TIOA: [ PC8<PeripheralCntr<PeriphB>> ] TIOB: [ PC9<PeripheralCntr<PeriphB>> ]
+	TC2, Ch2: TIOA: [ PC11<PeripheralCntr<PeriphB>> ] TIOB: [ PC12<PeripheralCntr<PeriphB>> ]
+	TC3, Ch0: TIOA: [ PE0<PeripheralCntr<PeriphB>> ] TIOB: [ PE1<PeripheralCntr<PeriphB>> ]
+	TC3, Ch1: TIOA: [ PE3<PeripheralCntr<PeriphB>> ] TIOB: [ PE4<PeripheralCntr<PeriphB>> ]
+	TC3, Ch2: TIOA: [ PD21<PeripheralCntr<PeriphC>> ] TIOB: [ PD22<PeripheralCntr<PeriphC>> ]
+}
+
+/// Signal edge
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Edge {
+	None,
+	Rising,
+	Falling,
+	Both,
+}
+
+impl Edge {
+	fn bits(&self) -> u32 {
+		match self {
+			Edge::None => 0,
+			Edge::Rising => 1,
+			Edge::Falling => 2,
+			Edge::Both => 3,
+		}
+	}
+}
+
+/// External trigger resetting the counter in capture mode
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Trigger {
+	None,
+	Tioa(Edge),
+	Tiob(Edge),
+}
+
+/// Capture error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+	/// RA or RB was loaded again before it was read
+	Overrun,
+	#[doc(hidden)]
+	_Extensible,
+}
+
+pub mod config {
+	use super::{Edge, Trigger};
+	use crate::time::Hertz;
+
+	/// Capture mode configuration
+	///
+	/// RA and RB are loaded on edges of TIOA. The default measures period and high time: the
+	/// counter is reset on a falling edge, RA is loaded on the rising and RB on the next falling
+	/// edge.
+	pub struct CaptureConfig {
+		pub min_frequency: Hertz,
+		pub ra: Edge,
+		pub rb: Edge,
+		pub trigger: Trigger,
+	}
+
+	impl CaptureConfig {
+		/// Lowest signal frequency to measure, selects the fastest clock which does not overflow
+		pub fn min_frequency(mut self, freq: Hertz) -> Self {
+			self.min_frequency = freq;
+			self
+		}
+
+		pub fn ra_edge(mut self, edge: Edge) -> Self {
+			self.ra = edge;
+			self
+		}
+
+		pub fn rb_edge(mut self, edge: Edge) -> Self {
+			self.rb = edge;
+			self
+		}
+
+		pub fn trigger(mut self, trigger: Trigger) -> Self {
+			self.trigger = trigger;
+			self
+		}
+	}
+
+	impl Default for CaptureConfig {
+		fn default() -> CaptureConfig {
+			CaptureConfig {
+				min_frequency: Hertz(100),
+				ra: Edge::Rising,
+				rb: Edge::Falling,
+				trigger: Trigger::Tioa(Edge::Falling),
+			}
+		}
+	}
+
+	#[derive(Debug)]
+	pub struct InvalidConfig;
+}
+
+/// RA and RB of one capture cycle
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Measurement {
+	pub ra: u16,
+	pub rb: u16,
+	/// Counter clock frequency
+	pub clock: Hertz,
+}
+
+impl Measurement {
+	fn ticks_to_us(&self, ticks: u16) -> MicroSeconds {
+		MicroSeconds((ticks as u64 * 1_000_000 / self.clock.0 as u64) as u32)
+	}
+
+	/// Signal period, RB ticks with the default configuration
+	pub fn period(&self) -> MicroSeconds {
+		self.ticks_to_us(self.rb)
+	}
+
+	/// Signal frequency, derived from RB with the default configuration
+	pub fn frequency(&self) -> Hertz {
+		if self.rb == 0 {
+			return Hertz(0);
+		}
+		Hertz(self.clock.0 / self.rb as u32)
+	}
+
+	/// Time the signal was high, RB - RA ticks with the default configuration
+	pub fn high_time(&self) -> MicroSeconds {
+		self.ticks_to_us(self.rb.saturating_sub(self.ra))
+	}
+
+	/// Ratio of the high time to the period with the default configuration
+	pub fn duty_cycle(&self) -> f32 {
+		if self.rb == 0 {
+			return 0.0;
+		}
+		self.rb.saturating_sub(self.ra) as f32 / self.rb as f32
+	}
+}
+
+/// Timer channel in capture mode
+pub struct Capture<TC, CH, PIN> {
+	timer: Timer<TC, CH>,
+	pin: PIN,
+	clock: Hertz,
+}
+
+impl<TC: Instance, CH: ChannelId> Timer<TC, CH> {
+	/// Switches the channel to capture mode measuring the signal on `pin`
+	pub fn into_capture<PIN>(self, pin: PIN, config: config::CaptureConfig) -> Result<Capture<TC, CH, PIN>, config::InvalidConfig>
+	where
+		PIN: PinTioa<TC, CH>,
+	{
+		if config.min_frequency.0 == 0 || config.min_frequency.0 > 1_000_000 {
+			return Err(config::InvalidConfig);
+		}
+		let selection = select_clock(config.min_frequency.into(), self.mck, self.slck, self.pck6)
+			.ok_or(config::InvalidConfig)?;
+
+		let trigger = match config.trigger {
+			Trigger::None => 0,
+			Trigger::Tioa(edge) => edge.bits() << CMR_ETRGEDG_SHIFT | CMR_ABETRG,
+			Trigger::Tiob(edge) => edge.bits() << CMR_ETRGEDG_SHIFT,
+		};
+
+		let ch = Self::regs();
+		ch.tc_ccr.write(|w| w.clkdis().set_bit());
+		ch.tc_cmr.write(|w| unsafe {
+			w.bits(selection.source.tcclks()
+				| trigger
+				| config.ra.bits() << CMR_LDRA_SHIFT
+				| config.rb.bits() << CMR_LDRB_SHIFT)
+		});
+
+		// clear pending status flags
+		let _ = ch.tc_sr.read().bits();
+
+		ch.tc_ccr.write(|w| {
+			w.clken().set_bit();
+			w.swtrg().set_bit()
+		});
+
+		Ok(Capture { timer: self, pin, clock: selection.frequency })
+	}
+}
+
+impl<TC: Instance, CH: ChannelId, PIN> Capture<TC, CH, PIN> {
+	/// Counter clock frequency
+	pub fn clock(&self) -> Hertz {
+		self.clock
+	}
+
+	/// Returns RA and RB once RB was loaded
+	pub fn read(&mut self) -> nb::Result<Measurement, Error> {
+		let ch = Timer::<TC, CH>::regs();
+		let sr = ch.tc_sr.read().bits();
+
+		if sr & LOVRS != 0 {
+			// drop the stale values
+			let _ = ch.tc_ra.read().bits();
+			let _ = ch.tc_rb.read().bits();
+			return Err(nb::Error::Other(Error::Overrun));
+		}
+		if sr & LDRBS == 0 {
+			return Err(nb::Error::WouldBlock);
+		}
+
+		Ok(Measurement {
+			ra: ch.tc_ra.read().bits() as u16,
+			rb: ch.tc_rb.read().bits() as u16,
+			clock: self.clock,
+		})
+	}
+
+	pub fn listen(&mut self, event: Event) {
+		self.timer.listen(event);
+	}
+
+	pub fn unlisten(&mut self, event: Event) {
+		self.timer.unlisten(event);
+	}
+
+	/// Stops the counter and releases the channel and pin
+	pub fn release(mut self) -> (Timer<TC, CH>, PIN) {
+		self.timer.stop();
+		(self.timer, self.pin)
+	}
+}
+
+impl<TC: Instance, PIN> Capture<TC, Ch0, PIN> {
+	/// Loads the captured RA and RB values alternately into `buffer`
+	///
+	/// Only channel 0 of a TC has a DMA request.
+	pub fn read_dma<B, D>(&mut self, buffer: B, channel: Channel<D>) -> Transfer<D, B>
+	where
+		B: WriteBuffer<Word = u32>,
+		D: DmaChannelId,
+	{
+		let rab = &Timer::<TC, Ch0>::regs().tc_rab as *const _ as u32;
+		channel.read_from_peripheral(TC::DMA, rab, buffer)
+	}
+}
+
+/// Timer channels of a TC instance
+pub struct Channels<TC> {
+	pub ch0: Timer<TC, Ch0>,
+	pub ch1: Timer<TC, Ch1>,
+	pub ch2: Timer<TC, Ch2>,
+}
+
+/// Extension trait to split a TC into its channels
+pub trait TimerExt {
+	type Channels;
+
+	/// Enables the clocks of all channels and splits the TC into independent channels
+	fn split(self, clocks: &Clocks, pmc: &mut PMC) -> Self::Channels;
+}
+
+macro_rules! timer {
+	($($TCX:ident: ($en_reg:ident, [$($perid:ident),+], $dma:ident),)+) => {
+		$(
+			impl Instance for $TCX {
+				fn ptr() -> *const RegisterBlock {
+					$TCX::ptr()
+				}
+
+				const DMA: DmaPeripheral = DmaPeripheral::$dma;
+			}
+
+			impl TimerExt for $TCX {
+				type Channels = Channels<$TCX>;
+
+				fn split(self, clocks: &Clocks, pmc: &mut PMC) -> Channels<$TCX> {
+					//enable peripheral clocks in pmc
+					pmc.$en_reg.write(|w| {
+						$(
+							w.$perid().set_bit();
+						)+
+						w
+					});
+
+					for ch in self.tc_channel.iter() {
+						ch.tc_ccr.write(|w| w.clkdis().set_bit());
+						ch.tc_idr.write(|w| unsafe { w.bits(0xffff_ffff) });
+					}
+
+					Channels {
+						ch0: Timer { mck: clocks.mck(), slck: clocks.slck(), pck6: None, trigger_output: false, _tc: PhantomData },
+						ch1: Timer { mck: clocks.mck(), slck: clocks.slck(), pck6: None, trigger_output: false, _tc: PhantomData },
+						ch2: Timer { mck: clocks.mck(), slck: clocks.slck(), pck6: None, trigger_output: false, _tc: PhantomData },
+					}
+				}
+			}
+		)+
+	}
+}
+
+timer! {
+	TC0: (pmc_pcer0, [pid23, pid24, pid25], Tc0),
+	TC1: (pmc_pcer0, [pid26, pid27, pid28], Tc1),
+	TC2: (pmc_pcer1, [pid47, pid48, pid49], Tc2),
+	TC3: (pmc_pcer1, [pid50, pid51, pid52], Tc3),
+}
@@ -0,0 +1,486 @@
+//! USB High-Speed device (USBHS)
+//!
+//! `UsbBus` implements `usb_device::bus::UsbBus`, so the classes of the usb-device ecosystem run
+//! on top of it. The UTMI transceiver is clocked by UPLLCK, which has to be enabled in the
+//! `clock_gen` configuration.
+//!
+//! The USBHS has 10 endpoints. Endpoint 0 is the control endpoint, all other endpoints are either
+//! IN or OUT. Their banks are allocated in the 4 KiB DPRAM in ascending endpoint order when the
+//! device is enabled.
+
+use cortex_m::interrupt::{self, Mutex};
+
+use usb_device::bus::{PollResult, UsbBus as UsbBusTrait, UsbBusAllocator};
+use usb_device::endpoint::{EndpointAddress, EndpointType};
+use usb_device::{Result, UsbDirection, UsbError};
+
+use crate::target_device::{PMC, USBHS};
+
+use crate::clock_gen::Clocks;
+
+/// Number of endpoints
+pub const ENDPOINTS: usize = 10;
+
+/// Size of the DPRAM holding the endpoint banks
+pub(crate) const DPRAM_SIZE: usize = 4096;
+
+/// FIFO windows of the endpoints, 32 KiB each
+const FIFO_BASE: usize = 0xa010_0000;
+const FIFO_STRIDE: usize = 0x8000;
+
+// PMC USB clock, UPLLCK divided by 10 for the 48 MHz full speed clock
+const PMC_USB_USBS: u32 = 1 << 0;
+const PMC_USB_USBDIV_SHIFT: u32 = 8;
+const PMC_SCER_USBCLK: u32 = 1 << 5;
+
+// General control and status registers
+pub(crate) const CTRL_VBUSHWC: u32 = 1 << 8;
+const CTRL_FRZCLK: u32 = 1 << 14;
+pub(crate) const CTRL_USBE: u32 = 1 << 15;
+const CTRL_UIMOD_DEVICE: u32 = 1 << 25;
+pub(crate) const SR_CLKUSABLE: u32 = 1 << 14;
+
+// Device control register
+const DEVCTRL_UADD_MASK: u32 = 0x7f;
+const DEVCTRL_ADDEN: u32 = 1 << 7;
+const DEVCTRL_DETACH: u32 = 1 << 8;
+const DEVCTRL_SPDCONF_FORCED_FS: u32 = 3 << 10;
+
+// Device interrupt flags
+const DEV_SUSP: u32 = 1 << 0;
+const DEV_EORST: u32 = 1 << 3;
+const DEV_WAKEUP: u32 = 1 << 4;
+const DEV_EORSM: u32 = 1 << 5;
+const DEV_PEP_SHIFT: u32 = 12;
+
+// Endpoint configuration register
+const EPTCFG_ALLOC: u32 = 1 << 1;
+const EPTCFG_EPBK_SHIFT: u32 = 2;
+const EPTCFG_EPSIZE_SHIFT: u32 = 4;
+const EPTCFG_EPDIR_IN: u32 = 1 << 8;
+const EPTCFG_EPTYPE_SHIFT: u32 = 11;
+const EPTCFG_NBTRANS_1: u32 = 1 << 13;
+
+// Endpoint interrupt flags, shared by the status, clear, set, mask, enable and disable registers
+const EPT_TXINI: u32 = 1 << 0;
+const EPT_RXOUTI: u32 = 1 << 1;
+const EPT_RXSTPI: u32 = 1 << 2;
+const EPT_FIFOCON: u32 = 1 << 14;
+const EPT_RSTDT: u32 = 1 << 18;
+const EPT_STALLRQ: u32 = 1 << 19;
+// Endpoint status register
+const EPTISR_CFGOK: u32 = 1 << 18;
+const EPTISR_BYCT_SHIFT: u32 = 20;
+const EPTISR_BYCT_MASK: u32 = 0x7ff;
+
+/// Bus speed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Speed {
+	/// 12 Mbit/s, bulk and interrupt packets have at most 64 bytes
+	FullSpeed,
+	/// 480 Mbit/s if the host supports it, full speed otherwise
+	HighSpeed,
+}
+
+pub mod config {
+	#[derive(Debug)]
+	pub struct InvalidConfig;
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Endpoint {
+	/// EPTYPE field
+	ep_type: u32,
+	/// EPSIZE field, the bank size is `8 << size`
+	size: u32,
+	banks: u32,
+	max_packet_size: u16,
+	in_allocated: bool,
+	out_allocated: bool,
+}
+
+impl Endpoint {
+	const CONTROL: u32 = 0;
+	const ISOCHRONOUS: u32 = 1;
+
+	fn is_control(&self) -> bool {
+		self.ep_type == Self::CONTROL
+	}
+
+	fn cfg(&self) -> u32 {
+		let mut cfg = EPTCFG_ALLOC
+			| (self.banks - 1) << EPTCFG_EPBK_SHIFT
+			| self.size << EPTCFG_EPSIZE_SHIFT
+			| self.ep_type << EPTCFG_EPTYPE_SHIFT;
+		if self.in_allocated && !self.is_control() {
+			cfg |= EPTCFG_EPDIR_IN;
+		}
+		if self.ep_type == Self::ISOCHRONOUS {
+			cfg |= EPTCFG_NBTRANS_1;
+		}
+		cfg
+	}
+
+	/// Memory used in the DPRAM
+	fn memory(&self) -> usize {
+		(8 << self.size) * self.banks as usize
+	}
+}
+
+/// FIFO window of endpoint or pipe `index`
+pub(crate) fn fifo(index: usize) -> *mut u8 {
+	(FIFO_BASE + index * FIFO_STRIDE) as *mut u8
+}
+
+/// Enables the peripheral clock and the 48 MHz USB clock
+pub(crate) fn enable_clocks(pmc: &mut PMC) {
+	//enable peripheral clock in pmc
+	pmc.pmc_pcer1.write(|w| w.pid34().set_bit() );
+	pmc.pmc_usb.write(|w| unsafe { w.bits(PMC_USB_USBS | 9 << PMC_USB_USBDIV_SHIFT) });
+	pmc.pmc_scer.write(|w| unsafe { w.bits(PMC_SCER_USBCLK) });
+}
+
+/// USBHS in device mode
+pub struct UsbBus {
+	usbhs: Mutex<USBHS>,
+	speed: Speed,
+	endpoints: [Option<Endpoint>; ENDPOINTS],
+	memory_used: usize,
+}
+
+impl UsbBus {
+	/// Enables the USB clocks and creates the bus allocator for usb-device
+	///
+	/// Fails if UPLLCK is not enabled.
+	pub fn new(usbhs: USBHS, speed: Speed, clocks: &Clocks, pmc: &mut PMC) -> core::result::Result<UsbBusAllocator<Self>, config::InvalidConfig> {
+		if clocks.upll().0 == 0 {
+			return Err(config::InvalidConfig);
+		}
+
+		enable_clocks(pmc);
+
+		Ok(UsbBusAllocator::new(UsbBus {
+			usbhs: Mutex::new(usbhs),
+			speed,
+			endpoints: [None; ENDPOINTS],
+			memory_used: 0,
+		}))
+	}
+
+	fn regs<R, F: FnOnce(&USBHS) -> R>(&self, f: F) -> R {
+		interrupt::free(|cs| f(self.usbhs.borrow(cs)))
+	}
+
+	/// Allocates and enables all endpoints, must be done in ascending order
+	///
+	/// `alloc_ep` only accepts endpoints that pass the CFGOK check, an endpoint failing it
+	/// anyway stays disabled.
+	fn configure_endpoints(&self, usbhs: &USBHS) {
+		let mut enabled = 0;
+		for (index, ep) in self.endpoints.iter().enumerate() {
+			let ep = match ep {
+				Some(ep) => ep,
+				None => continue,
+			};
+
+			usbhs.usbhs_devept.modify(|r, w| unsafe { w.bits(r.bits() | 1 << index) });
+			usbhs.usbhs_deveptcfg[index].write(|w| unsafe { w.bits(ep.cfg()) });
+			if usbhs.usbhs_deveptisr[index].read().bits() & EPTISR_CFGOK == 0 {
+				usbhs.usbhs_devept.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << index)) });
+				continue;
+			}
+
+			let irqs = if ep.is_control() {
+				EPT_RXSTPI | EPT_RXOUTI
+			} else if ep.out_allocated {
+				EPT_RXOUTI
+			} else {
+				0
+			};
+			usbhs.usbhs_deveptier[index].write(|w| unsafe { w.bits(irqs) });
+			enabled |= 1 << (index as u32 + DEV_PEP_SHIFT);
+		}
+		usbhs.usbhs_devier.write(|w| unsafe { w.bits(enabled | DEV_EORST | DEV_SUSP) });
+	}
+
+	fn endpoint(&self, ep_addr: EndpointAddress) -> Result<&Endpoint> {
+		let ep = self.endpoints.get(ep_addr.index()).and_then(|ep| ep.as_ref()).ok_or(UsbError::InvalidEndpoint)?;
+		let allocated = match ep_addr.direction() {
+			UsbDirection::In => ep.in_allocated,
+			UsbDirection::Out => ep.out_allocated,
+		};
+		if allocated {
+			Ok(ep)
+		} else {
+			Err(UsbError::InvalidEndpoint)
+		}
+	}
+}
+
+impl UsbBusTrait for UsbBus {
+	fn alloc_ep(
+		&mut self,
+		ep_dir: UsbDirection,
+		ep_addr: Option<EndpointAddress>,
+		ep_type: EndpointType,
+		max_packet_size: u16,
+		_interval: u8,
+	) -> Result<EndpointAddress> {
+		// the sizes and the DPRAM usage checked here are the ones the hardware verifies by CFGOK
+		let full_speed = self.speed == Speed::FullSpeed;
+		let (ep_type, max_size) = match ep_type {
+			EndpointType::Control => (Endpoint::CONTROL, 64),
+			EndpointType::Isochronous { .. } => (Endpoint::ISOCHRONOUS, if full_speed { 1023 } else { 1024 }),
+			EndpointType::Bulk => (2, if full_speed { 64 } else { 512 }),
+			EndpointType::Interrupt => (3, if full_speed { 64 } else { 1024 }),
+		};
+		if max_packet_size > max_size {
+			return Err(UsbError::EndpointMemoryOverflow);
+		}
+
+		let range = match ep_addr {
+			Some(addr) => addr.index()..addr.index() + 1,
+			None => 0..ENDPOINTS,
+		};
+		for index in range {
+			// endpoint 0 is reserved for the control endpoint, which has both directions
+			if index >= ENDPOINTS || (index == 0) != (ep_type == Endpoint::CONTROL) {
+				continue;
+			}
+
+			match &mut self.endpoints[index] {
+				Some(ep) => {
+					if !ep.is_control() || ep.max_packet_size != max_packet_size {
+						continue;
+					}
+					let allocated = match ep_dir {
+						UsbDirection::In => &mut ep.in_allocated,
+						UsbDirection::Out => &mut ep.out_allocated,
+					};
+					if *allocated {
+						continue;
+					}
+					*allocated = true;
+				}
+				slot @ None => {
+					let mut size = 0;
+					while (8 << size) < max_packet_size {
+						size += 1;
+					}
+					let ep = Endpoint {
+						ep_type,
+						size,
+						banks: if ep_type == Endpoint::CONTROL { 1 } else { 2 },
+						max_packet_size,
+						in_allocated: ep_dir == UsbDirection::In,
+						out_allocated: ep_dir == UsbDirection::Out,
+					};
+					if self.memory_used + ep.memory() > DPRAM_SIZE {
+						return Err(UsbError::EndpointMemoryOverflow);
+					}
+					self.memory_used += ep.memory();
+					*slot = Some(ep);
+				}
+			}
+
+			return Ok(EndpointAddress::from_parts(index, ep_dir));
+		}
+
+		Err(if ep_addr.is_some() { UsbError::InvalidEndpoint } else { UsbError::EndpointOverflow })
+	}
+
+	fn enable(&mut self) {
+		let speed = self.speed;
+		self.regs(|usbhs| {
+			usbhs.usbhs_ctrl.write(|w| unsafe { w.bits(CTRL_UIMOD_DEVICE | CTRL_USBE | CTRL_VBUSHWC) });
+			while usbhs.usbhs_sr.read().bits() & SR_CLKUSABLE == 0 {
+				//Wait for the UTMI clock
+			}
+
+			let spdconf = match speed {
+				Speed::FullSpeed => DEVCTRL_SPDCONF_FORCED_FS,
+				Speed::HighSpeed => 0,
+			};
+			usbhs.usbhs_devctrl.write(|w| unsafe { w.bits(spdconf | DEVCTRL_DETACH) });
+			self.configure_endpoints(usbhs);
+
+			// attach to the bus
+			usbhs.usbhs_devctrl.write(|w| unsafe { w.bits(spdconf) });
+		});
+	}
+
+	fn reset(&self) {
+		self.regs(|usbhs| {
+			usbhs.usbhs_devctrl.modify(|r, w| unsafe { w.bits(r.bits() & !(DEVCTRL_UADD_MASK | DEVCTRL_ADDEN)) });
+			self.configure_endpoints(usbhs);
+		});
+	}
+
+	fn set_device_address(&self, addr: u8) {
+		self.regs(|usbhs| {
+			// the address has to be written before it is enabled
+			usbhs.usbhs_devctrl.modify(|r, w| unsafe {
+				w.bits(r.bits() & !(DEVCTRL_UADD_MASK | DEVCTRL_ADDEN) | addr as u32 & DEVCTRL_UADD_MASK)
+			});
+			usbhs.usbhs_devctrl.modify(|r, w| unsafe { w.bits(r.bits() | DEVCTRL_ADDEN) });
+		});
+	}
+
+	fn write(&self, ep_addr: EndpointAddress, buf: &[u8]) -> Result<usize> {
+		let ep = self.endpoint(ep_addr)?;
+		if ep_addr.direction() != UsbDirection::In {
+			return Err(UsbError::InvalidEndpoint);
+		}
+		if buf.len() > ep.max_packet_size as usize {
+			return Err(UsbError::BufferOverflow);
+		}
+
+		let index = ep_addr.index();
+		self.regs(|usbhs| {
+			if usbhs.usbhs_deveptisr[index].read().bits() & EPT_TXINI == 0 {
+				return Err(UsbError::WouldBlock);
+			}
+
+			let fifo = fifo(index);
+			for (i, &byte) in buf.iter().enumerate() {
+				// NOTE(unsafe) the bank is owned by the CPU while TXINI is set
+				unsafe { core::ptr::write_volatile(fifo.add(i), byte) };
+			}
+
+			// clearing TXINI sends control packets, other endpoints send the bank with FIFOCON
+			usbhs.usbhs_devepticr[index].write(|w| unsafe { w.bits(EPT_TXINI) });
+			if !ep.is_control() {
+				usbhs.usbhs_deveptidr[index].write(|w| unsafe { w.bits(EPT_FIFOCON) });
+			}
+			// reports the completion in poll
+			usbhs.usbhs_deveptier[index].write(|w| unsafe { w.bits(EPT_TXINI) });
+
+			Ok(buf.len())
+		})
+	}
+
+	fn read(&self, ep_addr: EndpointAddress, buf: &mut [u8]) -> Result<usize> {
+		let ep = self.endpoint(ep_addr)?;
+		if ep_addr.direction() != UsbDirection::Out {
+			return Err(UsbError::InvalidEndpoint);
+		}
+
+		let index = ep_addr.index();
+		self.regs(|usbhs| {
+			let isr = usbhs.usbhs_deveptisr[index].read().bits();
+			let flag = if ep.is_control() && isr & EPT_RXSTPI != 0 {
+				EPT_RXSTPI
+			} else if isr & EPT_RXOUTI != 0 {
+				EPT_RXOUTI
+			} else {
+				return Err(UsbError::WouldBlock);
+			};
+
+			let len = ((isr >> EPTISR_BYCT_SHIFT) & EPTISR_BYCT_MASK) as usize;
+			if len > buf.len() {
+				return Err(UsbError::BufferOverflow);
+			}
+
+			let fifo = fifo(index);
+			for (i, byte) in buf[..len].iter_mut().enumerate() {
+				// NOTE(unsafe) the bank is owned by the CPU while the flag is set
+				*byte = unsafe { core::ptr::read_volatile(fifo.add(i)) };
+			}
+
+			usbhs.usbhs_devepticr[index].write(|w| unsafe { w.bits(flag) });
+			if !ep.is_control() {
+				// frees the bank
+				usbhs.usbhs_deveptidr[index].write(|w| unsafe { w.bits(EPT_FIFOCON) });
+			}
+
+			Ok(len)
+		})
+	}
+
+	fn set_stalled(&self, ep_addr: EndpointAddress, stalled: bool) {
+		let index = ep_addr.index();
+		if index >= ENDPOINTS {
+			return;
+		}
+		self.regs(|usbhs| {
+			if stalled {
+				usbhs.usbhs_deveptier[index].write(|w| unsafe { w.bits(EPT_STALLRQ) });
+			} else {
+				usbhs.usbhs_deveptidr[index].write(|w| unsafe { w.bits(EPT_STALLRQ) });
+				usbhs.usbhs_deveptier[index].write(|w| unsafe { w.bits(EPT_RSTDT) });
+			}
+		});
+	}
+
+	fn is_stalled(&self, ep_addr: EndpointAddress) -> bool {
+		let index = ep_addr.index();
+		index < ENDPOINTS && self.regs(|usbhs| usbhs.usbhs_deveptimr[index].read().bits() & EPT_STALLRQ != 0)
+	}
+
+	fn suspend(&self) {
+		self.regs(|usbhs| {
+			usbhs.usbhs_devicr.write(|w| unsafe { w.bits(DEV_SUSP) });
+			usbhs.usbhs_devidr.write(|w| unsafe { w.bits(DEV_SUSP) });
+			usbhs.usbhs_devier.write(|w| unsafe { w.bits(DEV_WAKEUP | DEV_EORSM) });
+			usbhs.usbhs_ctrl.modify(|r, w| unsafe { w.bits(r.bits() | CTRL_FRZCLK) });
+		});
+	}
+
+	fn resume(&self) {
+		self.regs(|usbhs| {
+			usbhs.usbhs_ctrl.modify(|r, w| unsafe { w.bits(r.bits() & !CTRL_FRZCLK) });
+			while usbhs.usbhs_sr.read().bits() & SR_CLKUSABLE == 0 {
+				//Wait for the UTMI clock
+			}
+			// the wake up flag can only be cleared with a running clock
+			usbhs.usbhs_devicr.write(|w| unsafe { w.bits(DEV_WAKEUP | DEV_EORSM) });
+			usbhs.usbhs_devidr.write(|w| unsafe { w.bits(DEV_WAKEUP | DEV_EORSM) });
+			usbhs.usbhs_devier.write(|w| unsafe { w.bits(DEV_SUSP) });
+		});
+	}
+
+	fn poll(&self) -> PollResult {
+		self.regs(|usbhs| {
+			let isr = usbhs.usbhs_devisr.read().bits();
+			let enabled = isr & usbhs.usbhs_devimr.read().bits();
+
+			if isr & DEV_EORST != 0 {
+				usbhs.usbhs_devicr.write(|w| unsafe { w.bits(DEV_EORST) });
+				return PollResult::Reset;
+			}
+			if enabled & (DEV_WAKEUP | DEV_EORSM) != 0 {
+				return PollResult::Resume;
+			}
+			if enabled & DEV_SUSP != 0 {
+				return PollResult::Suspend;
+			}
+
+			let mut ep_out = 0;
+			let mut ep_in_complete = 0;
+			let mut ep_setup = 0;
+			for (index, ep) in self.endpoints.iter().enumerate() {
+				if ep.is_none() {
+					continue;
+				}
+				let ept = usbhs.usbhs_deveptisr[index].read().bits();
+				if ept & EPT_RXSTPI != 0 {
+					ep_setup |= 1 << index;
+				}
+				if ept & EPT_RXOUTI != 0 {
+					ep_out |= 1 << index;
+				}
+				// TXINI is only enabled after a write, it signals that the bank has been sent
+				if ept & EPT_TXINI != 0 && usbhs.usbhs_deveptimr[index].read().bits() & EPT_TXINI != 0 {
+					usbhs.usbhs_deveptidr[index].write(|w| unsafe { w.bits(EPT_TXINI) });
+					ep_in_complete |= 1 << index;
+				}
+			}
+
+			if ep_out | ep_in_complete | ep_setup != 0 {
+				PollResult::Data { ep_out, ep_in_complete, ep_setup }
+			} else {
+				PollResult::None
+			}
+		})
+	}
+}
@@ -0,0 +1,717 @@
+//! USB High-Speed host (USBHS)
+//!
+//! `UsbHost` powers the bus, enumerates the attached device and performs control, bulk and
+//! interrupt transfers through pipes, so class drivers for mass storage or HID devices can be
+//! built on top of it. The SAM E70 has no VBUS output, the VBUS switch is driven by a GPIO.
+//!
+//! Pipes are allocated in the DPRAM in ascending order, so they can only be freed all at once.
+//! Pipe 0 is always the control pipe of the enumerated device.
+
+use embedded_hal::digital::v2::OutputPin;
+
+use usb_device::UsbDirection;
+
+use crate::target_device::{PMC, USBHS};
+
+use crate::clock_gen::Clocks;
+use crate::usb::{config, enable_clocks, fifo, CTRL_USBE, CTRL_VBUSHWC, DPRAM_SIZE, ENDPOINTS, SR_CLKUSABLE};
+
+/// Number of pipes
+pub const PIPES: usize = ENDPOINTS;
+
+/// Frames to wait for a transfer stage, 1 ms each
+const TIMEOUT_FRAMES: u32 = 500;
+
+// Status register
+const SR_SPEED_SHIFT: u32 = 12;
+// Status set and clear registers
+const SFR_VBUSRQS: u32 = 1 << 9;
+const SCR_VBUSRQC: u32 = 1 << 9;
+
+// Host control register
+const HSTCTRL_SOFE: u32 = 1 << 8;
+const HSTCTRL_RESET: u32 = 1 << 9;
+
+// Host interrupt flags
+const HST_DCONNI: u32 = 1 << 0;
+const HST_DDISCI: u32 = 1 << 1;
+const HST_RSTI: u32 = 1 << 2;
+
+// Host frame number register
+const HSTFNUM_FNUM_SHIFT: u32 = 3;
+const HSTFNUM_FNUM_MASK: u32 = 0x7ff;
+
+// Pipe configuration register
+const PIPCFG_ALLOC: u32 = 1 << 1;
+const PIPCFG_PBK_SHIFT: u32 = 2;
+const PIPCFG_PSIZE_SHIFT: u32 = 4;
+const PIPCFG_PTOKEN_SHIFT: u32 = 8;
+const PIPCFG_PTOKEN_MASK: u32 = 0x3 << PIPCFG_PTOKEN_SHIFT;
+const PIPCFG_PTYPE_SHIFT: u32 = 12;
+const PIPCFG_PEPNUM_SHIFT: u32 = 16;
+const PIPCFG_INTFRQ_SHIFT: u32 = 24;
+
+// Pipe tokens
+const TOKEN_SETUP: u32 = 0;
+const TOKEN_IN: u32 = 1;
+const TOKEN_OUT: u32 = 2;
+
+// Pipe interrupt flags, shared by the status, clear, mask, enable and disable registers
+const PIP_RXINI: u32 = 1 << 0;
+const PIP_TXOUTI: u32 = 1 << 1;
+const PIP_TXSTPI: u32 = 1 << 2;
+const PIP_PERRI: u32 = 1 << 3;
+const PIP_RXSTALLDI: u32 = 1 << 6;
+const PIP_FIFOCON: u32 = 1 << 14;
+const PIP_PFREEZE: u32 = 1 << 17;
+const PIP_RSTDT: u32 = 1 << 18;
+// Pipe status register
+const PIPISR_CFGOK: u32 = 1 << 18;
+const PIPISR_PBYCT_SHIFT: u32 = 20;
+const PIPISR_PBYCT_MASK: u32 = 0x7ff;
+
+// Pipe IN request register, continuous IN requests
+const PIPINRQ_INMODE: u32 = 1 << 8;
+
+// Standard requests
+const REQUEST_CLEAR_FEATURE: u8 = 1;
+const REQUEST_SET_ADDRESS: u8 = 5;
+const REQUEST_GET_DESCRIPTOR: u8 = 6;
+const REQUEST_SET_CONFIGURATION: u8 = 9;
+const FEATURE_ENDPOINT_HALT: u16 = 0;
+const DESCRIPTOR_DEVICE: u8 = 1;
+
+/// Host error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+	/// The device stalled the request
+	Stall,
+	/// CRC, timeout or data toggle error, contains the pipe error register
+	Pipe(u32),
+	/// The device did not respond in time
+	Timeout,
+	/// No device is connected
+	Disconnected,
+	/// All pipes are in use or the DPRAM is full
+	NoPipe,
+	/// The data does not fit into the buffer or packet
+	BufferOverflow,
+	/// The device returned a malformed descriptor
+	InvalidDescriptor,
+	#[doc(hidden)]
+	_Extensible,
+}
+
+/// Connection event
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+	Connected,
+	Disconnected,
+}
+
+impl Event {
+	fn mask(&self) -> u32 {
+		match self {
+			Event::Connected => HST_DCONNI,
+			Event::Disconnected => HST_DDISCI,
+		}
+	}
+}
+
+/// Speed of the attached device
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DeviceSpeed {
+	Low,
+	Full,
+	High,
+}
+
+/// Transfer type of a pipe
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TransferType {
+	Control,
+	Bulk,
+	Interrupt,
+}
+
+impl TransferType {
+	fn bits(&self) -> u32 {
+		match self {
+			TransferType::Control => 0,
+			TransferType::Bulk => 2,
+			TransferType::Interrupt => 3,
+		}
+	}
+}
+
+/// Handle of an allocated pipe
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Pipe(usize);
+
+impl Pipe {
+	/// Control pipe of the enumerated device
+	pub const CONTROL: Pipe = Pipe(0);
+}
+
+/// Setup packet of a control transfer
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SetupPacket {
+	pub request_type: u8,
+	pub request: u8,
+	pub value: u16,
+	pub index: u16,
+	pub length: u16,
+}
+
+impl SetupPacket {
+	/// Request type of standard device to host requests
+	pub const STANDARD_IN: u8 = 0x80;
+	/// Request type of standard host to device requests
+	pub const STANDARD_OUT: u8 = 0x00;
+
+	/// GET_DESCRIPTOR of descriptor `ty`, number `index`
+	pub fn get_descriptor(ty: u8, index: u8, length: u16) -> Self {
+		SetupPacket {
+			request_type: Self::STANDARD_IN,
+			request: REQUEST_GET_DESCRIPTOR,
+			value: (ty as u16) << 8 | index as u16,
+			index: 0,
+			length,
+		}
+	}
+
+	/// SET_CONFIGURATION
+	pub fn set_configuration(configuration: u8) -> Self {
+		SetupPacket {
+			request_type: Self::STANDARD_OUT,
+			request: REQUEST_SET_CONFIGURATION,
+			value: configuration as u16,
+			index: 0,
+			length: 0,
+		}
+	}
+
+	fn to_bytes(&self) -> [u8; 8] {
+		let value = self.value.to_le_bytes();
+		let index = self.index.to_le_bytes();
+		let length = self.length.to_le_bytes();
+		[self.request_type, self.request, value[0], value[1], index[0], index[1], length[0], length[1]]
+	}
+}
+
+/// Device descriptor
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DeviceDescriptor {
+	pub usb_version: u16,
+	pub class: u8,
+	pub subclass: u8,
+	pub protocol: u8,
+	pub max_packet_size0: u8,
+	pub vendor_id: u16,
+	pub product_id: u16,
+	pub device_version: u16,
+	pub manufacturer: u8,
+	pub product: u8,
+	pub serial_number: u8,
+	pub num_configurations: u8,
+}
+
+impl DeviceDescriptor {
+	/// Parses the 18 bytes of a device descriptor
+	pub fn parse(data: &[u8]) -> Result<Self, Error> {
+		if data.len() < 18 || data[0] < 18 || data[1] != DESCRIPTOR_DEVICE {
+			return Err(Error::InvalidDescriptor);
+		}
+		let le16 = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+
+		Ok(DeviceDescriptor {
+			usb_version: le16(2),
+			class: data[4],
+			subclass: data[5],
+			protocol: data[6],
+			max_packet_size0: data[7],
+			vendor_id: le16(8),
+			product_id: le16(10),
+			device_version: le16(12),
+			manufacturer: data[14],
+			product: data[15],
+			serial_number: data[16],
+			num_configurations: data[17],
+		})
+	}
+}
+
+/// An enumerated device
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Device {
+	pub address: u8,
+	pub speed: DeviceSpeed,
+	pub descriptor: DeviceDescriptor,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct PipeConfig {
+	max_packet_size: u16,
+	/// Memory used in the DPRAM
+	memory: usize,
+}
+
+/// USBHS in host mode
+pub struct UsbHost<VBUS> {
+	usbhs: USBHS,
+	vbus: VBUS,
+	pipes: [Option<PipeConfig>; PIPES],
+	memory_used: usize,
+	connected: bool,
+}
+
+impl<VBUS: OutputPin> UsbHost<VBUS> {
+	/// Enables the USBHS in host mode and switches VBUS on
+	///
+	/// Fails if UPLLCK is not enabled.
+	pub fn new(usbhs: USBHS, mut vbus: VBUS, clocks: &Clocks, pmc: &mut PMC) -> Result<Self, config::InvalidConfig> {
+		if clocks.upll().0 == 0 {
+			return Err(config::InvalidConfig);
+		}
+
+		enable_clocks(pmc);
+
+		usbhs.usbhs_ctrl.write(|w| unsafe { w.bits(CTRL_USBE | CTRL_VBUSHWC) });
+		while usbhs.usbhs_sr.read().bits() & SR_CLKUSABLE == 0 {
+			//Wait for the UTMI clock
+		}
+		usbhs.usbhs_hstidr.write(|w| unsafe { w.bits(0xffff_ffff) });
+		usbhs.usbhs_hsticr.write(|w| unsafe { w.bits(HST_DCONNI | HST_DDISCI | HST_RSTI) });
+
+		// connections are only detected while VBUS is requested
+		usbhs.usbhs_sfr.write(|w| unsafe { w.bits(SFR_VBUSRQS) });
+		let _ = vbus.set_high();
+
+		Ok(UsbHost {
+			usbhs,
+			vbus,
+			pipes: [None; PIPES],
+			memory_used: 0,
+			connected: false,
+		})
+	}
+
+	/// Checks for connection changes, frees all pipes on disconnection
+	pub fn poll(&mut self) -> Option<Event> {
+		let isr = self.usbhs.usbhs_hstisr.read().bits();
+		if isr & HST_DDISCI != 0 {
+			self.usbhs.usbhs_hsticr.write(|w| unsafe { w.bits(HST_DDISCI) });
+			self.usbhs.usbhs_hstctrl.write(|w| unsafe { w.bits(0) });
+			self.free_pipes();
+			if self.connected {
+				self.connected = false;
+				return Some(Event::Disconnected);
+			}
+		}
+		if isr & HST_DCONNI != 0 {
+			self.usbhs.usbhs_hsticr.write(|w| unsafe { w.bits(HST_DCONNI) });
+			// frames are needed for the timeouts and the reset
+			self.usbhs.usbhs_hstctrl.write(|w| unsafe { w.bits(HSTCTRL_SOFE) });
+			self.connected = true;
+			return Some(Event::Connected);
+		}
+		None
+	}
+
+	/// Returns if a device is connected
+	pub fn is_connected(&self) -> bool {
+		self.connected
+	}
+
+	/// Starts an interrupt event
+	pub fn listen(&mut self, event: Event) {
+		self.usbhs.usbhs_hstier.write(|w| unsafe { w.bits(event.mask()) });
+	}
+
+	/// Stops an interrupt event
+	pub fn unlisten(&mut self, event: Event) {
+		self.usbhs.usbhs_hstidr.write(|w| unsafe { w.bits(event.mask()) });
+	}
+
+	/// Speed of the connected device, valid after the bus reset
+	pub fn speed(&self) -> DeviceSpeed {
+		match (self.usbhs.usbhs_sr.read().bits() >> SR_SPEED_SHIFT) & 0x3 {
+			1 => DeviceSpeed::High,
+			2 => DeviceSpeed::Low,
+			_ => DeviceSpeed::Full,
+		}
+	}
+
+	fn frame_number(&self) -> u32 {
+		(self.usbhs.usbhs_hstfnum.read().bits() >> HSTFNUM_FNUM_SHIFT) & HSTFNUM_FNUM_MASK
+	}
+
+	/// Blocks for `frames` milliseconds, needs a connected device
+	pub fn wait_frames(&self, frames: u32) {
+		let start = self.frame_number();
+		while (self.frame_number().wrapping_sub(start) & HSTFNUM_FNUM_MASK) < frames {
+			if !self.connected {
+				return;
+			}
+		}
+	}
+
+	/// Resets the bus and assigns `address` to the connected device
+	///
+	/// All pipes are freed, afterwards only the control pipe `Pipe::CONTROL` exists.
+	pub fn enumerate(&mut self, address: u8) -> Result<Device, Error> {
+		if !self.connected {
+			return Err(Error::Disconnected);
+		}
+		self.free_pipes();
+
+		// debounce the connection and reset the device
+		self.wait_frames(100);
+		self.usbhs.usbhs_hstctrl.modify(|r, w| unsafe { w.bits(r.bits() | HSTCTRL_RESET) });
+		let start = self.frame_number();
+		loop {
+			let isr = self.usbhs.usbhs_hstisr.read().bits();
+			if isr & HST_RSTI != 0 {
+				break;
+			}
+			if isr & HST_DDISCI != 0 {
+				self.usbhs.usbhs_hstctrl.modify(|r, w| unsafe { w.bits(r.bits() & !HSTCTRL_RESET) });
+				return Err(Error::Disconnected);
+			}
+			if (self.frame_number().wrapping_sub(start) & HSTFNUM_FNUM_MASK) > TIMEOUT_FRAMES {
+				self.usbhs.usbhs_hstctrl.modify(|r, w| unsafe { w.bits(r.bits() & !HSTCTRL_RESET) });
+				return Err(Error::Timeout);
+			}
+		}
+		self.usbhs.usbhs_hsticr.write(|w| unsafe { w.bits(HST_RSTI) });
+		self.wait_frames(20);
+
+		// the first request only reads the maximum packet size of the control endpoint
+		let mut data = [0; 18];
+		let control = self.alloc_pipe(0, 0, TransferType::Control, UsbDirection::Out, 8, 0)?;
+		self.control_in(control, SetupPacket::get_descriptor(DESCRIPTOR_DEVICE, 0, 8), &mut data[..8])?;
+		let max_packet_size0 = data[7] as u16;
+
+		self.free_pipes();
+		let control = self.alloc_pipe(0, 0, TransferType::Control, UsbDirection::Out, max_packet_size0, 0)?;
+		let set_address = SetupPacket {
+			request_type: SetupPacket::STANDARD_OUT,
+			request: REQUEST_SET_ADDRESS,
+			value: address as u16,
+			index: 0,
+			length: 0,
+		};
+		self.control_out(control, set_address, &[])?;
+		// recovery interval
+		self.wait_frames(2);
+		self.set_pipe_address(control, address);
+
+		let len = self.control_in(control, SetupPacket::get_descriptor(DESCRIPTOR_DEVICE, 0, 18), &mut data)?;
+		let descriptor = DeviceDescriptor::parse(&data[..len])?;
+
+		Ok(Device { address, speed: self.speed(), descriptor })
+	}
+
+	/// Allocates the next pipe for `endpoint` of the device at `address`
+	///
+	/// `interval` is the polling interval of interrupt pipes in frames. IN pipes request data
+	/// continuously, the data waits in the pipe banks until it is read.
+	pub fn alloc_pipe(
+		&mut self,
+		address: u8,
+		endpoint: u8,
+		ty: TransferType,
+		direction: UsbDirection,
+		max_packet_size: u16,
+		interval: u8,
+	) -> Result<Pipe, Error> {
+		let index = self.pipes.iter().position(|p| p.is_none()).ok_or(Error::NoPipe)?;
+		if max_packet_size == 0 || max_packet_size > 1024 {
+			return Err(Error::BufferOverflow);
+		}
+
+		let mut size = 0;
+		while (8 << size) < max_packet_size {
+			size += 1;
+		}
+		let banks = if ty == TransferType::Control { 1 } else { 2 };
+		let memory = (8 << size) * banks;
+		if self.memory_used + memory > DPRAM_SIZE {
+			return Err(Error::NoPipe);
+		}
+
+		let token = match (ty, direction) {
+			(TransferType::Control, _) => TOKEN_SETUP,
+			(_, UsbDirection::In) => TOKEN_IN,
+			(_, UsbDirection::Out) => TOKEN_OUT,
+		};
+		let cfg = PIPCFG_ALLOC
+			| (banks as u32 - 1) << PIPCFG_PBK_SHIFT
+			| size << PIPCFG_PSIZE_SHIFT
+			| token << PIPCFG_PTOKEN_SHIFT
+			| ty.bits() << PIPCFG_PTYPE_SHIFT
+			| ((endpoint & 0xf) as u32) << PIPCFG_PEPNUM_SHIFT
+			| (interval as u32) << PIPCFG_INTFRQ_SHIFT;
+
+		let usbhs = &self.usbhs;
+		usbhs.usbhs_hstpip.modify(|r, w| unsafe { w.bits(r.bits() | 1 << index) });
+		usbhs.usbhs_hstpipcfg[index].write(|w| unsafe { w.bits(cfg) });
+		if usbhs.usbhs_hstpipisr[index].read().bits() & PIPISR_CFGOK == 0 {
+			usbhs.usbhs_hstpip.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << index)) });
+			return Err(Error::NoPipe);
+		}
+
+		self.pipes[index] = Some(PipeConfig { max_packet_size, memory });
+		self.memory_used += memory;
+		let pipe = Pipe(index);
+		self.set_pipe_address(pipe, address);
+
+		if ty != TransferType::Control {
+			if direction == UsbDirection::In {
+				self.usbhs.usbhs_hstpipinrq[index].write(|w| unsafe { w.bits(PIPINRQ_INMODE) });
+			}
+			self.usbhs.usbhs_hstpipidr[index].write(|w| unsafe { w.bits(PIP_PFREEZE) });
+		}
+
+		Ok(pipe)
+	}
+
+	/// Frees all pipes
+	pub fn free_pipes(&mut self) {
+		self.usbhs.usbhs_hstpip.write(|w| unsafe { w.bits(0) });
+		for cfg in self.usbhs.usbhs_hstpipcfg.iter() {
+			cfg.write(|w| unsafe { w.bits(0) });
+		}
+		self.pipes = [None; PIPES];
+		self.memory_used = 0;
+	}
+
+	/// Maximum packet size of `pipe`
+	pub fn max_packet_size(&self, pipe: Pipe) -> u16 {
+		self.pipes[pipe.0].map_or(0, |p| p.max_packet_size)
+	}
+
+	fn set_pipe_address(&mut self, pipe: Pipe, address: u8) {
+		let shift = 8 * (pipe.0 % 4) as u32;
+		let mask = 0x7f << shift;
+		let value = ((address & 0x7f) as u32) << shift;
+		let update = |r: u32| (r & !mask) | value;
+		match pipe.0 / 4 {
+			0 => self.usbhs.usbhs_hstaddr1.modify(|r, w| unsafe { w.bits(update(r.bits())) }),
+			1 => self.usbhs.usbhs_hstaddr2.modify(|r, w| unsafe { w.bits(update(r.bits())) }),
+			_ => self.usbhs.usbhs_hstaddr3.modify(|r, w| unsafe { w.bits(update(r.bits())) }),
+		}
+	}
+
+	fn check_pipe(&self, index: usize) -> Result<u32, Error> {
+		if self.usbhs.usbhs_hstisr.read().bits() & HST_DDISCI != 0 {
+			return Err(Error::Disconnected);
+		}
+
+		let isr = self.usbhs.usbhs_hstpipisr[index].read().bits();
+		if isr & PIP_RXSTALLDI != 0 {
+			self.usbhs.usbhs_hstpipicr[index].write(|w| unsafe { w.bits(PIP_RXSTALLDI) });
+			return Err(Error::Stall);
+		}
+		if isr & PIP_PERRI != 0 {
+			let err = self.usbhs.usbhs_hstpiperr[index].read().bits();
+			self.usbhs.usbhs_hstpiperr[index].write(|w| unsafe { w.bits(0) });
+			return Err(Error::Pipe(err));
+		}
+		Ok(isr)
+	}
+
+	/// Unfreezes a control pipe and waits for `flag`, the pipe is frozen again afterwards
+	fn control_stage(&mut self, index: usize, token: u32, flag: u32) -> Result<u32, Error> {
+		let usbhs = &self.usbhs;
+		usbhs.usbhs_hstpipcfg[index].modify(|r, w| unsafe {
+			w.bits(r.bits() & !PIPCFG_PTOKEN_MASK | token << PIPCFG_PTOKEN_SHIFT)
+		});
+		usbhs.usbhs_hstpipidr[index].write(|w| unsafe { w.bits(PIP_PFREEZE) });
+
+		let start = self.frame_number();
+		let result = loop {
+			match self.check_pipe(index) {
+				Ok(isr) if isr & flag != 0 => break Ok(isr),
+				Ok(_) => {}
+				Err(e) => break Err(e),
+			}
+			if (self.frame_number().wrapping_sub(start) & HSTFNUM_FNUM_MASK) > TIMEOUT_FRAMES {
+				break Err(Error::Timeout);
+			}
+		};
+
+		self.usbhs.usbhs_hstpipier[index].write(|w| unsafe { w.bits(PIP_PFREEZE) });
+		result
+	}
+
+	fn send_setup(&mut self, index: usize, setup: &SetupPacket) -> Result<(), Error> {
+		let fifo = fifo(index);
+		for (i, &byte) in setup.to_bytes().iter().enumerate() {
+			// NOTE(unsafe) the pipe is frozen, the bank is owned by the CPU
+			unsafe { core::ptr::write_volatile(fifo.add(i), byte) };
+		}
+		self.usbhs.usbhs_hstpipicr[index].write(|w| unsafe { w.bits(PIP_TXSTPI) });
+		self.control_stage(index, TOKEN_SETUP, PIP_TXSTPI)?;
+		self.usbhs.usbhs_hstpipicr[index].write(|w| unsafe { w.bits(PIP_TXSTPI) });
+		Ok(())
+	}
+
+	/// Sends the OUT bank, a zero length packet in the status stage
+	fn send_out(&mut self, index: usize) -> Result<(), Error> {
+		self.usbhs.usbhs_hstpipicr[index].write(|w| unsafe { w.bits(PIP_TXOUTI) });
+		self.control_stage(index, TOKEN_OUT, PIP_TXOUTI)?;
+		self.usbhs.usbhs_hstpipicr[index].write(|w| unsafe { w.bits(PIP_TXOUTI) });
+		Ok(())
+	}
+
+	/// Receives the zero length packet of the status stage
+	fn status_in(&mut self, index: usize) -> Result<(), Error> {
+		self.control_stage(index, TOKEN_IN, PIP_RXINI)?;
+		self.usbhs.usbhs_hstpipicr[index].write(|w| unsafe { w.bits(PIP_RXINI) });
+		Ok(())
+	}
+
+	/// Performs a control transfer reading into `buf`, returns the number of received bytes
+	///
+	/// If the data stage does not fit into `buf`, the rest is discarded and the transfer is
+	/// completed before `BufferOverflow` is returned.
+	pub fn control_in(&mut self, pipe: Pipe, setup: SetupPacket, buf: &mut [u8]) -> Result<usize, Error> {
+		let index = pipe.0;
+		let max_packet_size = self.pipes[index].ok_or(Error::NoPipe)?.max_packet_size as usize;
+		let len = core::cmp::min(setup.length as usize, buf.len());
+
+		self.send_setup(index, &setup)?;
+
+		let mut received = 0;
+		while received < setup.length as usize {
+			let isr = self.control_stage(index, TOKEN_IN, PIP_RXINI)?;
+			let count = ((isr >> PIPISR_PBYCT_SHIFT) & PIPISR_PBYCT_MASK) as usize;
+			// bytes beyond `buf` are dropped with the bank
+			let start = core::cmp::min(received, len);
+			let end = core::cmp::min(received + count, len);
+
+			let fifo = fifo(index);
+			for (i, byte) in buf[start..end].iter_mut().enumerate() {
+				// NOTE(unsafe) the bank is owned by the CPU while RXINI is set
+				*byte = unsafe { core::ptr::read_volatile(fifo.add(i)) };
+			}
+			self.usbhs.usbhs_hstpipicr[index].write(|w| unsafe { w.bits(PIP_RXINI) });
+			received += count;
+
+			// a short packet ends the data stage
+			if count < max_packet_size {
+				break;
+			}
+		}
+
+		self.send_out(index)?;
+		if received > len {
+			return Err(Error::BufferOverflow);
+		}
+		Ok(received)
+	}
+
+	/// Performs a control transfer sending `data`
+	pub fn control_out(&mut self, pipe: Pipe, setup: SetupPacket, data: &[u8]) -> Result<(), Error> {
+		let index = pipe.0;
+		let max_packet_size = self.pipes[index].ok_or(Error::NoPipe)?.max_packet_size as usize;
+		if data.len() != setup.length as usize {
+			return Err(Error::BufferOverflow);
+		}
+
+		self.send_setup(index, &setup)?;
+
+		for chunk in data.chunks(max_packet_size) {
+			let fifo = fifo(index);
+			for (i, &byte) in chunk.iter().enumerate() {
+				// NOTE(unsafe) the pipe is frozen, the bank is owned by the CPU
+				unsafe { core::ptr::write_volatile(fifo.add(i), byte) };
+			}
+			self.send_out(index)?;
+		}
+
+		self.status_in(index)
+	}
+
+	/// Clears the halt of the endpoint behind `pipe` and resets its data toggle
+	///
+	/// The request is sent through `control`, the control pipe of the device owning `pipe`.
+	pub fn clear_halt(&mut self, control: Pipe, pipe: Pipe, endpoint_address: u8) -> Result<(), Error> {
+		let setup = SetupPacket {
+			request_type: 0x02,
+			request: REQUEST_CLEAR_FEATURE,
+			value: FEATURE_ENDPOINT_HALT,
+			index: endpoint_address as u16,
+			length: 0,
+		};
+		self.control_out(control, setup, &[])?;
+
+		let index = pipe.0;
+		self.usbhs.usbhs_hstpipier[index].write(|w| unsafe { w.bits(PIP_RSTDT) });
+		self.usbhs.usbhs_hstpipidr[index].write(|w| unsafe { w.bits(PIP_PFREEZE) });
+		Ok(())
+	}
+
+	/// Reads the next packet of a bulk or interrupt IN pipe, returns the number of bytes
+	///
+	/// A stalled pipe stays frozen until the halt is cleared with `clear_halt`.
+	pub fn read(&mut self, pipe: Pipe, buf: &mut [u8]) -> nb::Result<usize, Error> {
+		let index = pipe.0;
+		let isr = self.check_pipe(index)?;
+		if isr & PIP_RXINI == 0 {
+			return Err(nb::Error::WouldBlock);
+		}
+
+		let count = ((isr >> PIPISR_PBYCT_SHIFT) & PIPISR_PBYCT_MASK) as usize;
+		if count > buf.len() {
+			return Err(nb::Error::Other(Error::BufferOverflow));
+		}
+
+		let fifo = fifo(index);
+		for (i, byte) in buf[..count].iter_mut().enumerate() {
+			// NOTE(unsafe) the bank is owned by the CPU while RXINI is set
+			*byte = unsafe { core::ptr::read_volatile(fifo.add(i)) };
+		}
+
+		// frees the bank
+		self.usbhs.usbhs_hstpipicr[index].write(|w| unsafe { w.bits(PIP_RXINI) });
+		self.usbhs.usbhs_hstpipidr[index].write(|w| unsafe { w.bits(PIP_FIFOCON) });
+
+		Ok(count)
+	}
+
+	/// Sends a packet of at most the maximum packet size on a bulk or interrupt OUT pipe
+	pub fn write(&mut self, pipe: Pipe, data: &[u8]) -> nb::Result<usize, Error> {
+		let index = pipe.0;
+		if data.len() > self.max_packet_size(pipe) as usize {
+			return Err(nb::Error::Other(Error::BufferOverflow));
+		}
+		let isr = self.check_pipe(index)?;
+		if isr & PIP_TXOUTI == 0 {
+			return Err(nb::Error::WouldBlock);
+		}
+
+		let fifo = fifo(index);
+		for (i, &byte) in data.iter().enumerate() {
+			// NOTE(unsafe) the bank is owned by the CPU while TXOUTI is set
+			unsafe { core::ptr::write_volatile(fifo.add(i), byte) };
+		}
+
+		// sends the bank
+		self.usbhs.usbhs_hstpipicr[index].write(|w| unsafe { w.bits(PIP_TXOUTI) });
+		self.usbhs.usbhs_hstpipidr[index].write(|w| unsafe { w.bits(PIP_FIFOCON) });
+
+		Ok(data.len())
+	}
+
+	/// Switches VBUS off and releases the USBHS and the VBUS pin
+	pub fn release(mut self) -> (USBHS, VBUS) {
+		self.free_pipes();
+		self.usbhs.usbhs_hstctrl.write(|w| unsafe { w.bits(0) });
+		self.usbhs.usbhs_scr.write(|w| unsafe { w.bits(SCR_VBUSRQC) });
+		self.usbhs.usbhs_ctrl.write(|w| unsafe { w.bits(0) });
+		let _ = self.vbus.set_low();
+		(self.usbhs, self.vbus)
+	}
+}